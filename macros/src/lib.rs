@@ -0,0 +1,274 @@
+//! Procedural macros for swingset vats. Use them through the "macros"
+//! feature of the swingset crate, which re-exports them.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, FnArg, ImplItem, ImplItemFn, ItemImpl, ItemTrait, Path,
+    ReturnType, Signature, TraitItem, Type,
+};
+
+/// `#[far_object]` on an `impl` block implements `swingset::FarObject` for
+/// its type: each method taking `&self` or `&mut self` can be called by
+/// other vats, by name. Arguments arrive marshalled as a list, one element
+/// per parameter. The return value fulfills the message's result promise;
+/// a method returning `Result` rejects it instead when it fails. On an
+/// `impl Trait for Type` block (for a `#[far_interface]` trait), only the
+/// methods written in the block are dispatched.
+#[proc_macro_attribute]
+pub fn far_object(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = TokenStream2::from(attr);
+        return syn::Error::new(attr.span(), "#[far_object] takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let item = parse_macro_input!(item as ItemImpl);
+    match expand(&item) {
+        Ok(dispatch) => quote!(#item #dispatch).into(),
+        Err(e) => {
+            let e = e.to_compile_error();
+            quote!(#item #e).into()
+        }
+    }
+}
+
+fn expand(item: &ItemImpl) -> syn::Result<TokenStream2> {
+    let trait_path = item.trait_.as_ref().map(|(_, path, _)| path);
+    let mut arms = vec![];
+    for impl_item in &item.items {
+        if let ImplItem::Fn(method) = impl_item {
+            if let Some(arm) = method_arm(method, trait_path)? {
+                arms.push(arm);
+            }
+        }
+    }
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::swingset::FarObject for #self_ty #where_clause {
+            fn dispatch(
+                &mut self,
+                method: &str,
+                args: &::swingset::VatCapData,
+            ) -> ::std::result::Result<::swingset::VatCapData, ::swingset::VatCapData> {
+                match method {
+                    #(#arms)*
+                    _ => ::std::result::Result::Err(
+                        ::swingset::__private::no_such_method(method),
+                    ),
+                }
+            }
+        }
+    })
+}
+
+/// the match arm which calls `method`, or None for associated functions
+fn method_arm(
+    method: &ImplItemFn,
+    trait_path: Option<&Path>,
+) -> syn::Result<Option<TokenStream2>> {
+    let sig = &method.sig;
+    if sig.receiver().is_none() {
+        return Ok(None);
+    }
+    check_signature(sig)?;
+    let ident = &sig.ident;
+    let name = ident.to_string();
+    let (bindings, types) = parameters(sig);
+    // a tuple for the arguments, or an empty array when there are none,
+    // since a unit would not accept an empty list
+    let decode = if types.is_empty() {
+        quote! {
+            let _: [(); 0] = ::swingset::__private::decode_args(method, args)?;
+        }
+    } else {
+        quote! {
+            let (#(#bindings,)*): (#(#types,)*) =
+                ::swingset::__private::decode_args(method, args)?;
+        }
+    };
+    let encode = if returns_result(&sig.output) {
+        quote! {
+            match result {
+                ::std::result::Result::Ok(value) => {
+                    ::swingset::__private::encode_result(method, &value)
+                }
+                ::std::result::Result::Err(error) => ::std::result::Result::Err(
+                    ::swingset::__private::method_failed(&error),
+                ),
+            }
+        }
+    } else {
+        quote!(::swingset::__private::encode_result(method, &result))
+    };
+    let call = match trait_path {
+        Some(path) => quote!(#path::#ident(self, #(#bindings),*)),
+        None => quote!(self.#ident(#(#bindings),*)),
+    };
+    Ok(Some(quote! {
+        #name => {
+            #decode
+            let result = #call;
+            #encode
+        }
+    }))
+}
+
+/// far methods take `&self` or `&mut self`, and are neither generic nor
+/// async
+fn check_signature(sig: &Signature) -> syn::Result<()> {
+    match sig.receiver() {
+        Some(receiver) if receiver.reference.is_some() => (),
+        Some(receiver) => {
+            return Err(syn::Error::new(
+                receiver.span(),
+                "far methods take &self or &mut self",
+            ))
+        }
+        None => {
+            return Err(syn::Error::new(
+                sig.span(),
+                "far methods take &self or &mut self",
+            ))
+        }
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "far methods cannot be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "far methods cannot be async",
+        ));
+    }
+    Ok(())
+}
+
+/// a name for each parameter after the receiver, and its type
+fn parameters(sig: &Signature) -> (Vec<syn::Ident>, Vec<&Type>) {
+    let mut bindings = vec![];
+    let mut types = vec![];
+    for (i, input) in sig.inputs.iter().enumerate() {
+        if let FnArg::Typed(pat_type) = input {
+            bindings.push(quote::format_ident!("__arg{}", i));
+            types.push(&*pat_type.ty);
+        }
+    }
+    (bindings, types)
+}
+
+/// whether a method's return type is spelled `Result<..>` (or
+/// `something::Result<..>`)
+fn returns_result(output: &ReturnType) -> bool {
+    result_type(output).is_some()
+}
+
+/// the `T` of a return type spelled `Result<T, E>`
+fn result_type(output: &ReturnType) -> Option<&Type> {
+    let path = match output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(path) => &path.path,
+            _ => return None,
+        },
+        ReturnType::Default => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `#[far_interface]` on a trait declares an interface which far objects
+/// can implement (with `#[far_object] impl Trait for Type`), and generates
+/// the client side: a `TraitRemote` trait, implemented for
+/// `swingset::Remote<dyn Trait>`, with a stub for each method. A stub
+/// takes the method's arguments and returns a `swingset::Call` for its
+/// result, which fails to compile if the method or its arguments are wrong.
+/// A method returning `Result<T, E>` has a `Call<T>`: the error arrives as
+/// a rejection.
+#[proc_macro_attribute]
+pub fn far_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = TokenStream2::from(attr);
+        return syn::Error::new(attr.span(), "#[far_interface] takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let item = parse_macro_input!(item as ItemTrait);
+    match expand_interface(&item) {
+        Ok(client) => quote!(#item #client).into(),
+        Err(e) => {
+            let e = e.to_compile_error();
+            quote!(#item #e).into()
+        }
+    }
+}
+
+fn expand_interface(item: &ItemTrait) -> syn::Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item.generics.span(),
+            "far interfaces cannot be generic",
+        ));
+    }
+    let vis = &item.vis;
+    let ident = &item.ident;
+    let client = quote::format_ident!("{}Remote", ident);
+    let mut decls = vec![];
+    let mut stubs = vec![];
+    for trait_item in &item.items {
+        let sig = match trait_item {
+            TraitItem::Fn(method) => &method.sig,
+            _ => continue,
+        };
+        check_signature(sig)?;
+        let method = &sig.ident;
+        let name = method.to_string();
+        let (bindings, types) = parameters(sig);
+        let result = match (&sig.output, result_type(&sig.output)) {
+            (_, Some(ty)) => quote!(#ty),
+            (ReturnType::Type(_, ty), None) => quote!(#ty),
+            (ReturnType::Default, None) => quote!(()),
+        };
+        let args = if types.is_empty() {
+            quote!(&[(); 0])
+        } else {
+            quote!(&(#(#bindings,)*))
+        };
+        let decl = quote! {
+            fn #method(&self, #(#bindings: #types),*) -> ::swingset::Call<#result>
+        };
+        stubs.push(quote! {
+            #decl {
+                self.call(#name, #args)
+            }
+        });
+        decls.push(decl);
+    }
+    let doc = format!("client stubs for a `Remote<dyn {}>`", ident);
+    Ok(quote! {
+        #[doc = #doc]
+        #vis trait #client {
+            #(#decls;)*
+        }
+
+        impl #client for ::swingset::Remote<dyn #ident> {
+            #(#stubs)*
+        }
+    })
+}
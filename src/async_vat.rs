@@ -0,0 +1,387 @@
+use super::config::Setup;
+use super::dispatch::Dispatch;
+use super::syscall::Syscall;
+use super::vat_types::{
+    InboundVatMessage, OutboundVatMessage, VatCapData, VatExportID, VatPromiseID,
+    VatResolveTarget, VatResolverID, VatSendTarget,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+// An AsyncVat runs its deliveries as futures, on a single-threaded executor
+// which only makes progress inside a crank: each delivery or notification
+// from the kernel wakes whatever was waiting on it, and the executor runs
+// until every task is stuck again. Nothing is polled between cranks, so a
+// vat's behavior depends only on the order of its deliveries.
+
+/// what a promise was fulfilled to: the Err side of a settled promise is
+/// its rejection data
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Fulfillment {
+    Target(VatResolveTarget),
+    Data(VatCapData),
+}
+
+/// the outcome of a promise, or of a delivery's task
+pub type Settlement = Result<Fulfillment, VatCapData>;
+
+/// the future for one delivery, as returned by an AsyncVat's handler
+pub type Task = Pin<Box<dyn Future<Output = Settlement>>>;
+
+type Spawned = Pin<Box<dyn Future<Output = ()>>>;
+
+struct State {
+    syscall: Box<dyn Syscall>,
+    subscribed: HashSet<VatPromiseID>,
+    resolutions: HashMap<VatPromiseID, Settlement>,
+    wakers: HashMap<VatPromiseID, Vec<Waker>>,
+    // messages sent to each promise this vat decides, awaiting resolution
+    pipelined: HashMap<VatResolverID, Vec<InboundVatMessage>>,
+    // work for the executor to pick up
+    deliveries: VecDeque<(VatExportID, InboundVatMessage)>,
+    spawned: VecDeque<Spawned>,
+}
+
+/// VatHandle is how a vat's tasks reach the kernel. It is cheap to clone,
+/// and every task gets one.
+#[derive(Clone)]
+pub struct VatHandle(Rc<RefCell<State>>);
+
+impl VatHandle {
+    /// something this vat can send messages to
+    pub fn far(&self, target: VatSendTarget) -> Far {
+        Far {
+            vat: self.clone(),
+            target,
+        }
+    }
+
+    /// Wait for a promise to be settled, subscribing to it when first
+    /// polled. Only promises this vat does not decide can be waited on.
+    pub fn when(&self, promise: VatPromiseID) -> PromiseFuture {
+        PromiseFuture {
+            vat: self.clone(),
+            promise,
+        }
+    }
+
+    /// make a new promise, for this vat to resolve
+    pub fn allocate_promise(&self) -> (VatPromiseID, VatResolverID) {
+        let mut state = self.0.borrow_mut();
+        let (promise, resolver) = state.syscall.allocate_promise_and_resolver();
+        state.pipelined.insert(resolver, vec![]);
+        (promise, resolver)
+    }
+
+    /// run another task alongside the current one
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.0.borrow_mut().spawned.push_back(Box::pin(task));
+    }
+
+    pub(crate) fn with_syscall<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut dyn Syscall) -> R,
+    {
+        f(&mut *self.0.borrow_mut().syscall)
+    }
+
+    /// Settle a promise this vat decides. Messages already pipelined on it
+    /// are passed along.
+    pub fn resolve(&self, resolver: VatResolverID, settlement: Settlement) {
+        let mut state = self.0.borrow_mut();
+        match &settlement {
+            Ok(Fulfillment::Target(target)) => {
+                state.syscall.fulfill_to_target(resolver, *target)
+            }
+            Ok(Fulfillment::Data(data)) => {
+                state.syscall.fulfill_to_data(resolver, data.clone())
+            }
+            Err(data) => state.syscall.reject(resolver, data.clone()),
+        }
+        for message in state.pipelined.remove(&resolver).unwrap_or_default() {
+            state.redirect(&settlement, message);
+        }
+    }
+}
+
+impl State {
+    /// pass along a message that waited on a promise this vat has now settled
+    fn redirect(&mut self, settlement: &Settlement, message: InboundVatMessage) {
+        match settlement {
+            Ok(Fulfillment::Target(VatResolveTarget::Export(id))) => {
+                self.deliveries.push_back((*id, message))
+            }
+            Ok(Fulfillment::Target(VatResolveTarget::Import(id))) => {
+                let target = VatSendTarget::Import(*id);
+                let vmsg = OutboundVatMessage::new(
+                    &message.name,
+                    &message.args.body,
+                    message.args.slots,
+                );
+                match message.resolver {
+                    Some(r) => {
+                        let p = self.syscall.send(target, vmsg);
+                        self.syscall.forward(r, p);
+                    }
+                    None => self.syscall.send_only(target, vmsg),
+                }
+            }
+            Ok(Fulfillment::Data(_)) => {
+                if let Some(r) = message.resolver {
+                    let data = VatCapData::new(b"cannot send to data", vec![]);
+                    self.syscall.reject(r, data);
+                }
+            }
+            Err(data) => {
+                if let Some(r) = message.resolver {
+                    self.syscall.reject(r, data.clone());
+                }
+            }
+        }
+    }
+
+    // a delivery's result is a promise this vat decides until its task ends
+    fn receive(&mut self, message: &InboundVatMessage) {
+        if let Some(r) = message.resolver {
+            self.pipelined.entry(r).or_default();
+        }
+    }
+
+    fn settle(&mut self, promise: VatPromiseID, settlement: Settlement) {
+        self.resolutions.insert(promise, settlement);
+        for waker in self.wakers.remove(&promise).unwrap_or_default() {
+            waker.wake();
+        }
+    }
+}
+
+/// Far is a remote object or promise, which messages can be sent to with
+/// `e(&far).send(..)`
+#[derive(Clone)]
+pub struct Far {
+    vat: VatHandle,
+    target: VatSendTarget,
+}
+
+impl Far {
+    pub fn target(&self) -> VatSendTarget {
+        self.target
+    }
+}
+
+/// the eventual-send proxy for `far`
+pub fn e(far: &Far) -> E<'_> {
+    E(far)
+}
+
+pub struct E<'a>(&'a Far);
+
+impl<'a> E<'a> {
+    /// send a message, returning a future for its result
+    pub fn send(&self, method: &str, args: VatCapData) -> PromiseFuture {
+        let vmsg = OutboundVatMessage::new(method, &args.body, args.slots);
+        let promise = self.0.vat.with_syscall(|s| s.send(self.0.target, vmsg));
+        self.0.vat.when(promise)
+    }
+
+    /// send a message without asking for its result
+    pub fn send_only(&self, method: &str, args: VatCapData) {
+        let vmsg = OutboundVatMessage::new(method, &args.body, args.slots);
+        self.0
+            .vat
+            .with_syscall(|s| s.send_only(self.0.target, vmsg));
+    }
+}
+
+/// A Future that completes when the kernel notifies this vat of a
+/// promise's settlement. Messages can be pipelined on the promise while
+/// waiting: see `far()`.
+pub struct PromiseFuture {
+    vat: VatHandle,
+    promise: VatPromiseID,
+}
+
+impl PromiseFuture {
+    pub fn promise(&self) -> VatPromiseID {
+        self.promise
+    }
+
+    /// the promise as a target, for pipelining
+    pub fn far(&self) -> Far {
+        self.vat.far(VatSendTarget::Promise(self.promise))
+    }
+}
+
+impl Future for PromiseFuture {
+    type Output = Settlement;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Settlement> {
+        let mut state = self.vat.0.borrow_mut();
+        if let Some(settlement) = state.resolutions.get(&self.promise) {
+            return Poll::Ready(settlement.clone());
+        }
+        state
+            .wakers
+            .entry(self.promise)
+            .or_default()
+            .push(cx.waker().clone());
+        if state.subscribed.insert(self.promise) {
+            state.syscall.subscribe(self.promise);
+        }
+        Poll::Pending
+    }
+}
+
+// wakers only queue their task: it is polled before the crank ends
+struct TaskWaker {
+    id: u64,
+    ready: Arc<Mutex<VecDeque<u64>>>,
+}
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        let mut ready = self.ready.lock().unwrap();
+        if !ready.contains(&self.id) {
+            ready.push_back(self.id);
+        }
+    }
+}
+
+type Handler = dyn FnMut(&VatHandle, VatExportID, InboundVatMessage) -> Task;
+
+/// AsyncVat implements Dispatch by handing each message to `handler`, and
+/// running the Task it returns. When the task finishes, its Settlement
+/// resolves the message's result promise.
+pub struct AsyncVat {
+    vat: VatHandle,
+    handler: Box<Handler>,
+    tasks: HashMap<u64, Spawned>,
+    next_task: u64,
+    ready: Arc<Mutex<VecDeque<u64>>>,
+}
+
+impl AsyncVat {
+    pub fn new<H>(syscall: Box<dyn Syscall>, handler: H) -> Self
+    where
+        H: FnMut(&VatHandle, VatExportID, InboundVatMessage) -> Task + 'static,
+    {
+        let state = State {
+            syscall,
+            subscribed: HashSet::new(),
+            resolutions: HashMap::new(),
+            wakers: HashMap::new(),
+            pipelined: HashMap::new(),
+            deliveries: VecDeque::new(),
+            spawned: VecDeque::new(),
+        };
+        AsyncVat {
+            vat: VatHandle(Rc::new(RefCell::new(state))),
+            handler: Box::new(handler),
+            tasks: HashMap::new(),
+            next_task: 0,
+            ready: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// the Setup for Config::add_vat, for a vat driven by `handler`
+    pub fn setup<H>(handler: H) -> Box<Setup>
+    where
+        H: FnMut(&VatHandle, VatExportID, InboundVatMessage) -> Task + 'static,
+    {
+        Box::new(move |syscall| -> Box<dyn Dispatch> {
+            Box::new(AsyncVat::new(syscall, handler))
+        })
+    }
+
+    fn spawn(&mut self, task: Spawned) {
+        let id = self.next_task;
+        self.next_task += 1;
+        self.tasks.insert(id, task);
+        self.ready.lock().unwrap().push_back(id);
+    }
+
+    fn start(&mut self, target: VatExportID, message: InboundVatMessage) {
+        let resolver = message.resolver;
+        let task = (self.handler)(&self.vat, target, message);
+        let vat = self.vat.clone();
+        self.spawn(Box::pin(async move {
+            let settlement = task.await;
+            if let Some(resolver) = resolver {
+                vat.resolve(resolver, settlement);
+            }
+        }));
+    }
+
+    /// run tasks until none can make progress
+    fn run(&mut self) {
+        loop {
+            let delivery = self.vat.0.borrow_mut().deliveries.pop_front();
+            if let Some((target, message)) = delivery {
+                self.start(target, message);
+                continue;
+            }
+            let spawned = self.vat.0.borrow_mut().spawned.pop_front();
+            if let Some(task) = spawned {
+                self.spawn(task);
+                continue;
+            }
+            let id = match self.ready.lock().unwrap().pop_front() {
+                Some(id) => id,
+                None => return,
+            };
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready: self.ready.clone(),
+            }));
+            let mut cx = Context::from_waker(&waker);
+            let task = self.tasks.get_mut(&id).unwrap();
+            if task.as_mut().poll(&mut cx).is_ready() {
+                self.tasks.remove(&id);
+            }
+        }
+    }
+
+    fn settle(&mut self, promise: VatPromiseID, settlement: Settlement) {
+        self.vat.0.borrow_mut().settle(promise, settlement);
+        self.run();
+    }
+}
+
+impl Dispatch for AsyncVat {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        self.vat.0.borrow_mut().receive(&message);
+        self.start(target, message);
+        self.run();
+    }
+
+    fn deliver_promise(&mut self, target: VatResolverID, message: InboundVatMessage) {
+        {
+            let mut state = self.vat.0.borrow_mut();
+            state.receive(&message);
+            match state.pipelined.get_mut(&target) {
+                Some(messages) => messages.push(message),
+                None => panic!("no unresolved promise for resolver {}", target),
+            }
+        }
+        self.run();
+    }
+
+    fn notify_fulfill_to_target(&mut self, id: VatPromiseID, target: VatResolveTarget) {
+        self.settle(id, Ok(Fulfillment::Target(target)));
+    }
+
+    fn notify_fulfill_to_data(&mut self, id: VatPromiseID, data: VatCapData) {
+        self.settle(id, Ok(Fulfillment::Data(data)));
+    }
+
+    fn notify_reject(&mut self, id: VatPromiseID, data: VatCapData) {
+        self.settle(id, Err(data));
+    }
+}
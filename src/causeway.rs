@@ -0,0 +1,135 @@
+use super::json::Json;
+use super::observer::{KernelEvent, KernelObserver};
+use super::report::{DeliveryKind, DeliveryReport, SyscallReport};
+
+// Causeway (the E debugger) reads a JSON array of events, each anchored in a
+// "turn" of some event "loop". Each crank is a turn of the vat it delivers
+// to, numbered by the crank. Messages queued by the host are anchored in
+// turns of a "host" loop instead.
+
+const HOST_LOOP: &str = "host";
+
+fn class(name: &str) -> Json {
+    Json::from(vec![
+        format!("org.ref_send.log.{}", name),
+        "org.ref_send.log.Event".to_string(),
+    ])
+}
+
+fn call_name(delivery: &DeliveryReport) -> String {
+    match &delivery.method {
+        Some(method) => format!("{}.{}", delivery.target.kref(), method),
+        None => format!("{} {}", delivery.kind.method_name(), delivery.target.kref()),
+    }
+}
+
+/// CausewayTrace is a KernelObserver which records the causal history of
+/// every message, for export in the trace-log format read by the Causeway
+/// debugger. Each delivery is a "Got" of its message, each message queued
+/// during a crank is "Sent" from that crank, and each resolution syscall is
+/// "Fulfilled" or "Rejected", followed by the notifications it queued.
+#[derive(Debug, Default)]
+pub struct CausewayTrace {
+    events: Vec<Json>,
+    crank: Option<u64>,
+    // the loop and number of the current turn, if a crank is in progress
+    turn: Option<(String, u64)>,
+    // the number of events so far in the current turn
+    anchor: u64,
+    host_turns: u64,
+}
+
+impl CausewayTrace {
+    pub fn new() -> Self {
+        CausewayTrace::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// the trace log: a JSON array with one event per line
+    pub fn to_json(&self) -> String {
+        let lines: Vec<String> = self.events.iter().map(|e| e.to_string()).collect();
+        format!("[\n{}\n]\n", lines.join(",\n"))
+    }
+
+    fn event(&mut self, class_name: &str, call: String) -> Json {
+        let (turn_loop, turn_number) = match &self.turn {
+            Some(turn) => turn.clone(),
+            None => {
+                // every host action is a turn of its own
+                self.host_turns += 1;
+                self.anchor = 0;
+                (HOST_LOOP.to_string(), self.host_turns)
+            }
+        };
+        self.anchor += 1;
+        let turn = Json::object()
+            .with("loop", turn_loop.as_str())
+            .with("number", turn_number);
+        let anchor = Json::object()
+            .with("number", self.anchor)
+            .with("turn", turn);
+        let calls = vec![Json::object().with("name", call).with("source", turn_loop)];
+        Json::object()
+            .with("class", class(class_name))
+            .with("anchor", anchor)
+            .with("trace", Json::object().with("calls", calls))
+    }
+}
+
+impl KernelObserver for CausewayTrace {
+    fn observe(&mut self, event: &KernelEvent) {
+        use KernelEvent::*;
+        let entry = match event {
+            CrankStart { crank } => {
+                self.crank = Some(*crank);
+                return;
+            }
+            Delivery { delivery, .. } => {
+                self.turn = Some((delivery.vat.0.clone(), self.crank.unwrap()));
+                self.anchor = 0;
+                self.event("Got", call_name(delivery))
+                    .with("message", delivery.id.to_string())
+            }
+            Queued { delivery } => {
+                let call = call_name(delivery);
+                let sent = match delivery.kind {
+                    DeliveryKind::Deliver | DeliveryKind::DeliverPromise => {
+                        self.event("Sent", call)
+                    }
+                    _ => self
+                        .event("SentIf", call)
+                        .with("condition", delivery.target.kref()),
+                };
+                sent.with("message", delivery.id.to_string())
+            }
+            Syscall { syscall, .. } => {
+                use SyscallReport::*;
+                let (class_name, promise) = match syscall {
+                    FulfillToTarget { promise, .. } | FulfillToData { promise, .. } => {
+                        ("Fulfilled", promise)
+                    }
+                    Reject { promise, .. } => ("Rejected", promise),
+                    Forward { promise, .. } => ("Progressed", promise),
+                    _ => return,
+                };
+                let call = format!("{} {}", syscall.name(), promise.kref());
+                self.event(class_name, call)
+                    .with("condition", promise.kref())
+            }
+            CrankFinish { .. } => {
+                self.crank = None;
+                self.turn = None;
+                return;
+            }
+            _ => return,
+        };
+        self.events.push(entry);
+    }
+}
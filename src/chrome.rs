@@ -0,0 +1,166 @@
+use super::json::Json;
+use super::observer::{KernelEvent, KernelObserver};
+use super::report::{DeliveryReport, MessageID};
+use super::slog::{Clock, SystemClock};
+use std::collections::HashMap;
+
+// Everything happens in one process. Each vat gets a thread (a track in
+// the viewer), numbered in the order the vats were created, and the host
+// gets thread 0, where its messages start.
+
+const PID: u32 = 1;
+const HOST_TID: u32 = 0;
+
+fn micros(seconds: f64) -> f64 {
+    (seconds * 1_000_000.0).round()
+}
+
+fn span_name(delivery: &DeliveryReport) -> String {
+    match &delivery.method {
+        Some(method) => method.clone(),
+        None => delivery.kind.method_name().to_string(),
+    }
+}
+
+fn thread_name(tid: u32, name: &str) -> Json {
+    Json::object()
+        .with("name", "thread_name")
+        .with("ph", "M")
+        .with("pid", PID)
+        .with("tid", tid)
+        .with("args", Json::object().with("name", name))
+}
+
+fn flow(ph: &str, id: MessageID, ts: f64, tid: u32) -> Json {
+    let event = Json::object()
+        .with("name", "message")
+        .with("cat", "flow")
+        .with("ph", ph)
+        .with("id", id.0)
+        .with("ts", ts)
+        .with("pid", PID)
+        .with("tid", tid);
+    match ph {
+        // attach the arrow to the delivery span which starts here
+        "f" => event.with("bp", "e"),
+        _ => event,
+    }
+}
+
+/// ChromeTrace is a KernelObserver which records a timeline of the kernel
+/// in the Chrome trace-event format, for viewing in Perfetto or
+/// chrome://tracing. Each vat has its own track, with a span for each
+/// delivery it received, and flow arrows lead from each send (or
+/// resolution) to the delivery it caused.
+pub struct ChromeTrace {
+    clock: Box<dyn Clock>,
+    events: Vec<Json>,
+    tids: HashMap<String, u32>,
+    // the delivery in progress: its report, track, and start time
+    current: Option<(DeliveryReport, u32, f64)>,
+}
+
+impl Default for ChromeTrace {
+    fn default() -> Self {
+        ChromeTrace::new()
+    }
+}
+
+impl ChromeTrace {
+    pub fn new() -> Self {
+        ChromeTrace::with_clock(Box::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Box<dyn Clock>) -> Self {
+        let process_name = Json::object()
+            .with("name", "process_name")
+            .with("ph", "M")
+            .with("pid", PID)
+            .with("args", Json::object().with("name", "swingset"));
+        ChromeTrace {
+            clock,
+            events: vec![process_name, thread_name(HOST_TID, "host")],
+            tids: HashMap::new(),
+            current: None,
+        }
+    }
+
+    /// the trace as a JSON object, ready to be loaded by the viewer
+    pub fn to_json(&self) -> String {
+        let events: Vec<String> = self.events.iter().map(|e| e.to_string()).collect();
+        format!(
+            "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+            events.join(",\n")
+        )
+    }
+
+    fn tid(&mut self, vat: &str) -> u32 {
+        if let Some(tid) = self.tids.get(vat) {
+            return *tid;
+        }
+        let tid = self.tids.len() as u32 + 1;
+        self.tids.insert(vat.to_string(), tid);
+        self.events.push(thread_name(tid, &format!("vat-{}", vat)));
+        tid
+    }
+}
+
+impl KernelObserver for ChromeTrace {
+    fn observe(&mut self, event: &KernelEvent) {
+        use KernelEvent::*;
+        match event {
+            VatCreated { vat } => {
+                self.tid(&vat.0);
+            }
+            Delivery { delivery, .. } => {
+                let tid = self.tid(&delivery.vat.0);
+                let ts = micros(self.clock.now());
+                self.events.push(flow("f", delivery.id, ts, tid));
+                self.current = Some((delivery.clone(), tid, ts));
+            }
+            Queued { delivery } => {
+                let ts = micros(self.clock.now());
+                let tid = match &self.current {
+                    Some((_, tid, _)) => *tid,
+                    None => {
+                        // flows must start inside a span, so give each
+                        // host-queued message an empty one
+                        self.events.push(
+                            Json::object()
+                                .with("name", span_name(delivery))
+                                .with("cat", "queue")
+                                .with("ph", "X")
+                                .with("ts", ts)
+                                .with("dur", 0.0)
+                                .with("pid", PID)
+                                .with("tid", HOST_TID),
+                        );
+                        HOST_TID
+                    }
+                };
+                self.events.push(flow("s", delivery.id, ts, tid));
+            }
+            CrankFinish { report } => {
+                let (delivery, tid, ts) = self.current.take().unwrap();
+                let dur = micros(self.clock.now()) - ts;
+                let args = Json::object()
+                    .with("crankNum", report.crank)
+                    .with("msgID", delivery.id.to_string())
+                    .with("target", delivery.target.kref())
+                    .with("syscalls", report.syscalls.len());
+                self.events.push(
+                    Json::object()
+                        .with("name", span_name(&delivery))
+                        .with("cat", delivery.kind.method_name())
+                        .with("ph", "X")
+                        .with("ts", ts)
+                        .with("dur", dur)
+                        .with("pid", PID)
+                        .with("tid", tid)
+                        .with("args", args),
+                );
+            }
+            _ => (),
+        }
+    }
+}
@@ -70,10 +70,10 @@ impl<KT: CListKernelEntry, VT: CListVatEntry> CList<KT, VT> {
     }
 
     pub fn add(&mut self, kernel_object: KT, vat_object: VT) {
-        if self.inbound.get(&kernel_object).is_some() {
+        if self.inbound.contains_key(&kernel_object) {
             panic!("already present");
         }
-        if self.outbound.get(&vat_object).is_some() {
+        if self.outbound.contains_key(&vat_object) {
             panic!("already present");
         }
         self.inbound.insert(kernel_object, vat_object);
@@ -143,5 +143,4 @@ mod test {
 
         c.add(KType(102), v1);
     }
-
 }
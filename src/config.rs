@@ -1,18 +1,27 @@
 use super::dispatch::Dispatch;
-use super::kernel_types::VatName;
+use super::guard::Interface;
+use super::kernel_types::{DeviceName, VatName};
+use super::observer::KernelObserver;
 use super::syscall::Syscall;
-use std::collections::HashMap;
+use super::vat_types::VatCapData;
+use std::collections::BTreeMap;
 
-/*#[derive(PartialEq, Eq, Debug, Hash)]
-pub struct DeviceName(pub String);
-#[derive(Debug)]
-pub struct DeviceSetup(pub Fn(impl Syscall) -> impl Dispatch);*/
+pub type Setup = dyn FnOnce(Box<dyn Syscall>) -> Box<dyn Dispatch>;
+
+/// A bridge handler is a host-side callback, invoked synchronously when a
+/// vat does syscall.invoke() on the bridge device. It receives the method
+/// name and the (slot-free) arguments, and returns the (slot-free) result.
+pub type BridgeHandler = dyn Fn(&str, VatCapData) -> VatCapData;
 
-pub type Setup = FnOnce(Box<dyn Syscall>) -> Box<dyn Dispatch>;
 #[derive(Default)]
 pub struct Config {
-    pub(crate) vats: HashMap<VatName, Box<Setup>>,
-    //devices: HashMap<DeviceName, DeviceSetup>,
+    pub(crate) vats: BTreeMap<VatName, Box<Setup>>,
+    pub(crate) bridges: BTreeMap<DeviceName, Box<BridgeHandler>>,
+    pub(crate) observers: Vec<Box<dyn KernelObserver>>,
+    pub(crate) check_invariants: bool,
+    pub(crate) chaos_seed: Option<u64>,
+    pub(crate) check_e_order: bool,
+    pub(crate) guards: BTreeMap<(VatName, u32), Interface>,
 }
 impl Config {
     pub fn new() -> Self {
@@ -22,4 +31,37 @@ impl Config {
         let vn = VatName(name.0.clone());
         self.vats.insert(vn, setup);
     }
+    pub fn add_bridge(&mut self, name: &DeviceName, handler: Box<BridgeHandler>) {
+        let dn = DeviceName(name.0.clone());
+        self.bridges.insert(dn, handler);
+    }
+    /// observers added here will also see the VatCreated events
+    pub fn add_observer(&mut self, observer: Box<dyn KernelObserver>) {
+        self.observers.push(observer);
+    }
+    /// check the kernel tables after every crank, panicking with a list of
+    /// the problems as soon as one is found. This is slow: it is meant for
+    /// tests and debugging.
+    pub fn check_invariants(&mut self, enable: bool) {
+        self.check_invariants = enable;
+    }
+    /// Instead of delivering the run queue in order, pick each delivery at
+    /// random (from `seed`) among those the kernel's ordering guarantees
+    /// allow: messages sent through one reference still arrive in order, and
+    /// pipelined messages after the message whose result they target. Use
+    /// this to find vats which depend on more than that.
+    pub fn chaos_scheduler(&mut self, seed: u64) {
+        self.chaos_seed = Some(seed);
+    }
+    /// check every delivery against the kernel's ordering guarantees,
+    /// panicking at the first that breaks them
+    pub fn check_e_order(&mut self, enable: bool) {
+        self.check_e_order = enable;
+    }
+    /// refuse messages to export `export` of `vat` which do not match
+    /// `interface`, rejecting their results instead of delivering them
+    pub fn guard_export(&mut self, vat: &VatName, export: u32, interface: Interface) {
+        let vn = VatName(vat.0.clone());
+        self.guards.insert((vn, export), interface);
+    }
 }
@@ -1,7 +1,21 @@
 //use std::fmt::Debug;
 use super::config::Config;
+use super::device::BridgeError;
+use super::host::{
+    CrankWaker, HostCapData, KernelExportHandle, KernelPromiseFuture,
+    KernelPromiseHandle, PromiseStatus,
+};
 use super::kernel::Kernel;
-use super::kernel_types::{KernelCapData, KernelExportID, KernelMessage, VatName};
+use super::kernel_types::{
+    DeviceName, KernelCapData, KernelExportID, KernelMessage, VatName,
+};
+use super::metrics::KernelMetrics;
+use super::observer::KernelObserver;
+use super::report::{CrankReport, RunSummary};
+use super::snapshot::KernelSnapshotView;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
 
 //#[derive(Debug)]
 pub struct Controller {
@@ -24,6 +38,10 @@ impl Controller {
         self.kernel.add_import(for_vat, for_id, to_vat, to_id);
     }
 
+    pub fn add_device(&mut self, for_vat: &VatName, for_id: u32, device: &DeviceName) {
+        self.kernel.add_device(for_vat, for_id, device);
+    }
+
     pub fn start(&mut self) {
         self.kernel.push(
             &VatName("bootstrap".to_string()),
@@ -54,14 +72,156 @@ impl Controller {
         );
     }
 
-    pub fn step(&mut self) {
-        println!("controller.step");
-        self.kernel.step();
+    /// queue a host-to-vat message on a bridge device, for delivery to the
+    /// inbound handler that its vat registered
+    pub fn bridge_inbound(
+        &mut self,
+        bridge: &str,
+        method: &str,
+        args_body: &[u8],
+    ) -> Result<(), BridgeError> {
+        self.kernel.bridge_inbound(
+            &DeviceName(bridge.to_string()),
+            KernelMessage {
+                name: method.to_string(),
+                args: KernelCapData {
+                    body: args_body.to_vec(),
+                    slots: vec![],
+                },
+                resolver: None,
+            },
+        )
+    }
+
+    /// get a host-side handle on an object exported by a vat
+    pub fn export_handle(&self, vat_name: &str, id: u32) -> KernelExportHandle {
+        let ke = self
+            .kernel
+            .export(&VatName(vat_name.to_string()), KernelExportID(id));
+        KernelExportHandle(ke)
+    }
+
+    /// queue a message to a vat export, like push(), but with slots in the
+    /// arguments and a result promise that the host can watch with
+    /// promise_status()
+    pub fn queue_to_export(
+        &mut self,
+        target: KernelExportHandle,
+        method: &str,
+        args: HostCapData,
+    ) -> KernelPromiseHandle {
+        let kprid = self.kernel.queue_to_export(target.0, method, args.into());
+        KernelPromiseHandle(kprid)
+    }
+
+    pub fn promise_status(&self, handle: KernelPromiseHandle) -> PromiseStatus {
+        self.kernel.promise_status(handle.0)
+    }
+
+    /// get a Future which completes when the kernel resolves this promise
+    pub fn promise_future(&self, handle: KernelPromiseHandle) -> KernelPromiseFuture {
+        self.kernel.promise_future(handle.0)
+    }
+
+    /// Run cranks until the given future completes, polling it whenever it
+    /// has been woken. Returns None if the run queue drains first, since
+    /// nothing else can make progress.
+    pub fn run_until_complete<F: Future>(&mut self, fut: F) -> Option<F::Output> {
+        let mut fut = Box::pin(fut);
+        let cw = Arc::new(CrankWaker::default());
+        let waker = Waker::from(cw.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut woken = true;
+        loop {
+            if woken {
+                if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                    return Some(output);
+                }
+            }
+            if !self.kernel.has_work() {
+                return None;
+            }
+            self.step();
+            woken = cw.take();
+        }
+    }
+
+    pub fn step(&mut self) -> Option<CrankReport> {
+        self.kernel.step()
+    }
+
+    /// Deliver the run-queue entry at `index` (as listed by dump_state)
+    /// instead of the one the scheduler would pick, to try out an ordering
+    /// by hand. An entry that would break E-order by going next is refused,
+    /// and nothing is delivered.
+    pub fn step_at(&mut self, index: usize) -> Option<CrankReport> {
+        self.kernel.step_at(index)
+    }
+
+    pub fn run(&mut self) -> RunSummary {
+        self.kernel.run()
+    }
+
+    /// run at most `max_cranks` cranks, so vats which ping-pong forever
+    /// cannot hang the host
+    pub fn run_with_limit(&mut self, max_cranks: u64) -> RunSummary {
+        self.kernel.run_with_limit(max_cranks)
+    }
+
+    /// run until `done` returns true for a crank's report, or the run queue
+    /// is empty
+    pub fn run_until<F>(&mut self, done: F) -> RunSummary
+    where
+        F: FnMut(&CrankReport) -> bool,
+    {
+        self.kernel.run_until(done)
+    }
+
+    /// run cranks until `budget` computrons have been spent, returning the
+    /// summary and whatever budget is left over
+    pub fn run_block(&mut self, budget: u64) -> (RunSummary, u64) {
+        self.kernel.run_block(budget)
+    }
+
+    /// terminate every vat, telling observers, and drop the kernel
+    pub fn shutdown(self) {
+        self.kernel.shutdown();
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn KernelObserver>) {
+        self.kernel.add_observer(observer);
+    }
+
+    /// a sorted copy of the kernel's vats, clists, promises, and run queue,
+    /// for debugging tools and tests
+    pub fn dump_state(&self) -> KernelSnapshotView {
+        self.kernel.dump_state()
+    }
+
+    /// the kernel's counters (deliveries, syscalls, crank durations) and
+    /// gauges (run-queue length, unresolved promises, clist sizes)
+    pub fn metrics(&self) -> KernelMetrics {
+        self.kernel.metrics()
+    }
+
+    /// Check the kernel tables for consistency: clists map both ways,
+    /// every promise they (or the run queue) mention exists, promises are
+    /// decided by and subscribed to by live vats, and only the decider holds
+    /// an unresolved promise's resolver. Returns a description of each
+    /// problem found.
+    pub fn check_invariants(&self) -> Result<(), Vec<String>> {
+        let problems = self.kernel.check_invariants();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
     }
 
-    pub fn run(&mut self) {
-        println!("controller.run");
-        self.kernel.run();
+    /// the capability graph (vats, exports, imports, promises, and queued
+    /// messages) in Graphviz DOT format
+    pub fn export_dot(&self) -> String {
+        self.kernel.dump_state().to_dot()
     }
 
     pub fn dump(&self) {
@@ -0,0 +1,40 @@
+use super::config::BridgeHandler;
+use super::kernel_types::{DeviceName, KernelExport};
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+
+/// A vat registers the export that should receive host-to-vat bridge
+/// traffic by invoking the bridge device with this method name, and a
+/// single slot pointing at that export.
+pub(crate) const REGISTER_INBOUND_HANDLER: &str = "registerInboundHandler";
+
+pub(crate) struct DeviceData {
+    pub(crate) name: DeviceName,
+    // held in an Rc so syscall.invoke can call it without keeping
+    // KernelData borrowed
+    pub(crate) handler: Rc<BridgeHandler>,
+    pub(crate) inbound_handler: Option<KernelExport>,
+}
+
+/// why the host could not queue a message on a bridge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BridgeError {
+    /// no bridge device was configured under this name
+    UnknownBridge(DeviceName),
+    /// no vat has registered an inbound handler with this bridge yet
+    NoInboundHandler(DeviceName),
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BridgeError::UnknownBridge(name) => write!(f, "no bridge named {}", name),
+            BridgeError::NoInboundHandler(name) => {
+                write!(f, "{} has no inbound handler registered", name)
+            }
+        }
+    }
+}
+
+impl error::Error for BridgeError {}
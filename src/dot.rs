@@ -0,0 +1,196 @@
+use super::host::{HostArgSlot, HostCapData, PromiseStatus};
+use super::kernel_types::VatName;
+use super::snapshot::KernelSnapshotView;
+use std::fmt::Write;
+
+// Node names are the kernel-reference strings ("ko1.2", "kp3") for exports
+// and promises, "vat-NAME" and "dev-NAME" for vats and devices, and "qN"
+// for the Nth entry of the run queue.
+
+fn quote(s: &str) -> String {
+    let s = s.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", s.replace('\n', "\\n"))
+}
+
+fn vat_node(name: &VatName) -> String {
+    quote(&name.to_string())
+}
+
+fn slot_node(slot: &HostArgSlot) -> String {
+    quote(&slot.kref())
+}
+
+fn data_edges(out: &mut String, from: &str, data: &HostCapData, label: &str) {
+    for slot in &data.slots {
+        writeln!(
+            out,
+            "  {} -> {} [label={}, style=dotted];",
+            from,
+            slot_node(slot),
+            quote(label)
+        )
+        .unwrap();
+    }
+}
+
+impl KernelSnapshotView {
+    /// Render the capability graph as Graphviz DOT. Each vat is a cluster
+    /// holding the vat itself and its exports, with an edge from every vat
+    /// that imports an export. Promises point at their decider, and are
+    /// pointed at by the vats which hold or subscribe to them. Queued
+    /// messages point at their targets.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph kernel {{").unwrap();
+        writeln!(out, "  rankdir=LR;").unwrap();
+
+        for v in &self.vats {
+            writeln!(
+                out,
+                "  subgraph {} {{",
+                quote(&format!("cluster_{}", v.name.0))
+            )
+            .unwrap();
+            writeln!(out, "    label={};", vat_node(&v.name)).unwrap();
+            writeln!(out, "    {} [shape=box];", vat_node(&v.name)).unwrap();
+            for (veid, ke) in &v.exports {
+                let label = format!("{}\nexport {}", ke.kref(), veid.0);
+                writeln!(out, "    {} [label={}];", quote(&ke.kref()), quote(&label))
+                    .unwrap();
+            }
+            writeln!(out, "  }}").unwrap();
+        }
+
+        for v in &self.vats {
+            let from = vat_node(&v.name);
+            for (viid, ke) in &v.imports {
+                let label = format!("import {}", viid.0);
+                writeln!(
+                    out,
+                    "  {} -> {} [label={}];",
+                    from,
+                    quote(&ke.kref()),
+                    quote(&label)
+                )
+                .unwrap();
+            }
+            for (vpid, kp) in &v.promises {
+                let label = format!("promise {}", vpid.0);
+                writeln!(
+                    out,
+                    "  {} -> {} [label={}, style=dashed];",
+                    from,
+                    quote(&kp.kref()),
+                    quote(&label)
+                )
+                .unwrap();
+            }
+            for (vdid, dn) in &v.devices {
+                let dev = quote(&dn.to_string());
+                writeln!(out, "  {} [shape=component];", dev).unwrap();
+                let label = format!("device {}", vdid.0);
+                writeln!(out, "  {} -> {} [label={}];", from, dev, quote(&label))
+                    .unwrap();
+            }
+        }
+
+        for p in &self.promises {
+            let node = quote(&p.promise.kref());
+            let state = match &p.status {
+                PromiseStatus::Unresolved => "unresolved",
+                PromiseStatus::FulfilledToTarget(_) => "fulfilled",
+                PromiseStatus::FulfilledToData(_) => "fulfilled to data",
+                PromiseStatus::Rejected(_) => "rejected",
+            };
+            let label = format!("{}\n{}", p.promise.kref(), state);
+            writeln!(out, "  {} [shape=diamond, label={}];", node, quote(&label))
+                .unwrap();
+            if let Some(decider) = &p.decider {
+                writeln!(
+                    out,
+                    "  {} -> {} [label=\"decider\", style=bold];",
+                    node,
+                    vat_node(decider)
+                )
+                .unwrap();
+            }
+            for s in &p.subscribers {
+                writeln!(
+                    out,
+                    "  {} -> {} [label=\"subscribes\", style=dashed, color=gray];",
+                    vat_node(s),
+                    node
+                )
+                .unwrap();
+            }
+            match &p.status {
+                PromiseStatus::Unresolved => (),
+                PromiseStatus::FulfilledToTarget(ke) => {
+                    writeln!(
+                        out,
+                        "  {} -> {} [label=\"fulfilled\"];",
+                        node,
+                        quote(&ke.kref())
+                    )
+                    .unwrap();
+                }
+                PromiseStatus::FulfilledToData(data) | PromiseStatus::Rejected(data) => {
+                    data_edges(&mut out, &node, data, "data");
+                }
+            }
+        }
+        for (old, new) in &self.forwarded {
+            let node = quote(&old.kref());
+            let label = format!("{}\nforwarded", old.kref());
+            writeln!(
+                out,
+                "  {} [shape=diamond, style=dashed, label={}];",
+                node,
+                quote(&label)
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "  {} -> {} [label=\"forwarded\"];",
+                node,
+                quote(&new.kref())
+            )
+            .unwrap();
+        }
+
+        for (i, q) in self.run_queue.iter().enumerate() {
+            let node = quote(&format!("q{}", i));
+            let mut label = format!("q{}: {}", i, q.delivery.kind.method_name());
+            if let Some(method) = &q.delivery.method {
+                write!(label, " {}", method).unwrap();
+            }
+            writeln!(
+                out,
+                "  {} [shape=note, color=blue, label={}];",
+                node,
+                quote(&label)
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "  {} -> {} [color=blue];",
+                node,
+                slot_node(&q.delivery.target)
+            )
+            .unwrap();
+            data_edges(&mut out, &node, &q.args, "arg");
+            if let Some(result) = q.result {
+                writeln!(
+                    out,
+                    "  {} -> {} [label=\"result\", style=dashed, color=blue];",
+                    node,
+                    quote(&result.kref())
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
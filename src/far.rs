@@ -0,0 +1,110 @@
+use super::config::Setup;
+use super::dispatch::Dispatch;
+use super::marshal::{serialize, Value};
+use super::syscall::Syscall;
+use super::typed::{from_capdata, to_capdata};
+use super::vat_types::{
+    InboundVatMessage, VatCapData, VatExportID, VatPromiseID, VatResolveTarget,
+    VatResolverID,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+
+/// FarObject is an object whose methods other vats can call by name,
+/// usually implemented with `#[far_object]`
+pub trait FarObject {
+    /// Call `method` with the marshalled `args`. Ok is the marshalled
+    /// return value, Err the marshalled reason the call failed.
+    fn dispatch(
+        &mut self,
+        method: &str,
+        args: &VatCapData,
+    ) -> Result<VatCapData, VatCapData>;
+}
+
+/// FarVat implements Dispatch for a vat holding a single FarObject, as
+/// export 0. Each message calls a method, and its result promise is settled
+/// with what the method returned before the delivery ends.
+pub struct FarVat {
+    syscall: Box<dyn Syscall>,
+    root: Box<dyn FarObject>,
+}
+
+impl FarVat {
+    pub fn new(syscall: Box<dyn Syscall>, root: Box<dyn FarObject>) -> Self {
+        FarVat { syscall, root }
+    }
+
+    /// the Setup for Config::add_vat
+    pub fn setup<T: FarObject + 'static>(root: T) -> Box<Setup> {
+        Box::new(move |syscall| -> Box<dyn Dispatch> {
+            Box::new(FarVat::new(syscall, Box::new(root)))
+        })
+    }
+}
+
+impl Dispatch for FarVat {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(target, VatExportID(0), "FarVat only exports its root");
+        let result = self.root.dispatch(&message.name, &message.args);
+        if let Some(resolver) = message.resolver {
+            match result {
+                Ok(data) => self.syscall.fulfill_to_data(resolver, data),
+                Err(data) => self.syscall.reject(resolver, data),
+            }
+        }
+    }
+
+    // Results are always settled to data during their delivery, so anything
+    // pipelined on one can only be rejected.
+    fn deliver_promise(&mut self, _target: VatResolverID, message: InboundVatMessage) {
+        if let Some(resolver) = message.resolver {
+            let error = Value::error("TypeError", "cannot send to data");
+            self.syscall.reject(resolver, serialize(&error));
+        }
+    }
+
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!("FarVat does not subscribe to promises");
+    }
+
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!("FarVat does not subscribe to promises");
+    }
+
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!("FarVat does not subscribe to promises");
+    }
+}
+
+// The rest is used by the code #[far_object] generates. Failures become
+// marshalled Errors, which JavaScript vats would see as thrown exceptions.
+
+fn error(name: &str, message: String) -> VatCapData {
+    serialize(&Value::error(name, &message))
+}
+
+pub fn decode_args<T: DeserializeOwned>(
+    method: &str,
+    args: &VatCapData,
+) -> Result<T, VatCapData> {
+    from_capdata(args)
+        .map_err(|e| error("TypeError", format!("bad arguments to {}: {}", method, e)))
+}
+
+pub fn encode_result<T: Serialize + ?Sized>(
+    method: &str,
+    value: &T,
+) -> Result<VatCapData, VatCapData> {
+    to_capdata(value)
+        .map_err(|e| error("TypeError", format!("cannot return from {}: {}", method, e)))
+}
+
+pub fn no_such_method(method: &str) -> VatCapData {
+    error("TypeError", format!("no such method {:?}", method))
+}
+
+pub fn method_failed<E: fmt::Display + ?Sized>(e: &E) -> VatCapData {
+    error("Error", e.to_string())
+}
@@ -0,0 +1,196 @@
+use super::controller::Controller;
+use super::report::{Cause, CrankReport, RunSummary, SyscallReport};
+use super::snapshot::KernelSnapshotView;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// set this (to anything) to rewrite golden files instead of comparing
+/// against them
+pub const UPDATE_GOLDEN: &str = "SWINGSET_UPDATE_GOLDEN";
+
+fn syscall(s: &SyscallReport) -> String {
+    use SyscallReport::*;
+    match s {
+        Send {
+            target,
+            method,
+            args,
+            result,
+        } => {
+            let mut line =
+                format!("{} {}.{} {}", s.name(), target.kref(), method, args.krefs());
+            if let Some(result) = result {
+                write!(line, " -> {}", result.kref()).unwrap();
+            }
+            line
+        }
+        Invoke {
+            device,
+            method,
+            args,
+            result,
+        } => format!(
+            "invoke {}.{} {} -> {}",
+            device.0,
+            method,
+            args.krefs(),
+            result.krefs()
+        ),
+        AllocatePromiseAndResolver { promise } => {
+            format!("allocate_promise_and_resolver -> {}", promise.kref())
+        }
+        Subscribe { promise } => format!("subscribe {}", promise.kref()),
+        FulfillToTarget { promise, target } => {
+            format!("fulfill_to_target {} {}", promise.kref(), target.kref())
+        }
+        FulfillToData { promise, data: d } => {
+            format!("fulfill_to_data {} {}", promise.kref(), d.krefs())
+        }
+        Reject { promise, data: d } => format!("reject {} {}", promise.kref(), d.krefs()),
+        Forward { promise, target } => {
+            format!("forward {} {}", promise.kref(), target.kref())
+        }
+    }
+}
+
+/// Transcript renders a kernel run as stable text, for comparison against
+/// a checked-in golden file: each crank's delivery, syscalls, queued
+/// deliveries and promise changes, then the state of the kernel. Timings
+/// are left out, so the same run always produces the same transcript.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    text: String,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Transcript::default()
+    }
+
+    /// add a line of commentary, e.g. to mark what the host did
+    pub fn note(&mut self, note: &str) {
+        writeln!(self.text, "# {}", note).unwrap();
+    }
+
+    pub fn crank(&mut self, report: &CrankReport) {
+        let d = &report.delivery;
+        let cause = match d.cause {
+            Cause::Host => "host".to_string(),
+            Cause::Syscall {
+                message, syscall, ..
+            } => format!("{} syscall {}", message, syscall),
+            Cause::Refused { message, .. } => format!("{} refused", message),
+        };
+        writeln!(
+            self.text,
+            "crank {}: {} (from {})",
+            report.crank,
+            d.krefs(),
+            cause
+        )
+        .unwrap();
+        for s in &report.syscalls {
+            writeln!(self.text, "  syscall {}", syscall(s)).unwrap();
+        }
+        for q in &report.queued {
+            writeln!(self.text, "  queued {}", q.krefs()).unwrap();
+        }
+        for p in &report.promises_created {
+            writeln!(self.text, "  created {}", p.kref()).unwrap();
+        }
+        for p in &report.promises_resolved {
+            writeln!(self.text, "  resolved {}", p.kref()).unwrap();
+        }
+    }
+
+    /// run `controller` until its run queue is empty, recording every crank
+    pub fn run(&mut self, controller: &mut Controller) -> RunSummary {
+        let mut summary = RunSummary::default();
+        while let Some(report) = controller.step() {
+            self.crank(&report);
+            summary.add(&report);
+        }
+        summary
+    }
+
+    pub fn state(&mut self, state: &KernelSnapshotView) {
+        write!(self.text, "{}", state).unwrap();
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// compare against (or, in update mode, rewrite) the golden file at
+    /// `path`
+    pub fn assert_golden<P: AsRef<Path>>(&self, path: P) {
+        assert_golden(path, &self.text);
+    }
+}
+
+/// a line diff of `expected` against `actual`, marking removed lines "-",
+/// added lines "+", and unchanged lines " "
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    // lcs[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            writeln!(out, " {}", old[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            writeln!(out, "+{}", new[j]).unwrap();
+            j += 1;
+        } else {
+            writeln!(out, "-{}", old[i]).unwrap();
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Compare `actual` against the golden file at `path`, panicking with a
+/// line diff if they differ. With SWINGSET_UPDATE_GOLDEN set, write
+/// `actual` to the file instead.
+pub fn assert_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(path, actual).unwrap();
+        return;
+    }
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) => panic!(
+            "cannot read golden file {}: {} (set {}=1 to create it)",
+            path.display(),
+            e,
+            UPDATE_GOLDEN
+        ),
+    };
+    if expected != actual {
+        panic!(
+            "output does not match golden file {} (set {}=1 to update it):\n{}",
+            path.display(),
+            UPDATE_GOLDEN,
+            diff(&expected, actual)
+        );
+    }
+}
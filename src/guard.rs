@@ -0,0 +1,180 @@
+use super::marshal::{unserialize, Value};
+use super::vat_types::{VatArgSlot, VatCapData};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Interface guards describe what an object will accept, so that malformed
+// messages (typically from untrusted vats) can be refused before they reach
+// its code. Arguments are checked as smallcaps: the body must be a list,
+// with one element per argument.
+
+/// the expected shape of one argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Any,
+    Undefined,
+    Null,
+    Bool,
+    Number,
+    BigInt,
+    String,
+    List,
+    Record,
+    /// a reference to an object
+    Remotable,
+    Promise,
+    /// the shape, or null or undefined: an optional argument may also be
+    /// left off the end of the list
+    Optional(Box<Shape>),
+}
+
+impl Shape {
+    pub fn optional(shape: Shape) -> Self {
+        Shape::Optional(Box::new(shape))
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Shape::Any, _) => true,
+            (Shape::Optional(_), Value::Undefined)
+            | (Shape::Optional(_), Value::Null) => true,
+            (Shape::Optional(shape), value) => shape.matches(value),
+            (Shape::Undefined, Value::Undefined) => true,
+            (Shape::Null, Value::Null) => true,
+            (Shape::Bool, Value::Bool(_)) => true,
+            (Shape::Number, Value::Number(_)) => true,
+            (Shape::BigInt, Value::BigInt(_)) => true,
+            (Shape::String, Value::String(_)) => true,
+            (Shape::List, Value::List(_)) => true,
+            (Shape::Record, Value::Record(_)) => true,
+            (Shape::Remotable, Value::Slot { slot, .. }) => {
+                !matches!(slot, VatArgSlot::Promise(_))
+            }
+            (Shape::Promise, Value::Slot { slot, .. }) => {
+                matches!(slot, VatArgSlot::Promise(_))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Shape::Any => write!(f, "anything"),
+            Shape::Undefined => write!(f, "undefined"),
+            Shape::Null => write!(f, "null"),
+            Shape::Bool => write!(f, "a boolean"),
+            Shape::Number => write!(f, "a number"),
+            Shape::BigInt => write!(f, "a bigint"),
+            Shape::String => write!(f, "a string"),
+            Shape::List => write!(f, "a list"),
+            Shape::Record => write!(f, "a record"),
+            Shape::Remotable => write!(f, "a remotable"),
+            Shape::Promise => write!(f, "a promise"),
+            Shape::Optional(shape) => write!(f, "{} (or nothing)", shape),
+        }
+    }
+}
+
+/// what a method accepts
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodGuard {
+    args: Vec<Shape>,
+    returns: bool,
+}
+
+impl MethodGuard {
+    /// a method which answers: its messages may ask for a result
+    pub fn call(args: Vec<Shape>) -> Self {
+        MethodGuard {
+            args,
+            returns: true,
+        }
+    }
+
+    /// a method which does not answer: its messages must not ask for a
+    /// result
+    pub fn send_only(args: Vec<Shape>) -> Self {
+        MethodGuard {
+            args,
+            returns: false,
+        }
+    }
+
+    fn check(&self, args: &[Value], has_result: bool) -> Result<(), String> {
+        if has_result && !self.returns {
+            return Err("does not return a result".to_string());
+        }
+        let required = self.args.len()
+            - self
+                .args
+                .iter()
+                .rev()
+                .take_while(|shape| matches!(shape, Shape::Optional(_)))
+                .count();
+        if args.len() < required || args.len() > self.args.len() {
+            let expected = if required == self.args.len() {
+                format!("{}", required)
+            } else {
+                format!("{} to {}", required, self.args.len())
+            };
+            return Err(format!("takes {} arguments, not {}", expected, args.len()));
+        }
+        for (i, (shape, arg)) in self.args.iter().zip(args).enumerate() {
+            if !shape.matches(arg) {
+                return Err(format!("argument {} must be {}, not {:?}", i, shape, arg));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Interface is the set of methods an object accepts. Declare one for an
+/// export with Config::guard_export, and the kernel refuses any message to
+/// it which does not match: the message's result promise (if any) is
+/// rejected with a TypeError describing the problem, and the vat never sees
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface {
+    name: String,
+    methods: BTreeMap<String, MethodGuard>,
+}
+
+impl Interface {
+    pub fn new(name: &str) -> Self {
+        Interface {
+            name: name.to_string(),
+            methods: BTreeMap::new(),
+        }
+    }
+
+    pub fn method(mut self, name: &str, guard: MethodGuard) -> Self {
+        self.methods.insert(name.to_string(), guard);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check a message against the interface, describing the first problem
+    /// found. Vats can also use this to guard objects themselves.
+    pub fn check(
+        &self,
+        method: &str,
+        args: &VatCapData,
+        has_result: bool,
+    ) -> Result<(), String> {
+        let guard = match self.methods.get(method) {
+            Some(guard) => guard,
+            None => return Err(format!("{} has no method {:?}", self.name, method)),
+        };
+        let problem = match unserialize(args) {
+            Ok(Value::List(args)) => guard.check(&args, has_result),
+            Ok(other) => Err(format!("arguments must be a list, not {:?}", other)),
+            Err(e) => Err(format!("cannot unserialize arguments: {}", e)),
+        };
+        problem.map_err(|problem| format!("{}.{} {}", self.name, method, problem))
+    }
+}
@@ -0,0 +1,337 @@
+use super::json::Json;
+use super::kernel::KernelData;
+use super::kernel_types::{
+    KernelArgSlot, KernelCapData, KernelExport, KernelPromiseResolverID, KernelTarget,
+};
+use super::promise::KernelPromise;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+
+// The host does not live inside a vat, so it has no clists. Instead it
+// refers to kernel objects through these opaque handles.
+
+/// "KernelExportHandle" is the host's reference to an object exported by
+/// some vat, obtained from Controller::export_handle
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct KernelExportHandle(pub(crate) KernelExport);
+
+/// "KernelPromiseHandle" is the host's reference to a kernel promise, such
+/// as the result promise returned by Controller::queue_to_export
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct KernelPromiseHandle(pub(crate) KernelPromiseResolverID);
+
+/// HostCapData can contain HostArgSlots
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum HostArgSlot {
+    Export(KernelExportHandle),
+    Promise(KernelPromiseHandle),
+}
+
+/// HostCapData is the host-side equivalent of VatCapData, used for the
+/// arguments of Controller::queue_to_export and for promise resolutions
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct HostCapData {
+    pub body: Vec<u8>,
+    pub slots: Vec<HostArgSlot>,
+}
+impl HostCapData {
+    pub fn new(body: &[u8], slots: Vec<HostArgSlot>) -> Self {
+        HostCapData {
+            body: body.to_vec(),
+            slots,
+        }
+    }
+}
+
+/// the state of a kernel promise, as reported by Controller::promise_status
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum PromiseStatus {
+    Unresolved,
+    FulfilledToTarget(KernelExportHandle),
+    FulfilledToData(HostCapData),
+    Rejected(HostCapData),
+}
+
+/// A Future that completes with the (resolved) PromiseStatus of a kernel
+/// promise, obtained from Controller::promise_future. Nothing happens
+/// unless somebody runs cranks: use Controller::run_until_complete to
+/// drive the kernel while waiting.
+pub struct KernelPromiseFuture {
+    kprid: KernelPromiseResolverID,
+    kd: Rc<RefCell<KernelData>>,
+    id: u64,
+    // where our waker is parked, if anywhere
+    waiting_on: Option<KernelPromiseResolverID>,
+}
+impl KernelPromiseFuture {
+    pub(crate) fn new(
+        kprid: KernelPromiseResolverID,
+        kd: Rc<RefCell<KernelData>>,
+    ) -> Self {
+        let id = {
+            let mut kd = kd.borrow_mut();
+            kd.next_future_id += 1;
+            kd.next_future_id
+        };
+        KernelPromiseFuture {
+            kprid,
+            kd,
+            id,
+            waiting_on: None,
+        }
+    }
+}
+
+impl Future for KernelPromiseFuture {
+    type Output = PromiseStatus;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<PromiseStatus> {
+        let this = self.get_mut();
+        let mut kd = this.kd.borrow_mut();
+        let kprid = kd.current_promise(this.kprid);
+        match PromiseStatus::from(kd.promises.get(&kprid).unwrap()) {
+            PromiseStatus::Unresolved => {
+                // each poll replaces our waker rather than adding another
+                let wakers = kd.promise_wakers.entry(kprid).or_default();
+                match wakers.get(&this.id) {
+                    Some(w) if w.will_wake(cx.waker()) => {}
+                    _ => {
+                        wakers.insert(this.id, cx.waker().clone());
+                    }
+                }
+                this.waiting_on = Some(kprid);
+                Poll::Pending
+            }
+            status => {
+                this.waiting_on = None;
+                Poll::Ready(status)
+            }
+        }
+    }
+}
+
+impl Drop for KernelPromiseFuture {
+    fn drop(&mut self) {
+        let kprid = match self.waiting_on {
+            Some(kprid) => kprid,
+            None => return,
+        };
+        // a future dropped while the kernel is busy leaves its waker
+        // behind, to be discarded when the promise resolves
+        if let Ok(mut kd) = self.kd.try_borrow_mut() {
+            if let Some(wakers) = kd.promise_wakers.get_mut(&kprid) {
+                wakers.remove(&self.id);
+                if wakers.is_empty() {
+                    kd.promise_wakers.remove(&kprid);
+                }
+            }
+        }
+    }
+}
+
+/// the waker used by Controller::run_until_complete: it only records that
+/// the future should be polled again before the next crank
+#[derive(Default)]
+pub(crate) struct CrankWaker(AtomicBool);
+impl CrankWaker {
+    pub(crate) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+impl Wake for CrankWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl From<KernelArgSlot> for HostArgSlot {
+    fn from(slot: KernelArgSlot) -> HostArgSlot {
+        match slot {
+            KernelArgSlot::Export(ke) => HostArgSlot::Export(KernelExportHandle(ke)),
+            KernelArgSlot::Promise(kp) => HostArgSlot::Promise(KernelPromiseHandle(kp)),
+        }
+    }
+}
+
+impl From<KernelTarget> for HostArgSlot {
+    fn from(target: KernelTarget) -> HostArgSlot {
+        match target {
+            KernelTarget::Export(ke) => HostArgSlot::Export(KernelExportHandle(ke)),
+            KernelTarget::Promise(kp) => HostArgSlot::Promise(KernelPromiseHandle(kp)),
+        }
+    }
+}
+
+impl From<HostArgSlot> for KernelArgSlot {
+    fn from(slot: HostArgSlot) -> KernelArgSlot {
+        match slot {
+            HostArgSlot::Export(KernelExportHandle(ke)) => KernelArgSlot::Export(ke),
+            HostArgSlot::Promise(KernelPromiseHandle(kp)) => KernelArgSlot::Promise(kp),
+        }
+    }
+}
+
+impl From<&KernelCapData> for HostCapData {
+    fn from(data: &KernelCapData) -> HostCapData {
+        HostCapData {
+            body: data.body.clone(),
+            slots: data.slots.iter().map(|slot| (*slot).into()).collect(),
+        }
+    }
+}
+
+impl From<HostCapData> for KernelCapData {
+    fn from(data: HostCapData) -> KernelCapData {
+        KernelCapData {
+            body: data.body,
+            slots: data.slots.into_iter().map(|slot| slot.into()).collect(),
+        }
+    }
+}
+
+impl From<&KernelPromise> for PromiseStatus {
+    fn from(p: &KernelPromise) -> PromiseStatus {
+        use KernelPromise::*;
+        match p {
+            Unresolved { .. } => PromiseStatus::Unresolved,
+            FulfilledToTarget(ke) => {
+                PromiseStatus::FulfilledToTarget(KernelExportHandle(*ke))
+            }
+            FulfilledToData(data) => PromiseStatus::FulfilledToData(data.into()),
+            Rejected(data) => PromiseStatus::Rejected(data.into()),
+        }
+    }
+}
+
+impl fmt::Display for KernelExportHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for KernelPromiseHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for HostArgSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostArgSlot::Export(ke) => write!(f, "{}", ke),
+            HostArgSlot::Promise(kp) => write!(f, "{}", kp),
+        }
+    }
+}
+
+impl fmt::Display for HostCapData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} [", String::from_utf8_lossy(&self.body))?;
+        for (i, slot) in self.slots.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", slot)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl fmt::Display for PromiseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use PromiseStatus::*;
+        match self {
+            Unresolved => write!(f, "unresolved"),
+            FulfilledToTarget(ke) => write!(f, "fulfilled-to-target {}", ke),
+            FulfilledToData(data) => write!(f, "fulfilled-to-data {}", data),
+            Rejected(data) => write!(f, "rejected {}", data),
+        }
+    }
+}
+
+impl KernelExportHandle {
+    /// the kernel-level reference string used in slogs and state dumps
+    pub fn kref(&self) -> String {
+        let KernelExport(vat_id, keid) = self.0;
+        format!("ko{}.{}", vat_id.0, keid.0)
+    }
+}
+
+impl KernelPromiseHandle {
+    /// the kernel-level reference string used in slogs and state dumps
+    pub fn kref(&self) -> String {
+        format!("kp{}", (self.0).0)
+    }
+}
+
+impl HostArgSlot {
+    pub fn kref(&self) -> String {
+        match self {
+            HostArgSlot::Export(ke) => ke.kref(),
+            HostArgSlot::Promise(kp) => kp.kref(),
+        }
+    }
+}
+
+impl HostCapData {
+    /// like Display, but with kernel-level reference strings
+    pub(crate) fn krefs(&self) -> String {
+        let slots: Vec<String> = self.slots.iter().map(|s| s.kref()).collect();
+        format!(
+            "{:?} [{}]",
+            String::from_utf8_lossy(&self.body),
+            slots.join(", ")
+        )
+    }
+}
+
+impl PromiseStatus {
+    /// like Display, but with kernel-level reference strings
+    pub(crate) fn krefs(&self) -> String {
+        use PromiseStatus::*;
+        match self {
+            Unresolved => "unresolved".to_string(),
+            FulfilledToTarget(ke) => format!("fulfilled-to-target {}", ke.kref()),
+            FulfilledToData(data) => format!("fulfilled-to-data {}", data.krefs()),
+            Rejected(data) => format!("rejected {}", data.krefs()),
+        }
+    }
+}
+
+impl From<&HostCapData> for Json {
+    fn from(data: &HostCapData) -> Json {
+        let slots: Vec<String> = data.slots.iter().map(|s| s.kref()).collect();
+        let body = match std::str::from_utf8(&data.body) {
+            Ok(body) => Json::object().with("body", body),
+            // a body that is not text is logged byte for byte, in hex
+            Err(_) => {
+                let hex: Vec<String> =
+                    data.body.iter().map(|b| format!("{:02x}", b)).collect();
+                Json::object().with("bodyHex", hex.concat())
+            }
+        };
+        body.with("slots", slots)
+    }
+}
+
+impl From<&PromiseStatus> for Json {
+    fn from(status: &PromiseStatus) -> Json {
+        use PromiseStatus::*;
+        match status {
+            Unresolved => Json::object().with("state", "unresolved"),
+            FulfilledToTarget(ke) => Json::object()
+                .with("state", "fulfilledToTarget")
+                .with("target", ke.kref()),
+            FulfilledToData(data) => Json::object()
+                .with("state", "fulfilledToData")
+                .with("data", data),
+            Rejected(data) => Json::object().with("state", "rejected").with("data", data),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use super::clist::{CList, CListKernelEntry, CListVatEntry};
+use super::kernel::{KernelData, PendingDelivery};
+use super::kernel_types::{KernelArgSlot, KernelCapData, KernelPromiseResolverID, VatID};
+use super::promise::KernelPromise;
+use std::fmt::Debug;
+
+// Each check appends a description of every violation it finds, so a broken
+// kernel reports everything wrong with it at once. Vats and clists are
+// visited in ID order to keep the report stable.
+
+fn check_clist<KT, VT>(problems: &mut Vec<String>, table: &str, clist: &CList<KT, VT>)
+where
+    KT: CListKernelEntry + Debug,
+    VT: CListVatEntry + Debug,
+{
+    let mut inbound: Vec<String> = clist
+        .inbound
+        .iter()
+        .filter(|(k, v)| clist.outbound.get(v) != Some(k))
+        .map(|(k, v)| {
+            format!(
+                "{}: inbound {:?} -> {:?} has no outbound inverse",
+                table, k, v
+            )
+        })
+        .collect();
+    let mut outbound: Vec<String> = clist
+        .outbound
+        .iter()
+        .filter(|(v, k)| clist.inbound.get(k) != Some(v))
+        .map(|(v, k)| {
+            format!(
+                "{}: outbound {:?} -> {:?} has no inbound inverse",
+                table, v, k
+            )
+        })
+        .collect();
+    inbound.sort();
+    outbound.sort();
+    problems.append(&mut inbound);
+    problems.append(&mut outbound);
+}
+
+impl KernelData {
+    fn check_promise(
+        &self,
+        problems: &mut Vec<String>,
+        kprid: KernelPromiseResolverID,
+        referrer: &str,
+    ) {
+        if !self.promises.contains_key(&kprid) {
+            let retired = match self.forwarded.get(&kprid) {
+                Some(new_id) => format!(" (forwarded to {})", new_id),
+                None => String::new(),
+            };
+            problems.push(format!(
+                "{} refers to missing promise {}{}",
+                referrer, kprid, retired
+            ));
+        }
+    }
+
+    fn check_data(
+        &self,
+        problems: &mut Vec<String>,
+        data: &KernelCapData,
+        referrer: &str,
+    ) {
+        for slot in &data.slots {
+            if let KernelArgSlot::Promise(kprid) = slot {
+                self.check_promise(problems, *kprid, referrer);
+            }
+        }
+    }
+
+    fn check_vat(&self, problems: &mut Vec<String>, vat_id: VatID, referrer: &str) {
+        if !self.vat_data.contains_key(&vat_id) {
+            problems.push(format!("{} refers to missing vat {}", referrer, vat_id));
+        }
+    }
+
+    /// Check the kernel tables for consistency, returning a description of
+    /// each problem found: every clist must be a bijection, every promise
+    /// mentioned by a clist, the run queue, or resolution data must exist,
+    /// the deciders and subscribers of promises must be live vats, and a
+    /// resolver may only be held by its promise's decider, and only until
+    /// the promise is resolved.
+    pub(crate) fn check_invariants(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        let mut vat_ids: Vec<&VatID> = self.vat_data.keys().collect();
+        vat_ids.sort_by_key(|vat_id| vat_id.0);
+        for vat_id in vat_ids {
+            let vd = self.vat_data.get(vat_id).unwrap();
+            let name = &vd.name;
+            check_clist(
+                &mut problems,
+                &format!("{} import clist", name),
+                &vd.import_clist,
+            );
+            check_clist(
+                &mut problems,
+                &format!("{} promise clist", name),
+                &vd.promise_clist,
+            );
+            check_clist(
+                &mut problems,
+                &format!("{} resolver clist", name),
+                &vd.resolver_clist,
+            );
+            check_clist(
+                &mut problems,
+                &format!("{} device clist", name),
+                &vd.device_clist,
+            );
+
+            let mut promises: Vec<_> = vd.promise_clist.inbound.keys().collect();
+            promises.sort_by_key(|kprid| kprid.0);
+            for kprid in promises {
+                self.check_promise(
+                    &mut problems,
+                    *kprid,
+                    &format!("{} promise clist", name),
+                );
+            }
+            // an alias must name a promise the vat also knows by its clist
+            // entry, and must not shadow a vpid in the clist
+            let mut aliases: Vec<_> = vd.promise_aliases.iter().collect();
+            aliases.sort_by_key(|(vpid, _)| vpid.0);
+            for (vpid, kprid) in aliases {
+                if vd.promise_clist.outbound.contains_key(vpid) {
+                    problems.push(format!(
+                        "{} promise alias {:?} is also in the clist",
+                        name, vpid
+                    ));
+                }
+                if !vd.promise_clist.inbound.contains_key(kprid) {
+                    problems.push(format!(
+                        "{} promise alias {:?} -> {:?} has no clist entry",
+                        name, vpid, kprid
+                    ));
+                }
+            }
+            let mut resolvers: Vec<_> = vd.resolver_clist.inbound.keys().collect();
+            resolvers.sort_by_key(|kprid| kprid.0);
+            for kprid in resolvers {
+                let referrer = format!("{} resolver clist", name);
+                match self.promises.get(kprid) {
+                    Some(KernelPromise::Unresolved { decider, .. })
+                        if decider != vat_id =>
+                    {
+                        problems.push(format!(
+                            "{} holds the resolver for {}, decided by vat {}",
+                            referrer, kprid, decider
+                        ));
+                    }
+                    Some(KernelPromise::Unresolved { .. }) => (),
+                    Some(_) => problems.push(format!(
+                        "{} holds the resolver for {}, which is already resolved",
+                        referrer, kprid
+                    )),
+                    None => self.check_promise(&mut problems, *kprid, &referrer),
+                }
+            }
+        }
+
+        let mut promises: Vec<_> = self.promises.iter().collect();
+        promises.sort_by_key(|(kprid, _)| kprid.0);
+        for (kprid, p) in promises {
+            let referrer = format!("promise {}", kprid);
+            match p {
+                KernelPromise::Unresolved {
+                    subscribers,
+                    decider,
+                } => {
+                    self.check_vat(
+                        &mut problems,
+                        *decider,
+                        &format!("{} decider", referrer),
+                    );
+                    for s in subscribers {
+                        self.check_vat(
+                            &mut problems,
+                            *s,
+                            &format!("{} subscriber", referrer),
+                        );
+                    }
+                }
+                KernelPromise::FulfilledToTarget(ke) => {
+                    self.check_vat(&mut problems, ke.0, &referrer);
+                }
+                KernelPromise::FulfilledToData(data) | KernelPromise::Rejected(data) => {
+                    self.check_data(&mut problems, data, &referrer);
+                }
+            }
+        }
+
+        use PendingDelivery::*;
+        for entry in &self.run_queue.0 {
+            let referrer = format!("queued message {}", entry.id);
+            match &entry.pd {
+                Deliver { target, message } => {
+                    self.check_vat(&mut problems, target.0, &referrer);
+                    self.check_data(&mut problems, &message.args, &referrer);
+                    if let Some(kprid) = message.resolver {
+                        self.check_promise(&mut problems, kprid, &referrer);
+                    }
+                }
+                DeliverPromise {
+                    vat_id,
+                    target,
+                    message,
+                } => {
+                    self.check_vat(&mut problems, *vat_id, &referrer);
+                    self.check_promise(&mut problems, *target, &referrer);
+                    self.check_data(&mut problems, &message.args, &referrer);
+                    if let Some(kprid) = message.resolver {
+                        self.check_promise(&mut problems, kprid, &referrer);
+                    }
+                }
+                NotifyFulfillToData {
+                    vat_id,
+                    target,
+                    data,
+                }
+                | NotifyReject {
+                    vat_id,
+                    target,
+                    data,
+                } => {
+                    self.check_vat(&mut problems, *vat_id, &referrer);
+                    self.check_promise(&mut problems, *target, &referrer);
+                    self.check_data(&mut problems, data, &referrer);
+                }
+                NotifyFulfillToTarget {
+                    vat_id,
+                    target,
+                    result,
+                } => {
+                    self.check_vat(&mut problems, *vat_id, &referrer);
+                    self.check_promise(&mut problems, *target, &referrer);
+                    self.check_vat(&mut problems, result.0, &referrer);
+                }
+            }
+        }
+
+        problems
+    }
+}
@@ -0,0 +1,490 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+/// A minimal JSON value, enough for the kernel's own structured output.
+/// Object members keep their insertion order, so output is stable.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    // only for integers too big for an Int
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(crate) fn object() -> Self {
+        Json::Object(vec![])
+    }
+
+    /// append a member to an object, builder-style
+    pub(crate) fn with<T: Into<Json>>(mut self, key: &str, value: T) -> Self {
+        match &mut self {
+            Json::Object(members) => members.push((key.to_string(), value.into())),
+            _ => panic!("Json::with on a non-object"),
+        }
+        self
+    }
+
+    /// append all the members of another object
+    pub(crate) fn merge(mut self, other: Json) -> Self {
+        if let Json::Object(more) = other {
+            for (key, value) in more {
+                self = self.with(&key, value);
+            }
+        }
+        self
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+impl From<i64> for Json {
+    fn from(n: i64) -> Json {
+        Json::Int(n)
+    }
+}
+impl From<u64> for Json {
+    fn from(n: u64) -> Json {
+        match i64::try_from(n) {
+            Ok(n) => Json::Int(n),
+            Err(_) => Json::UInt(n),
+        }
+    }
+}
+impl From<u32> for Json {
+    fn from(n: u32) -> Json {
+        Json::Int(i64::from(n))
+    }
+}
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::from(n as u64)
+    }
+}
+impl From<f64> for Json {
+    fn from(n: f64) -> Json {
+        Json::Float(n)
+    }
+}
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Json {
+        Json::Array(items.into_iter().map(|i| i.into()).collect())
+    }
+}
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(o: Option<T>) -> Json {
+        match o {
+            Some(v) => v.into(),
+            None => Json::Null,
+        }
+    }
+}
+
+/// Format a finite number the way JavaScript's JSON.stringify does: as an
+/// integer when it is one, and with an exponent when it is very large or
+/// very small.
+fn write_number(f: &mut fmt::Formatter, n: f64) -> fmt::Result {
+    let abs = n.abs();
+    if abs != 0.0 && !(1e-6..1e21).contains(&abs) {
+        let s = format!("{:e}", n);
+        match s.find("e-") {
+            Some(_) => write!(f, "{}", s),
+            None => write!(f, "{}", s.replacen('e', "e+", 1)),
+        }
+    } else {
+        // Rust prints -0.0 as "-0", JavaScript as "0"
+        write!(f, "{}", if n == 0.0 { 0.0 } else { n })
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// RFC 8259 section 6: -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+fn is_number(literal: &str) -> bool {
+    let digits =
+        |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let mut rest = literal.strip_prefix('-').unwrap_or(literal);
+    match digits(rest) {
+        0 => return false,
+        n if n > 1 && rest.starts_with('0') => return false,
+        n => rest = &rest[n..],
+    }
+    if let Some(fraction) = rest.strip_prefix('.') {
+        match digits(fraction) {
+            0 => return false,
+            n => rest = &fraction[n..],
+        }
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        match digits(exponent) {
+            0 => return false,
+            n => rest = &exponent[n..],
+        }
+    }
+    rest.is_empty()
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(n) => write!(f, "{}", n),
+            Json::UInt(n) => write!(f, "{}", n),
+            // JSON has no NaN or Infinity
+            Json::Float(n) if !n.is_finite() => write!(f, "null"),
+            Json::Float(n) => write_number(f, *n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// how deeply arrays and objects may nest, so hostile input cannot exhaust
+/// the stack
+const MAX_DEPTH: usize = 256;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, what: &str) -> Result<T, String> {
+        Err(format!("{} at offset {}", what, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => self.error(&format!("expected '{}'", c)),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            self.error("unexpected token")
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            None => self.error("unexpected end of input"),
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') | Some('{') => {
+                if self.depth == MAX_DEPTH {
+                    return self.error("nested too deeply");
+                }
+                self.depth += 1;
+                let value = if self.peek() == Some('[') {
+                    self.array()
+                } else {
+                    self.object()
+                };
+                self.depth -= 1;
+                value
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => self.error("unexpected character"),
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = vec![];
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.expect(']')?;
+        Ok(Json::Array(items))
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut members = vec![];
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some('"') {
+                return self.error("expected a string key");
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.expect('}')?;
+        Ok(Json::Object(members))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        let rest = &self.text[start..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(rest.len());
+        let literal = &rest[..len];
+        // Rust's parsers are laxer than JSON, so check the grammar first
+        if !is_number(literal) {
+            return self.error("bad number");
+        }
+        self.pos += len;
+        if let Ok(n) = literal.parse::<i64>() {
+            return Ok(Json::Int(n));
+        }
+        if let Ok(n) = literal.parse::<u64>() {
+            return Ok(Json::UInt(n));
+        }
+        match literal.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Json::Float(n)),
+            _ => {
+                self.pos = start;
+                self.error("number out of range")
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        // from_str_radix alone would also take a sign
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()));
+        match digits.and_then(|d| u32::from_str_radix(d, 16).ok()) {
+            Some(n) => {
+                self.pos += 4;
+                Ok(n)
+            }
+            None => self.error("bad \\u escape"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let c = match self.text[self.pos..].chars().next() {
+                Some(c) => c,
+                None => return self.error("unterminated string"),
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let e = match self.text[self.pos..].chars().next() {
+                        Some(e) => e,
+                        None => return self.error("unterminated string"),
+                    };
+                    self.pos += e.len_utf8();
+                    match e {
+                        '"' | '\\' | '/' => s.push(e),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xD800..0xDC00).contains(&code)
+                                && self.text[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return self.error("unpaired surrogate");
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            match std::char::from_u32(code) {
+                                Some(c) => s.push(c),
+                                None => return self.error("unpaired surrogate"),
+                            }
+                        }
+                        _ => return self.error("bad escape"),
+                    }
+                }
+                c if (c as u32) < 0x20 => {
+                    return self.error("control character in string")
+                }
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+impl Json {
+    /// parse a complete JSON text
+    pub(crate) fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        match parser.peek() {
+            None => Ok(value),
+            Some(_) => parser.error("trailing characters"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let j = Json::object()
+            .with("type", "deliver")
+            .with("n", 3u64)
+            .with("t", 1.5)
+            .with("none", Json::Null)
+            .with("list", vec!["a\"b", "c\nd"]);
+        assert_eq!(
+            j.to_string(),
+            r#"{"type":"deliver","n":3,"t":1.5,"none":null,"list":["a\"b","c\nd"]}"#
+        );
+        assert_eq!(Json::from("\u{1}").to_string(), r#""\u0001""#);
+        assert_eq!(Json::Float(f64::NAN).to_string(), "null");
+        assert_eq!(Json::Float(1e21).to_string(), "1e+21");
+        assert_eq!(Json::Float(1.5e-7).to_string(), "1.5e-7");
+        assert_eq!(Json::Float(-0.0).to_string(), "0");
+        assert_eq!(Json::from(u64::MAX).to_string(), "18446744073709551615");
+        assert_eq!(Json::from(i64::MAX as u64), Json::Int(i64::MAX));
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#" {"a": [1, -2.5, 2.5e-3, true, null], "b\u00e9\ud83d\ude00": "x\ny", "c": {}} "#;
+        let expected = Json::object()
+            .with(
+                "a",
+                Json::Array(vec![
+                    Json::Int(1),
+                    Json::Float(-2.5),
+                    Json::Float(0.0025),
+                    Json::Bool(true),
+                    Json::Null,
+                ]),
+            )
+            .with("b\u{e9}\u{1f600}", "x\ny")
+            .with("c", Json::object());
+        assert_eq!(Json::parse(text), Ok(expected.clone()));
+        assert_eq!(Json::parse(&expected.to_string()), Ok(expected));
+        let big = Json::from(u64::MAX);
+        assert_eq!(Json::parse(&big.to_string()), Ok(big));
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+        assert!(Json::parse("\"abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_hostile() {
+        let problem = |text: &str| Json::parse(text).unwrap_err();
+        assert_eq!(
+            problem(r#""\ud800\u0041""#),
+            "unpaired surrogate at offset 13"
+        );
+        assert_eq!(problem(r#""\ud800""#), "unpaired surrogate at offset 7");
+        assert_eq!(problem(r#""\u+041""#), "bad \\u escape at offset 3");
+        assert_eq!(problem("\"\\\u{e9}\""), "bad escape at offset 4");
+        assert_eq!(problem("[01]"), "bad number at offset 1");
+        assert_eq!(problem("[1.]"), "bad number at offset 1");
+        assert_eq!(problem("[1e999]"), "number out of range at offset 1");
+        assert_eq!(problem("-1e999"), "number out of range at offset 0");
+        assert_eq!(
+            Json::parse("[0, -0.5, 1E+2]").unwrap().to_string(),
+            "[0,-0.5,100]"
+        );
+        let deep = "[".repeat(1_000_000);
+        assert!(problem(&deep).starts_with("nested too deeply"));
+        let ok = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(Json::parse(&ok).is_ok());
+    }
+}
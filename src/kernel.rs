@@ -1,19 +1,34 @@
 use super::clist::{CList, CListKernelEntry, CListVatEntry};
 use super::config::Config;
+use super::device::{BridgeError, DeviceData};
 use super::dispatch::Dispatch;
+use super::guard::Interface;
+use super::host::{HostCapData, KernelPromiseFuture, KernelPromiseHandle, PromiseStatus};
 use super::kernel_types::{
-    KernelArgSlot, KernelCapData, KernelExport, KernelExportID, KernelMessage,
-    KernelPromiseResolverID, VatID, VatName,
+    DeviceID, DeviceName, KernelArgSlot, KernelCapData, KernelExport, KernelExportID,
+    KernelMessage, KernelPromiseResolverID, VatID, VatName,
 };
+use super::marshal::{serialize, Value};
+use super::metrics::{KernelMetrics, MetricCounters};
+use super::observer::{KernelEvent, KernelObserver};
 use super::promise::KernelPromise;
+use super::report::{
+    Cause, CrankRecord, CrankReport, DeliveryKind, DeliveryReport, MessageID, RunSummary,
+    SyscallReport,
+};
+use super::scheduler::{eligible, EOrderChecker, Rng, Scheduler};
+use super::snapshot::KernelSnapshotView;
 use super::vat::VatSyscall;
 use super::vat_types::{
-    InboundVatMessage, VatArgSlot, VatCapData, VatExportID, VatImportID, VatPromiseID,
-    VatResolveTarget, VatResolverID,
+    InboundVatMessage, VatArgSlot, VatCapData, VatDeviceID, VatExportID, VatImportID,
+    VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
 };
 use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
 use std::rc::Rc;
+use std::task::Waker;
+use std::time::Instant;
 
 impl CListVatEntry for VatImportID {
     fn new(index: u32) -> Self {
@@ -30,8 +45,14 @@ impl CListVatEntry for VatResolverID {
         VatResolverID(index)
     }
 }
+impl CListVatEntry for VatDeviceID {
+    fn new(index: u32) -> Self {
+        VatDeviceID(index)
+    }
+}
 impl CListKernelEntry for KernelExport {}
 impl CListKernelEntry for KernelPromiseResolverID {}
+impl CListKernelEntry for DeviceID {}
 
 #[derive(Debug)]
 pub(crate) enum PendingDelivery {
@@ -62,10 +83,16 @@ pub(crate) enum PendingDelivery {
 }
 
 pub(crate) struct VatData {
-    vat_id: VatID,
+    pub(crate) vat_id: VatID,
+    pub(crate) name: VatName,
     pub(crate) import_clist: CList<KernelExport, VatImportID>,
     pub(crate) promise_clist: CList<KernelPromiseResolverID, VatPromiseID>,
+    // vpids left over when syscall.forward joins two promises the vat
+    // already knew: still usable outbound, and notified alongside the
+    // promise_clist entry for the same kernel promise
+    pub(crate) promise_aliases: HashMap<VatPromiseID, KernelPromiseResolverID>,
     pub(crate) resolver_clist: CList<KernelPromiseResolverID, VatResolverID>,
+    pub(crate) device_clist: CList<DeviceID, VatDeviceID>,
 }
 impl VatData {
     // it's totally legit for vat A to hold a promise, vat B resolves
@@ -121,11 +148,39 @@ impl VatData {
     ) -> VatPromiseID {
         self.promise_clist.map_inbound(kprid)
     }
+
+    /// every vpid a notification about this promise must be delivered
+    /// under: its clist entry first, then any aliases
+    pub fn notified_promises(
+        &mut self,
+        kprid: KernelPromiseResolverID,
+    ) -> Vec<VatPromiseID> {
+        let mut aliases: Vec<VatPromiseID> = self
+            .promise_aliases
+            .iter()
+            .filter(|(_, k)| **k == kprid)
+            .map(|(v, _)| *v)
+            .collect();
+        aliases.sort_by_key(|vpid| vpid.0);
+        let mut vpids = vec![self.map_inbound_promise(kprid)];
+        vpids.append(&mut aliases);
+        vpids
+    }
+
+    pub fn map_outbound_promise(&self, vpid: VatPromiseID) -> KernelPromiseResolverID {
+        match self.promise_aliases.get(&vpid) {
+            Some(kprid) => *kprid,
+            None => self.promise_clist.map_outbound(vpid),
+        }
+    }
     pub fn get_outbound_promise(
         &mut self,
         vpid: VatPromiseID,
     ) -> KernelPromiseResolverID {
-        self.promise_clist.get_outbound(vpid)
+        match self.promise_aliases.get(&vpid) {
+            Some(kprid) => *kprid,
+            None => self.promise_clist.get_outbound(vpid),
+        }
     }
 
     pub fn get_inbound_resolver(
@@ -148,68 +203,536 @@ impl VatData {
         self.resolver_clist.map_outbound(vrid)
     }
 
+    /// a resolved or forwarded promise's resolver leaves its decider's clist
+    pub fn spend_resolver(
+        &mut self,
+        kprid: KernelPromiseResolverID,
+        vrid: VatResolverID,
+    ) {
+        self.resolver_clist.inbound.remove(&kprid);
+        self.resolver_clist.outbound.remove(&vrid);
+    }
+
     pub fn forward_promise(
         &mut self,
         old_id: KernelPromiseResolverID,
         new_id: KernelPromiseResolverID,
     ) {
+        for kprid in self.promise_aliases.values_mut() {
+            if *kprid == old_id {
+                *kprid = new_id;
+            }
+        }
         let pc = &mut self.promise_clist;
-        if pc.inbound.contains_key(&old_id) {
-            let vpid = *pc.inbound.get(&old_id).unwrap();
-            assert!(pc.outbound.contains_key(&vpid));
-            pc.inbound.remove(&old_id);
-            pc.inbound.insert(new_id, vpid);
-            pc.outbound.insert(vpid, new_id);
+        if let Some(vpid) = pc.inbound.remove(&old_id) {
+            pc.outbound.remove(&vpid);
+            if pc.inbound.contains_key(&new_id) {
+                // the vat already knew the new promise, so the old vpid
+                // becomes an alias for it
+                self.promise_aliases.insert(vpid, new_id);
+            } else {
+                pc.add(new_id, vpid);
+            }
+        }
+    }
+}
+
+/// the reference a message was sent through: messages sent through the same
+/// reference must be delivered in the order they were sent
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub(crate) enum SendReference {
+    Host(KernelExport),
+    Vat(VatID, VatSendTarget),
+}
+
+impl fmt::Display for SendReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendReference::Host(ke) => write!(f, "host {}", ke),
+            SendReference::Vat(vat_id, target) => write!(f, "{} {}", vat_id, target),
         }
     }
 }
 
+/// a PendingDelivery, with where it came from
+#[derive(Debug)]
+pub(crate) struct RunQueueEntry {
+    pub(crate) id: MessageID,
+    pub(crate) cause: Cause,
+    // messages have one, notifications do not
+    pub(crate) reference: Option<SendReference>,
+    pub(crate) pd: PendingDelivery,
+}
+
 #[derive(Debug, Default)]
-pub(crate) struct RunQueue(pub VecDeque<PendingDelivery>);
+pub(crate) struct RunQueue(pub VecDeque<RunQueueEntry>);
 
 pub(crate) struct KernelData {
     pub(crate) vat_names: HashMap<VatName, VatID>,
     pub(crate) vat_data: HashMap<VatID, VatData>,
+    pub(crate) device_names: HashMap<DeviceName, DeviceID>,
+    pub(crate) device_data: HashMap<DeviceID, DeviceData>,
     pub(crate) run_queue: RunQueue,
     pub(crate) next_promise_resolver_id: u32,
     pub(crate) promises: HashMap<KernelPromiseResolverID, KernelPromise>,
+    // syscall.forward retires the old promise: remember where it went, so
+    // host-held handles can follow it
+    pub(crate) forwarded: HashMap<KernelPromiseResolverID, KernelPromiseResolverID>,
+    // host-side futures waiting for an unresolved promise to change state,
+    // one waker per future, keyed by the future's id
+    pub(crate) promise_wakers: HashMap<KernelPromiseResolverID, BTreeMap<u64, Waker>>,
+    pub(crate) next_future_id: u64,
+    pub(crate) next_message_id: u64,
+    // the message being delivered by the current crank, if any
+    pub(crate) delivering: Option<MessageID>,
+    /// set while the kernel rejects a refused message, which has no syscall
+    /// to blame for what it queues
+    pub(crate) refusing: bool,
+    pub(crate) crank_num: u64,
+    pub(crate) crank: CrankRecord,
+    pub(crate) metrics: MetricCounters,
+    pub(crate) observers: Vec<Box<dyn KernelObserver>>,
+}
+
+impl KernelData {
+    pub(crate) fn allocate_promise(
+        &mut self,
+        p: KernelPromise,
+    ) -> KernelPromiseResolverID {
+        let id = self.next_promise_resolver_id;
+        self.next_promise_resolver_id = id + 1;
+        let kprid = KernelPromiseResolverID(id);
+        let status = PromiseStatus::from(&p);
+        self.promises.insert(kprid, p);
+        self.crank.promises_created.push(KernelPromiseHandle(kprid));
+        self.emit(KernelEvent::PromiseState {
+            promise: KernelPromiseHandle(kprid),
+            status,
+        });
+        kprid
+    }
+
+    pub(crate) fn emit(&mut self, event: KernelEvent) {
+        for o in self.observers.iter_mut() {
+            o.observe(&event);
+        }
+    }
+
+    pub(crate) fn record_syscall(&mut self, vat_id: VatID, report: SyscallReport) {
+        self.crank.syscalls.push(report.clone());
+        let vat = self.vat_data.get(&vat_id).unwrap().name.clone();
+        self.emit(KernelEvent::Syscall {
+            vat,
+            syscall: report,
+        });
+    }
+
+    /// Queue a notification, or a message from the host
+    pub(crate) fn queue(&mut self, pd: PendingDelivery) {
+        let reference = match (&pd, self.delivering) {
+            (PendingDelivery::Deliver { target, .. }, None) => {
+                Some(SendReference::Host(*target))
+            }
+            _ => None,
+        };
+        self.queue_entry(pd, reference);
+    }
+
+    /// Queue a message sent by a vat
+    pub(crate) fn queue_send(&mut self, pd: PendingDelivery, reference: SendReference) {
+        self.queue_entry(pd, Some(reference));
+    }
+
+    /// All additions to the run-queue go through here, so the current crank
+    /// can report them. Syscalls are recorded before they queue anything,
+    /// so the most recent one is the cause.
+    fn queue_entry(&mut self, pd: PendingDelivery, reference: Option<SendReference>) {
+        let id = MessageID(self.next_message_id);
+        self.next_message_id += 1;
+        let cause = match self.delivering {
+            Some(message) if self.refusing => Cause::Refused {
+                message,
+                crank: self.crank_num,
+            },
+            Some(message) => Cause::Syscall {
+                message,
+                crank: self.crank_num,
+                syscall: self.crank.syscalls.len() - 1,
+            },
+            None => Cause::Host,
+        };
+        let entry = RunQueueEntry {
+            id,
+            cause,
+            reference,
+            pd,
+        };
+        let report = self.describe(&entry);
+        if self.delivering.is_some() {
+            self.crank.queued.push(report.clone());
+        }
+        self.emit(KernelEvent::Queued { delivery: report });
+        self.run_queue.0.push_back(entry);
+    }
+
+    pub(crate) fn describe(&self, entry: &RunQueueEntry) -> DeliveryReport {
+        use PendingDelivery::*;
+        let (vat_id, kind, target, method) = match &entry.pd {
+            Deliver { target, message } => (
+                target.0,
+                DeliveryKind::Deliver,
+                KernelArgSlot::Export(*target),
+                Some(message.name.clone()),
+            ),
+            DeliverPromise {
+                vat_id,
+                target,
+                message,
+            } => (
+                *vat_id,
+                DeliveryKind::DeliverPromise,
+                KernelArgSlot::Promise(*target),
+                Some(message.name.clone()),
+            ),
+            NotifyFulfillToData { vat_id, target, .. } => (
+                *vat_id,
+                DeliveryKind::NotifyFulfillToData,
+                KernelArgSlot::Promise(*target),
+                None,
+            ),
+            NotifyFulfillToTarget { vat_id, target, .. } => (
+                *vat_id,
+                DeliveryKind::NotifyFulfillToTarget,
+                KernelArgSlot::Promise(*target),
+                None,
+            ),
+            NotifyReject { vat_id, target, .. } => (
+                *vat_id,
+                DeliveryKind::NotifyReject,
+                KernelArgSlot::Promise(*target),
+                None,
+            ),
+        };
+        DeliveryReport {
+            id: entry.id,
+            cause: entry.cause,
+            vat: self.vat_data.get(&vat_id).unwrap().name.clone(),
+            kind,
+            target: target.into(),
+            method,
+        }
+    }
+
+    /// the message arguments (or resolution data) and result promise of a
+    /// run-queue entry
+    pub(crate) fn delivery_args(
+        &self,
+        pd: &PendingDelivery,
+    ) -> (HostCapData, Option<KernelPromiseHandle>) {
+        use PendingDelivery::*;
+        match pd {
+            Deliver { message, .. } | DeliverPromise { message, .. } => (
+                (&message.args).into(),
+                message.resolver.map(KernelPromiseHandle),
+            ),
+            NotifyFulfillToData { data, .. } | NotifyReject { data, .. } => {
+                (data.into(), None)
+            }
+            NotifyFulfillToTarget { result, .. } => {
+                let slots = vec![KernelArgSlot::Export(*result).into()];
+                (HostCapData::new(b"", slots), None)
+            }
+        }
+    }
+
+    /// a promise has just been fulfilled, rejected, or forwarded
+    pub(crate) fn promise_resolved(&mut self, kprid: KernelPromiseResolverID) {
+        self.crank
+            .promises_resolved
+            .push(KernelPromiseHandle(kprid));
+        self.wake_promise(kprid);
+        let event = match self.promises.get(&kprid) {
+            Some(p) => KernelEvent::PromiseState {
+                promise: KernelPromiseHandle(kprid),
+                status: p.into(),
+            },
+            None => KernelEvent::PromiseForwarded {
+                promise: KernelPromiseHandle(kprid),
+                target: KernelPromiseHandle(*self.forwarded.get(&kprid).unwrap()),
+            },
+        };
+        self.emit(event);
+    }
+
+    /// follow any forwarding of a (possibly retired) promise to the one
+    /// that currently stands in for it
+    pub(crate) fn current_promise(
+        &self,
+        mut kprid: KernelPromiseResolverID,
+    ) -> KernelPromiseResolverID {
+        while let Some(new_id) = self.forwarded.get(&kprid) {
+            kprid = *new_id;
+        }
+        kprid
+    }
+
+    /// Reject a promise on behalf of the kernel (rather than its decider),
+    /// notifying its subscribers
+    pub(crate) fn reject_promise(
+        &mut self,
+        kprid: KernelPromiseResolverID,
+        data: KernelCapData,
+    ) {
+        let old = self
+            .promises
+            .insert(kprid, KernelPromise::Rejected(data.clone()));
+        let subscribers: Vec<VatID> = match old {
+            Some(KernelPromise::Unresolved { subscribers, .. }) => {
+                subscribers.into_iter().collect()
+            }
+            _ => panic!("{} is already resolved", kprid),
+        };
+        self.promise_resolved(kprid);
+        for vat_id in subscribers {
+            self.queue(PendingDelivery::NotifyReject {
+                vat_id,
+                target: kprid,
+                data: data.clone(),
+            });
+        }
+    }
+
+    /// syscall.forward has retired `old_id` in favor of `new_id`, so rewrite
+    /// every reference to it in the run queue and in resolution data.
+    /// Messages that were queued to the old promise are retargeted at the new
+    /// one, then taken off the run queue and returned, to be sent on to
+    /// wherever the new promise leads.
+    pub(crate) fn forward_references(
+        &mut self,
+        old_id: KernelPromiseResolverID,
+        new_id: KernelPromiseResolverID,
+    ) -> Vec<RunQueueEntry> {
+        let fix = |data: &mut KernelCapData| {
+            for slot in data.slots.iter_mut() {
+                if *slot == KernelArgSlot::Promise(old_id) {
+                    *slot = KernelArgSlot::Promise(new_id);
+                }
+            }
+        };
+        for p in self.promises.values_mut() {
+            if let KernelPromise::FulfilledToData(data) | KernelPromise::Rejected(data) =
+                p
+            {
+                fix(data);
+            }
+        }
+        use PendingDelivery::*;
+        let mut redirected = vec![];
+        for mut entry in std::mem::take(&mut self.run_queue.0) {
+            match entry.pd {
+                Deliver {
+                    ref mut message, ..
+                } => fix(&mut message.args),
+                DeliverPromise {
+                    ref mut target,
+                    ref mut message,
+                    ..
+                } => {
+                    fix(&mut message.args);
+                    if *target == old_id {
+                        *target = new_id;
+                        redirected.push(entry);
+                        continue;
+                    }
+                }
+                NotifyFulfillToData { ref mut data, .. }
+                | NotifyReject { ref mut data, .. } => fix(data),
+                NotifyFulfillToTarget { .. } => (),
+            }
+            self.run_queue.0.push_back(entry);
+        }
+        redirected
+    }
+
+    /// take the messages queued to a promise off the run queue
+    pub(crate) fn take_queued_to(
+        &mut self,
+        kprid: KernelPromiseResolverID,
+    ) -> VecDeque<RunQueueEntry> {
+        let (taken, kept) =
+            std::mem::take(&mut self.run_queue.0)
+                .into_iter()
+                .partition(|entry| match entry.pd {
+                    PendingDelivery::DeliverPromise { target, .. } => target == kprid,
+                    _ => false,
+                });
+        self.run_queue.0 = kept;
+        taken
+    }
+
+    /// Send queued messages (all DeliverPromise) to the back of the run
+    /// queue, addressed to wherever their target promise now leads: its
+    /// decider, or the export it was fulfilled to. Messages sent to data or
+    /// to a rejected promise are dropped, and their results rejected. When
+    /// a message's result promise gets a new decider (or is rejected),
+    /// anything pipelined on that result must follow it too.
+    pub(crate) fn resend(&mut self, entries: Vec<RunQueueEntry>) {
+        use KernelPromise::*;
+        use PendingDelivery::*;
+        let mut work: VecDeque<RunQueueEntry> = entries.into();
+        while let Some(mut entry) = work.pop_front() {
+            let (target, message) = match entry.pd {
+                DeliverPromise {
+                    target, message, ..
+                } => (target, message),
+                _ => unreachable!(),
+            };
+            let result = message.resolver;
+            let (destination, pd) = match self.promises.get(&target).unwrap() {
+                Unresolved { decider, .. } => (
+                    *decider,
+                    DeliverPromise {
+                        vat_id: *decider,
+                        target,
+                        message,
+                    },
+                ),
+                FulfilledToTarget(ke) => (
+                    ke.0,
+                    Deliver {
+                        target: *ke,
+                        message,
+                    },
+                ),
+                FulfilledToData(_) | Rejected(_) => {
+                    if let Some(kprid) = result {
+                        let data = match self.promises.get(&target).unwrap() {
+                            Rejected(data) => data.clone(),
+                            _ => KernelCapData {
+                                body: b"cannot send to data".to_vec(),
+                                slots: vec![],
+                            },
+                        };
+                        self.reject_promise(kprid, data);
+                        work.extend(self.take_queued_to(kprid));
+                    }
+                    continue;
+                }
+            };
+            entry.pd = pd;
+            self.run_queue.0.push_back(entry);
+            // the message's result is now decided wherever it is going, and
+            // anything pipelined on it must stay behind it in the queue, even
+            // if the decider is unchanged
+            if let Some(kprid) = result {
+                if let Some(Unresolved { decider, .. }) = self.promises.get_mut(&kprid) {
+                    *decider = destination;
+                    work.extend(self.take_queued_to(kprid));
+                }
+            }
+        }
+    }
+
+    /// Wake any futures waiting on a promise that has just been resolved or
+    /// forwarded. Wakers must only schedule a re-poll: they are called while
+    /// KernelData is borrowed.
+    pub(crate) fn wake_promise(&mut self, kprid: KernelPromiseResolverID) {
+        if let Some(wakers) = self.promise_wakers.remove(&kprid) {
+            for w in wakers.into_values() {
+                w.wake();
+            }
+        }
+    }
 }
 
 //#[derive(Debug)]
 pub struct Kernel {
     pub(crate) vat_dispatch: HashMap<VatID, Box<dyn Dispatch>>,
     pub(crate) kd: Rc<RefCell<KernelData>>,
+    check_invariants: bool,
+    scheduler: Scheduler,
+    e_order: Option<EOrderChecker>,
+    guards: HashMap<KernelExport, Interface>,
 }
 
 impl Kernel {
     pub fn new(cfg: Config) -> Self {
+        let check_invariants = cfg.check_invariants;
+        let scheduler = match cfg.chaos_seed {
+            Some(seed) => Scheduler::Chaos(Rng::new(seed)),
+            None => Scheduler::Fifo,
+        };
+        let e_order = if cfg.check_e_order {
+            Some(EOrderChecker::default())
+        } else {
+            None
+        };
         let mut vat_dispatch = HashMap::new();
         let kd = Rc::new(RefCell::new(KernelData {
             vat_names: HashMap::new(),
             vat_data: HashMap::new(),
+            device_names: HashMap::new(),
+            device_data: HashMap::new(),
             run_queue: RunQueue::default(),
             promises: HashMap::default(),
             next_promise_resolver_id: 0,
+            forwarded: HashMap::new(),
+            promise_wakers: HashMap::new(),
+            next_future_id: 0,
+            next_message_id: 0,
+            delivering: None,
+            refusing: false,
+            crank_num: 0,
+            crank: CrankRecord::default(),
+            metrics: MetricCounters::default(),
+            observers: cfg.observers,
         }));
-        let mut id = 0;
-        for (key, setup) in cfg.vats {
-            let vat_id = VatID(id);
-            id += 1;
+        for (id, (key, handler)) in cfg.bridges.into_iter().enumerate() {
+            let device_id = DeviceID(id as u32);
+            let mut kd = kd.borrow_mut();
+            kd.device_names.insert(DeviceName(key.0.clone()), device_id);
+            let dd = DeviceData {
+                name: key,
+                handler: Rc::from(handler),
+                inbound_handler: None,
+            };
+            kd.device_data.insert(device_id, dd);
+        }
+        for (id, (key, setup)) in cfg.vats.into_iter().enumerate() {
+            let vat_id = VatID(id as u32);
+            kd.borrow_mut().vat_names.insert(key.clone(), vat_id);
             kd.borrow_mut()
-                .vat_names
-                .insert(VatName(key.0.clone()), vat_id);
+                .emit(KernelEvent::VatCreated { vat: key.clone() });
             let vd = VatData {
                 vat_id,
+                name: key,
                 import_clist: CList::new(),
                 promise_clist: CList::new(),
+                promise_aliases: HashMap::new(),
                 resolver_clist: CList::new(),
+                device_clist: CList::new(),
             };
             kd.borrow_mut().vat_data.insert(vat_id, vd);
             let syscall = VatSyscall::new(vat_id, kd.clone());
             let dispatch = setup(Box::new(syscall));
             vat_dispatch.insert(vat_id, dispatch);
         }
-        Kernel { vat_dispatch, kd }
+        let mut guards = HashMap::new();
+        for ((vat, export), interface) in cfg.guards {
+            let vat_id = match kd.borrow().vat_names.get(&vat) {
+                Some(vat_id) => *vat_id,
+                None => panic!("cannot guard an export of unknown vat {}", vat),
+            };
+            guards.insert(KernelExport(vat_id, KernelExportID(export)), interface);
+        }
+        Kernel {
+            vat_dispatch,
+            kd,
+            check_invariants,
+            scheduler,
+            e_order,
+            guards,
+        }
     }
 
     /*
@@ -239,14 +762,52 @@ impl Kernel {
         // allocation doesn't conflict with the one we add now (e.g. a loop()
         // that keeps trying higher numbers until it finds a free one)
         let mut kd = self.kd.borrow_mut();
-        let for_vat_id = *kd.vat_names.get(&for_vat).unwrap();
-        let to_vat_id = *kd.vat_names.get(&to_vat).unwrap();
+        let for_vat_id = *kd.vat_names.get(for_vat).unwrap();
+        let to_vat_id = *kd.vat_names.get(to_vat).unwrap();
         kd.vat_data.get_mut(&for_vat_id).unwrap().import_clist.add(
             KernelExport(to_vat_id, KernelExportID(to_id)),
             VatImportID(for_id),
         );
     }
 
+    pub(crate) fn add_device(
+        &mut self,
+        for_vat: &VatName,
+        for_id: u32,
+        device: &DeviceName,
+    ) {
+        let mut kd = self.kd.borrow_mut();
+        let for_vat_id = *kd.vat_names.get(for_vat).unwrap();
+        let device_id = *kd.device_names.get(device).unwrap();
+        kd.vat_data
+            .get_mut(&for_vat_id)
+            .unwrap()
+            .device_clist
+            .add(device_id, VatDeviceID(for_id));
+    }
+
+    /// host-to-vat bridge traffic is delivered to whichever export the vat
+    /// registered as the bridge's inbound handler
+    pub(crate) fn bridge_inbound(
+        &mut self,
+        bridge: &DeviceName,
+        message: KernelMessage,
+    ) -> Result<(), BridgeError> {
+        let mut kd = self.kd.borrow_mut();
+        let device_id = match kd.device_names.get(bridge) {
+            Some(device_id) => *device_id,
+            None => return Err(BridgeError::UnknownBridge(bridge.clone())),
+        };
+        let dd = kd.device_data.get(&device_id).unwrap();
+        let target = match dd.inbound_handler {
+            Some(ke) => ke,
+            None => return Err(BridgeError::NoInboundHandler(dd.name.clone())),
+        };
+        let pd = PendingDelivery::Deliver { target, message };
+        kd.queue(pd);
+        Ok(())
+    }
+
     pub(crate) fn push(
         &mut self,
         name: &VatName,
@@ -254,12 +815,68 @@ impl Kernel {
         message: KernelMessage,
     ) {
         let mut kd = self.kd.borrow_mut();
-        let vat_id = *kd.vat_names.get(&name).unwrap();
+        let vat_id = *kd.vat_names.get(name).unwrap();
         let pd = PendingDelivery::Deliver {
             target: KernelExport(vat_id, export),
             message,
         };
-        kd.run_queue.0.push_back(pd);
+        kd.queue(pd);
+    }
+
+    pub(crate) fn export(&self, name: &VatName, export: KernelExportID) -> KernelExport {
+        let kd = self.kd.borrow();
+        let vat_id = *kd.vat_names.get(name).unwrap();
+        KernelExport(vat_id, export)
+    }
+
+    /// queue a message from the host, with a result promise decided by the
+    /// target vat
+    pub(crate) fn queue_to_export(
+        &mut self,
+        target: KernelExport,
+        name: &str,
+        args: KernelCapData,
+    ) -> KernelPromiseResolverID {
+        let mut kd = self.kd.borrow_mut();
+        let p = KernelPromise::Unresolved {
+            subscribers: BTreeSet::new(),
+            decider: target.0,
+        };
+        let kprid = kd.allocate_promise(p);
+        let slots = args
+            .slots
+            .into_iter()
+            .map(|slot| match slot {
+                KernelArgSlot::Promise(kp) => {
+                    KernelArgSlot::Promise(kd.current_promise(kp))
+                }
+                _ => slot,
+            })
+            .collect();
+        let message = KernelMessage {
+            name: name.to_string(),
+            args: KernelCapData {
+                body: args.body,
+                slots,
+            },
+            resolver: Some(kprid),
+        };
+        let pd = PendingDelivery::Deliver { target, message };
+        kd.queue(pd);
+        kprid
+    }
+
+    pub(crate) fn promise_future(
+        &self,
+        kprid: KernelPromiseResolverID,
+    ) -> KernelPromiseFuture {
+        KernelPromiseFuture::new(kprid, self.kd.clone())
+    }
+
+    pub(crate) fn promise_status(&self, kprid: KernelPromiseResolverID) -> PromiseStatus {
+        let kd = self.kd.borrow();
+        let kprid = kd.current_promise(kprid);
+        PromiseStatus::from(kd.promises.get(&kprid).unwrap())
     }
 
     /// exports return home with the same index
@@ -267,23 +884,63 @@ impl Kernel {
         VatExportID(id.0)
     }
 
+    /// Check a message against its target's Interface, if it has one. A
+    /// refused message is not delivered: its result promise is rejected
+    /// instead, along with anything pipelined on it.
+    fn refused(&mut self, target: KernelExport, kmsg: &KernelMessage) -> bool {
+        let interface = match self.guards.get(&target) {
+            Some(interface) => interface,
+            None => return false,
+        };
+        // only the kind of each slot matters to the guard
+        let slots = kmsg
+            .args
+            .slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| match slot {
+                KernelArgSlot::Export(_) => VatArgSlot::Import(VatImportID(i as u32)),
+                KernelArgSlot::Promise(_) => VatArgSlot::Promise(VatPromiseID(i as u32)),
+            })
+            .collect();
+        let args = VatCapData::new(&kmsg.args.body, slots);
+        let problem = match interface.check(&kmsg.name, &args, kmsg.resolver.is_some()) {
+            Ok(()) => return false,
+            Err(problem) => problem,
+        };
+        if let Some(kprid) = kmsg.resolver {
+            let error = serialize(&Value::error("TypeError", &problem));
+            let data = KernelCapData {
+                body: error.body,
+                slots: vec![],
+            };
+            let mut kd = self.kd.borrow_mut();
+            kd.refusing = true;
+            kd.reject_promise(kprid, data);
+            let queued = kd.take_queued_to(kprid);
+            kd.resend(queued.into());
+            kd.refusing = false;
+        }
+        true
+    }
+
     fn process(&mut self, pd: PendingDelivery) {
         match pd {
             PendingDelivery::Deliver {
                 target,
                 message: kmsg,
             } => {
+                if self.refused(target, &kmsg) {
+                    return;
+                }
                 let vat_id = target.0; // TODO nicer destructuring assignment
                 let kid = target.1;
-                println!("process.Deliver: {}.{}", target, kmsg.name);
                 let veid = self.map_inbound_target(kid);
                 let vmsg = {
                     let mut kd = self.kd.borrow_mut();
                     let vd = kd.vat_data.get_mut(&vat_id).unwrap();
-                    let ovrid: Option<VatResolverID> = match kmsg.resolver {
-                        Some(krid) => Some(vd.map_inbound_resolver(krid)),
-                        None => None,
-                    };
+                    let ovrid: Option<VatResolverID> =
+                        kmsg.resolver.map(|krid| vd.map_inbound_resolver(krid));
                     InboundVatMessage {
                         name: kmsg.name,
                         args: VatCapData {
@@ -307,18 +964,12 @@ impl Kernel {
                 target: target_kprid,
                 message: kmsg,
             } => {
-                println!(
-                    "process.DeliverPromise: {} {}.{}",
-                    vat_id, target_kprid, kmsg.name
-                );
                 let (target_vrid, vmsg) = {
                     let mut kd = self.kd.borrow_mut();
                     let vd = kd.vat_data.get_mut(&vat_id).unwrap();
                     let target_vrid = vd.get_inbound_resolver(target_kprid);
-                    let ovrid: Option<VatResolverID> = match kmsg.resolver {
-                        Some(krid) => Some(vd.map_inbound_resolver(krid)),
-                        None => None,
-                    };
+                    let ovrid: Option<VatResolverID> =
+                        kmsg.resolver.map(|krid| vd.map_inbound_resolver(krid));
                     let vmsg = InboundVatMessage {
                         name: kmsg.name,
                         args: VatCapData {
@@ -343,8 +994,7 @@ impl Kernel {
                 target,
                 data: kdata,
             } => {
-                println!("pd::nftd");
-                let (vdata, vpid) = {
+                let (vdata, vpids) = {
                     let mut kd = self.kd.borrow_mut();
                     let vd = kd.vat_data.get_mut(&vat_id).unwrap();
                     let vdata = VatCapData {
@@ -355,11 +1005,13 @@ impl Kernel {
                             .map(|slot| vd.map_inbound_arg_slot(slot))
                             .collect(),
                     };
-                    let vpid = vd.map_inbound_promise(target);
-                    (vdata, vpid)
+                    let vpids = vd.notified_promises(target);
+                    (vdata, vpids)
                 };
                 let dispatch = self.vat_dispatch.get_mut(&vat_id).unwrap();
-                dispatch.notify_fulfill_to_data(vpid, vdata);
+                for vpid in vpids {
+                    dispatch.notify_fulfill_to_data(vpid, vdata.clone());
+                }
             }
 
             PendingDelivery::NotifyFulfillToTarget {
@@ -367,15 +1019,17 @@ impl Kernel {
                 target,
                 result,
             } => {
-                let (vpid, vrt) = {
+                let (vpids, vrt) = {
                     let mut kd = self.kd.borrow_mut();
                     let vd = kd.vat_data.get_mut(&vat_id).unwrap();
-                    let vpid = vd.map_inbound_promise(target);
+                    let vpids = vd.notified_promises(target);
                     let vrt = vd.map_inbound_resolve_target(result);
-                    (vpid, vrt)
+                    (vpids, vrt)
                 };
                 let dispatch = self.vat_dispatch.get_mut(&vat_id).unwrap();
-                dispatch.notify_fulfill_to_target(vpid, vrt);
+                for vpid in vpids {
+                    dispatch.notify_fulfill_to_target(vpid, vrt);
+                }
             }
 
             PendingDelivery::NotifyReject {
@@ -383,7 +1037,7 @@ impl Kernel {
                 target,
                 data: kdata,
             } => {
-                let (vdata, vpid) = {
+                let (vdata, vpids) = {
                     let mut kd = self.kd.borrow_mut();
                     let vd = kd.vat_data.get_mut(&vat_id).unwrap();
                     let vdata = VatCapData {
@@ -394,38 +1048,162 @@ impl Kernel {
                             .map(|slot| vd.map_inbound_arg_slot(slot))
                             .collect(),
                     };
-                    let vpid = vd.map_inbound_promise(target);
-                    (vdata, vpid)
+                    let vpids = vd.notified_promises(target);
+                    (vdata, vpids)
                 };
                 let dispatch = self.vat_dispatch.get_mut(&vat_id).unwrap();
-                dispatch.notify_reject(vpid, vdata);
+                for vpid in vpids {
+                    dispatch.notify_reject(vpid, vdata.clone());
+                }
             }
         };
     }
 
-    pub fn step(&mut self) {
-        println!("kernel.step");
-        let pdo = self.kd.borrow_mut().run_queue.0.pop_front();
-        if let Some(pd) = pdo {
-            self.process(pd);
+    pub fn step(&mut self) -> Option<CrankReport> {
+        let next = self.scheduler.next(&self.kd.borrow().run_queue)?;
+        self.step_at(next)
+    }
+
+    /// deliver the run-queue entry at `index`, whatever the scheduler
+    /// would have picked, as long as it is eligible to go next
+    pub fn step_at(&mut self, index: usize) -> Option<CrankReport> {
+        let (pd, delivery) = {
+            let mut kd = self.kd.borrow_mut();
+            if !eligible(&kd.run_queue).contains(&index) {
+                return None;
+            }
+            let entry = kd.run_queue.0.remove(index)?;
+            if let Some(checker) = &mut self.e_order {
+                if let Err(problem) = checker.check(&entry, &kd) {
+                    panic!("E-order violated: {}", problem);
+                }
+            }
+            kd.crank = CrankRecord::default();
+            kd.delivering = Some(entry.id);
+            let crank = kd.crank_num;
+            kd.emit(KernelEvent::CrankStart { crank });
+            let delivery = kd.describe(&entry);
+            let (args, result) = kd.delivery_args(&entry.pd);
+            kd.emit(KernelEvent::Delivery {
+                delivery: delivery.clone(),
+                args,
+                result,
+            });
+            (entry.pd, delivery)
+        };
+        let start = Instant::now();
+        self.process(pd);
+        let elapsed = start.elapsed();
+        let mut kd = self.kd.borrow_mut();
+        kd.delivering = None;
+        let crank = kd.crank_num;
+        kd.crank_num += 1;
+        let record = std::mem::take(&mut kd.crank);
+        let report = CrankReport {
+            crank,
+            delivery,
+            syscalls: record.syscalls,
+            queued: record.queued,
+            promises_created: record.promises_created,
+            promises_resolved: record.promises_resolved,
+            elapsed,
+        };
+        kd.metrics.record_crank(&report);
+        kd.emit(KernelEvent::CrankFinish {
+            report: report.clone(),
+        });
+        if self.check_invariants {
+            let problems = kd.check_invariants();
+            if !problems.is_empty() {
+                panic!(
+                    "kernel invariants violated after crank {}:\n{}",
+                    crank,
+                    problems.join("\n")
+                );
+            }
         }
+        Some(report)
+    }
+
+    pub(crate) fn has_work(&self) -> bool {
+        !self.kd.borrow().run_queue.0.is_empty()
+    }
+
+    pub fn run(&mut self) -> RunSummary {
+        self.run_until(|_| false)
     }
 
-    pub fn run(&mut self) {
-        println!("kernel.run");
-        loop {
-            if self.kd.borrow_mut().run_queue.0.is_empty() {
-                return;
+    /// run until the run queue is empty, or until `done` returns true for
+    /// the report of the crank just performed
+    pub fn run_until<F>(&mut self, mut done: F) -> RunSummary
+    where
+        F: FnMut(&CrankReport) -> bool,
+    {
+        let mut summary = RunSummary::default();
+        while let Some(report) = self.step() {
+            summary.add(&report);
+            if done(&report) {
+                break;
             }
-            self.step();
         }
+        summary
+    }
+
+    pub fn run_with_limit(&mut self, max_cranks: u64) -> RunSummary {
+        if max_cranks == 0 {
+            return RunSummary::default();
+        }
+        let mut cranks = 0;
+        self.run_until(|_| {
+            cranks += 1;
+            cranks >= max_cranks
+        })
+    }
+
+    /// Run until the computron budget is used up (or the run queue is
+    /// empty). The crank that exhausts the budget is allowed to finish, so
+    /// the budget may be overspent by part of one crank. Returns the unspent
+    /// budget.
+    pub fn run_block(&mut self, budget: u64) -> (RunSummary, u64) {
+        if budget == 0 {
+            return (RunSummary::default(), 0);
+        }
+        let mut used = 0;
+        let summary = self.run_until(|report| {
+            used += report.computrons();
+            used >= budget
+        });
+        (summary, budget.saturating_sub(used))
+    }
+
+    pub(crate) fn add_observer(&mut self, observer: Box<dyn KernelObserver>) {
+        self.kd.borrow_mut().observers.push(observer);
+    }
+
+    pub(crate) fn dump_state(&self) -> KernelSnapshotView {
+        KernelSnapshotView::from(&*self.kd.borrow())
+    }
+
+    pub(crate) fn metrics(&self) -> KernelMetrics {
+        KernelMetrics::from(&*self.kd.borrow())
+    }
+
+    pub(crate) fn check_invariants(&self) -> Vec<String> {
+        self.kd.borrow().check_invariants()
     }
 
     pub fn dump(&self) {
-        println!("Kernel Dump:");
-        println!(" run-queue:");
-        for pd in &self.kd.borrow().run_queue.0 {
-            println!("  {:?}", pd);
+        print!("{}", self.dump_state());
+    }
+
+    /// terminate every vat, in the order they were created
+    pub fn shutdown(self) {
+        let mut kd = self.kd.borrow_mut();
+        let mut vat_ids: Vec<VatID> = kd.vat_data.keys().cloned().collect();
+        vat_ids.sort_by_key(|vat_id| vat_id.0);
+        for vat_id in vat_ids {
+            let vat = kd.vat_data.get(&vat_id).unwrap().name.clone();
+            kd.emit(KernelEvent::VatTerminated { vat });
         }
     }
 }
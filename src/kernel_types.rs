@@ -1,11 +1,17 @@
 use std::fmt;
 
-#[derive(PartialEq, Eq, Debug, Hash)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Clone)]
 pub struct VatName(pub String);
 
-#[derive(PartialEq, Eq, Debug, Hash, Copy, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Copy, Clone)]
 pub struct VatID(pub u32);
 
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Clone)]
+pub struct DeviceName(pub String);
+
+#[derive(PartialEq, Eq, Debug, Hash, Copy, Clone)]
+pub struct DeviceID(pub u32);
+
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub struct KernelExportID(pub u32);
 
@@ -58,6 +64,16 @@ impl fmt::Display for VatID {
         write!(f, "vat{}", self.0)
     }
 }
+impl fmt::Display for DeviceName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dev-{}", self.0)
+    }
+}
+impl fmt::Display for DeviceID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dev{}", self.0)
+    }
+}
 impl fmt::Display for KernelExportID {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "KernelExportID-{}", self.0)
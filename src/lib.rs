@@ -1,20 +1,91 @@
+mod async_vat;
+mod causeway;
+mod chrome;
 mod clist;
 mod config;
 mod controller;
+mod device;
 mod dispatch;
+mod dot;
+#[cfg(feature = "serde")]
+mod far;
+mod golden;
+mod guard;
+mod host;
+mod invariants;
+mod json;
 mod kernel;
 mod kernel_types;
+mod liveslots;
+mod marshal;
+mod metrics;
+mod mock;
+mod observer;
 mod promise;
+#[cfg(feature = "serde")]
+mod remote;
+mod report;
+mod scheduler;
+mod scripted;
+mod slog;
+mod snapshot;
 mod syscall;
+#[cfg(feature = "serde")]
+mod typed;
 mod vat;
 mod vat_types;
 
-pub use config::{Config, Setup};
+pub use async_vat::{
+    e, AsyncVat, Far, Fulfillment, PromiseFuture, Settlement, Task, VatHandle, E,
+};
+pub use causeway::CausewayTrace;
+pub use chrome::ChromeTrace;
+pub use config::{BridgeHandler, Config, Setup};
 pub use controller::Controller;
+pub use device::BridgeError;
 pub use dispatch::Dispatch;
-pub use kernel_types::VatName;
+#[cfg(feature = "serde")]
+pub use far::{FarObject, FarVat};
+pub use golden::{assert_golden, Transcript, UPDATE_GOLDEN};
+pub use guard::{Interface, MethodGuard, Shape};
+pub use host::{
+    HostArgSlot, HostCapData, KernelExportHandle, KernelPromiseFuture,
+    KernelPromiseHandle, PromiseStatus,
+};
+pub use kernel_types::{DeviceName, VatName};
+pub use liveslots::{Args, LiveSlots, Presence, Ref, RemotableObject, Resolution};
+pub use marshal::{serialize, unserialize, MarshalError, Value};
+pub use metrics::{Histogram, KernelMetrics};
+pub use mock::{MockSyscall, RecordedSyscall};
+pub use observer::{KernelEvent, KernelObserver, NullObserver, StderrLogger};
+#[cfg(feature = "serde")]
+pub use remote::{Call, Promised, Remote, RemoteFuture};
+pub use report::{
+    Cause, CrankReport, DeliveryKind, DeliveryReport, MessageID, RunSummary,
+    SyscallReport,
+};
+pub use scripted::{Delivery, EventLog, Script, ScriptedVat};
+pub use slog::{Clock, SlogWriter, SystemClock};
+pub use snapshot::{KernelSnapshotView, PromiseSnapshot, QueuedDelivery, VatSnapshot};
+#[cfg(feature = "macros")]
+pub use swingset_macros::{far_interface, far_object};
 pub use syscall::Syscall;
+#[cfg(feature = "serde")]
+pub use typed::{from_capdata, from_value, to_capdata, to_value, Slot};
 pub use vat_types::{
-    InboundVatMessage, OutboundVatMessage, VatArgSlot, VatCapData, VatExportID,
-    VatImportID, VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
+    InboundVatMessage, OutboundVatMessage, VatArgSlot, VatCapData, VatDeviceID,
+    VatExportID, VatImportID, VatPromiseID, VatResolveTarget, VatResolverID,
+    VatSendTarget,
 };
+
+// the randomized tests draw from the chaos scheduler's generator
+#[cfg(feature = "fuzz")]
+#[doc(hidden)]
+pub use scheduler::Rng;
+
+// used by the code #[far_object] generates
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub mod __private {
+    pub use super::far::{decode_args, encode_result, method_failed, no_such_method};
+}
@@ -0,0 +1,350 @@
+use super::config::Setup;
+use super::dispatch::Dispatch;
+use super::syscall::Syscall;
+use super::vat_types::{
+    InboundVatMessage, OutboundVatMessage, VatArgSlot, VatCapData, VatExportID,
+    VatImportID, VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// RemotableObject is an object which other vats can send messages to. The
+/// first time one is passed out of its vat (in the slots of a message or a
+/// resolution), LiveSlots exports it.
+pub trait RemotableObject {
+    /// Handle a message sent to this object. `result` is the resolver for
+    /// the message's result promise, if the sender asked for one: the
+    /// object should resolve it, now or later.
+    fn deliver(
+        &self,
+        vat: &mut LiveSlots,
+        method: &str,
+        args: Args,
+        result: Option<VatResolverID>,
+    );
+}
+
+/// Presence stands in for an object in some other vat: messages can be
+/// sent to it, and it can be passed along to others
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct Presence(pub VatImportID);
+
+/// Ref is a reference carried in message arguments or resolutions
+#[derive(Clone)]
+pub enum Ref {
+    Local(Rc<dyn RemotableObject>),
+    Remote(Presence),
+    Promise(VatPromiseID),
+}
+
+impl fmt::Debug for Ref {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ref::Local(obj) => write!(f, "Local({:p})", Rc::as_ptr(obj) as *const ()),
+            Ref::Remote(p) => write!(f, "Remote({})", p.0),
+            Ref::Promise(p) => write!(f, "Promise({})", p),
+        }
+    }
+}
+
+impl Ref {
+    /// the object this refers to, if it lives in this vat
+    pub fn local(&self) -> Option<&Rc<dyn RemotableObject>> {
+        match self {
+            Ref::Local(obj) => Some(obj),
+            _ => None,
+        }
+    }
+}
+
+/// Args are the arguments of a message, or the data of a resolution: the
+/// body, and the references it mentions (by index, in whatever encoding the
+/// body uses)
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    pub body: Vec<u8>,
+    pub refs: Vec<Ref>,
+}
+
+impl Args {
+    pub fn new(body: &[u8], refs: Vec<Ref>) -> Self {
+        Args {
+            body: body.to_vec(),
+            refs,
+        }
+    }
+}
+
+/// what a promise was resolved to, as passed to a `when` callback
+#[derive(Debug, Clone)]
+pub enum Resolution {
+    Fulfilled(Ref),
+    Data(Args),
+    Rejected(Args),
+}
+
+type Callback = Box<dyn FnOnce(&mut LiveSlots, Resolution)>;
+
+// where messages pipelined on a promise go once this vat resolves it
+enum Settled {
+    To(Ref),
+    Broken(VatCapData),
+}
+
+/// LiveSlots runs a vat made of RemotableObjects. It implements Dispatch by
+/// routing each message to the object it targets, and keeps the tables
+/// that let objects refer to one another across vats: exports (assigned
+/// IDs as they are passed out), imports (presented as Presences), and
+/// promises (whose resolutions can be awaited with `when`).
+pub struct LiveSlots {
+    syscall: Box<dyn Syscall>,
+    exports: HashMap<VatExportID, Rc<dyn RemotableObject>>,
+    // keyed by the address of each exported object
+    export_ids: HashMap<*const (), VatExportID>,
+    next_export: u32,
+    // messages sent to each promise this vat decides, awaiting resolution
+    pipelined: HashMap<VatResolverID, Vec<InboundVatMessage>>,
+    callbacks: HashMap<VatPromiseID, Vec<Callback>>,
+}
+
+fn address(obj: &Rc<dyn RemotableObject>) -> *const () {
+    Rc::as_ptr(obj) as *const ()
+}
+
+impl LiveSlots {
+    /// `root` is export 0, which receives the bootstrap message
+    pub fn new(syscall: Box<dyn Syscall>, root: Rc<dyn RemotableObject>) -> Self {
+        let mut vat = LiveSlots {
+            syscall,
+            exports: HashMap::new(),
+            export_ids: HashMap::new(),
+            next_export: 0,
+            pipelined: HashMap::new(),
+            callbacks: HashMap::new(),
+        };
+        vat.export(&root);
+        vat
+    }
+
+    /// the Setup for Config::add_vat, for a vat whose root object is made
+    /// by `root`
+    pub fn setup<F>(root: F) -> Box<Setup>
+    where
+        F: FnOnce() -> Rc<dyn RemotableObject> + 'static,
+    {
+        Box::new(move |syscall| -> Box<dyn Dispatch> {
+            Box::new(LiveSlots::new(syscall, root()))
+        })
+    }
+
+    /// the export ID of `obj`, exporting it if this is the first time
+    pub fn export(&mut self, obj: &Rc<dyn RemotableObject>) -> VatExportID {
+        if let Some(id) = self.export_ids.get(&address(obj)) {
+            return *id;
+        }
+        let id = VatExportID(self.next_export);
+        self.next_export += 1;
+        self.exports.insert(id, obj.clone());
+        self.export_ids.insert(address(obj), id);
+        id
+    }
+
+    fn slot(&mut self, r: &Ref) -> VatArgSlot {
+        match r {
+            Ref::Local(obj) => VatArgSlot::Export(self.export(obj)),
+            Ref::Remote(p) => VatArgSlot::Import(p.0),
+            Ref::Promise(p) => VatArgSlot::Promise(*p),
+        }
+    }
+
+    fn capdata(&mut self, args: &Args) -> VatCapData {
+        let slots = args.refs.iter().map(|r| self.slot(r)).collect();
+        VatCapData::new(&args.body, slots)
+    }
+
+    fn local(&self, id: VatExportID) -> Rc<dyn RemotableObject> {
+        match self.exports.get(&id) {
+            Some(obj) => obj.clone(),
+            None => panic!("no such export {}", id),
+        }
+    }
+
+    fn reference(&self, slot: VatArgSlot) -> Ref {
+        match slot {
+            VatArgSlot::Export(id) => Ref::Local(self.local(id)),
+            VatArgSlot::Import(id) => Ref::Remote(Presence(id)),
+            VatArgSlot::Promise(id) => Ref::Promise(id),
+        }
+    }
+
+    fn args(&self, data: VatCapData) -> Args {
+        let refs = data.slots.iter().map(|s| self.reference(*s)).collect();
+        Args {
+            body: data.body,
+            refs,
+        }
+    }
+
+    fn target(&self, target: &Ref) -> VatSendTarget {
+        match target {
+            Ref::Remote(p) => VatSendTarget::Import(p.0),
+            Ref::Promise(p) => VatSendTarget::Promise(*p),
+            Ref::Local(_) => panic!("eventual sends to local objects are not supported"),
+        }
+    }
+
+    /// send a message to a remote object or a promise, returning the
+    /// promise for its result
+    pub fn send(&mut self, target: &Ref, method: &str, args: Args) -> VatPromiseID {
+        let target = self.target(target);
+        let args = self.capdata(&args);
+        let vmsg = OutboundVatMessage::new(method, &args.body, args.slots);
+        self.syscall.send(target, vmsg)
+    }
+
+    /// send a message without asking for its result
+    pub fn send_only(&mut self, target: &Ref, method: &str, args: Args) {
+        let target = self.target(target);
+        let args = self.capdata(&args);
+        let vmsg = OutboundVatMessage::new(method, &args.body, args.slots);
+        self.syscall.send_only(target, vmsg);
+    }
+
+    /// make a new promise, for this vat to resolve
+    pub fn allocate_promise(&mut self) -> (VatPromiseID, VatResolverID) {
+        let (promise, resolver) = self.syscall.allocate_promise_and_resolver();
+        self.pipelined.insert(resolver, vec![]);
+        (promise, resolver)
+    }
+
+    /// Call `callback` when `promise` is resolved. Only promises this vat
+    /// does not decide can be waited on.
+    pub fn when<F>(&mut self, promise: VatPromiseID, callback: F)
+    where
+        F: FnOnce(&mut LiveSlots, Resolution) + 'static,
+    {
+        let callbacks = self.callbacks.entry(promise).or_default();
+        if callbacks.is_empty() {
+            self.syscall.subscribe(promise);
+        }
+        callbacks.push(Box::new(callback));
+    }
+
+    /// Resolve a promise to an object, or (for a promise) forward it.
+    /// Messages already pipelined on it are passed along.
+    pub fn resolve(&mut self, resolver: VatResolverID, to: Ref) {
+        match &to {
+            Ref::Local(obj) => {
+                let target = VatResolveTarget::Export(self.export(obj));
+                self.syscall.fulfill_to_target(resolver, target);
+            }
+            Ref::Remote(p) => {
+                let target = VatResolveTarget::Import(p.0);
+                self.syscall.fulfill_to_target(resolver, target);
+            }
+            Ref::Promise(p) => self.syscall.forward(resolver, *p),
+        }
+        self.settle(resolver, Settled::To(to));
+    }
+
+    /// resolve a promise to data, rejecting any messages pipelined on it
+    pub fn resolve_to_data(&mut self, resolver: VatResolverID, data: Args) {
+        let data = self.capdata(&data);
+        self.syscall.fulfill_to_data(resolver, data);
+        let broken = VatCapData::new(b"cannot send to data", vec![]);
+        self.settle(resolver, Settled::Broken(broken));
+    }
+
+    /// reject a promise, and any messages pipelined on it
+    pub fn reject(&mut self, resolver: VatResolverID, data: Args) {
+        let data = self.capdata(&data);
+        self.syscall.reject(resolver, data.clone());
+        self.settle(resolver, Settled::Broken(data));
+    }
+
+    fn settle(&mut self, resolver: VatResolverID, settled: Settled) {
+        for message in self.pipelined.remove(&resolver).unwrap_or_default() {
+            self.redirect(&settled, message);
+        }
+    }
+
+    /// pass along a message pipelined on a promise this vat has resolved
+    fn redirect(&mut self, settled: &Settled, message: InboundVatMessage) {
+        match settled {
+            Settled::To(Ref::Local(obj)) => self.dispatch(obj.clone(), message),
+            Settled::To(to) => {
+                let target = self.target(to);
+                let vmsg = OutboundVatMessage::new(
+                    &message.name,
+                    &message.args.body,
+                    message.args.slots,
+                );
+                match message.resolver {
+                    Some(r) => {
+                        let p = self.syscall.send(target, vmsg);
+                        self.syscall.forward(r, p);
+                    }
+                    None => self.syscall.send_only(target, vmsg),
+                }
+            }
+            Settled::Broken(data) => {
+                if let Some(r) = message.resolver {
+                    self.syscall.reject(r, data.clone());
+                }
+            }
+        }
+    }
+
+    fn dispatch(&mut self, obj: Rc<dyn RemotableObject>, message: InboundVatMessage) {
+        let args = self.args(message.args);
+        obj.deliver(self, &message.name, args, message.resolver);
+    }
+
+    // the result of each message is a promise this vat now decides
+    fn receive(&mut self, message: &InboundVatMessage) {
+        if let Some(r) = message.resolver {
+            self.pipelined.entry(r).or_default();
+        }
+    }
+
+    fn notify(&mut self, promise: VatPromiseID, resolution: Resolution) {
+        for callback in self.callbacks.remove(&promise).unwrap_or_default() {
+            callback(self, resolution.clone());
+        }
+    }
+}
+
+impl Dispatch for LiveSlots {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        let obj = self.local(target);
+        self.receive(&message);
+        self.dispatch(obj, message);
+    }
+
+    fn deliver_promise(&mut self, target: VatResolverID, message: InboundVatMessage) {
+        self.receive(&message);
+        match self.pipelined.get_mut(&target) {
+            Some(messages) => messages.push(message),
+            None => panic!("no unresolved promise for resolver {}", target),
+        }
+    }
+
+    fn notify_fulfill_to_target(&mut self, id: VatPromiseID, target: VatResolveTarget) {
+        let to = match target {
+            VatResolveTarget::Export(e) => Ref::Local(self.local(e)),
+            VatResolveTarget::Import(i) => Ref::Remote(Presence(i)),
+        };
+        self.notify(id, Resolution::Fulfilled(to));
+    }
+
+    fn notify_fulfill_to_data(&mut self, id: VatPromiseID, data: VatCapData) {
+        let data = self.args(data);
+        self.notify(id, Resolution::Data(data));
+    }
+
+    fn notify_reject(&mut self, id: VatPromiseID, data: VatCapData) {
+        let data = self.args(data);
+        self.notify(id, Resolution::Rejected(data));
+    }
+}
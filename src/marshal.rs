@@ -0,0 +1,312 @@
+use super::json::Json;
+use super::vat_types::{VatArgSlot, VatCapData};
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+
+// The smallcaps encoding, as used by JavaScript vats (@endo/marshal): the
+// body is "#" followed by JSON. Strings carry everything JSON cannot
+// express, marked by their first character:
+//
+//   "#undefined", "#NaN", "#Infinity", "#-Infinity"   constants
+//   "+123", "-123"                                    bigints
+//   "$0.Alleged: name"                                slot 0, an object
+//   "&1"                                              slot 1, a promise
+//   "!..."                                            a string which starts
+//                                                     with one of !"#$%&'()*+,-
+//
+// Errors and tagged values are objects with a "#error" or "#tag" member.
+// Record keys are encoded like any other string, so cannot be mistaken for
+// those.
+
+/// a structured value, as carried in a VatCapData body
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    BigInt(i128),
+    String(String),
+    List(Vec<Value>),
+    /// a record, with its keys in sorted order
+    Record(BTreeMap<String, Value>),
+    /// a tagged value, e.g. the "copySet" or "copyBag" of JavaScript vats
+    Tagged(String, Box<Value>),
+    Error {
+        name: String,
+        message: String,
+    },
+    /// a reference to an object or promise, which marshals as an index into
+    /// the slots. The interface name (e.g. "Alleged: purse") is only
+    /// carried with the first reference to each slot.
+    Slot {
+        slot: VatArgSlot,
+        iface: Option<String>,
+    },
+}
+
+impl Value {
+    pub fn record<K: Into<String>>(members: Vec<(K, Value)>) -> Self {
+        Value::Record(members.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    pub fn slot(slot: VatArgSlot) -> Self {
+        Value::Slot { slot, iface: None }
+    }
+
+    pub fn error(name: &str, message: &str) -> Self {
+        Value::Error {
+            name: name.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::Number(f64::from(n))
+    }
+}
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+impl From<VatArgSlot> for Value {
+    fn from(slot: VatArgSlot) -> Value {
+        Value::slot(slot)
+    }
+}
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Value {
+        Value::List(items.into_iter().map(|i| i.into()).collect())
+    }
+}
+
+/// why a body could not be unserialized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarshalError(pub String);
+
+impl fmt::Display for MarshalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "marshal: {}", self.0)
+    }
+}
+
+impl error::Error for MarshalError {}
+
+fn fail<T>(what: String) -> Result<T, MarshalError> {
+    Err(MarshalError(what))
+}
+
+/// the first characters which mark a string as something else
+fn is_special(s: &str) -> bool {
+    match s.chars().next() {
+        Some(c) => ('!'..='-').contains(&c),
+        None => false,
+    }
+}
+
+fn escape(s: &str) -> String {
+    if is_special(s) {
+        format!("!{}", s)
+    } else {
+        s.to_string()
+    }
+}
+
+fn encode_string(s: &str) -> Json {
+    Json::String(escape(s))
+}
+
+#[derive(Default)]
+struct Encoder {
+    slots: Vec<VatArgSlot>,
+    indices: HashMap<VatArgSlot, usize>,
+}
+
+impl Encoder {
+    fn encode(&mut self, value: &Value) -> Json {
+        match value {
+            Value::Undefined => Json::from("#undefined"),
+            Value::Null => Json::Null,
+            Value::Bool(b) => Json::Bool(*b),
+            Value::Number(n) if n.is_nan() => Json::from("#NaN"),
+            Value::Number(n) if *n == f64::INFINITY => Json::from("#Infinity"),
+            Value::Number(n) if *n == f64::NEG_INFINITY => Json::from("#-Infinity"),
+            Value::Number(n) => Json::Float(*n),
+            Value::BigInt(n) if *n < 0 => Json::String(n.to_string()),
+            Value::BigInt(n) => Json::String(format!("+{}", n)),
+            Value::String(s) => encode_string(s),
+            Value::List(items) => {
+                Json::Array(items.iter().map(|i| self.encode(i)).collect())
+            }
+            Value::Record(members) => Json::Object(
+                members
+                    .iter()
+                    .map(|(k, v)| (escape(k), self.encode(v)))
+                    .collect(),
+            ),
+            Value::Tagged(tag, payload) => Json::object()
+                .with("#tag", encode_string(tag))
+                .with("payload", self.encode(payload)),
+            Value::Error { name, message } => Json::object()
+                .with("#error", encode_string(message))
+                .with("name", encode_string(name)),
+            Value::Slot { slot, iface } => {
+                let prefix = match slot {
+                    VatArgSlot::Promise(_) => '&',
+                    _ => '$',
+                };
+                if let Some(index) = self.indices.get(slot) {
+                    return Json::String(format!("{}{}", prefix, index));
+                }
+                let index = self.slots.len();
+                self.slots.push(*slot);
+                self.indices.insert(*slot, index);
+                match iface {
+                    Some(iface) => Json::String(format!("{}{}.{}", prefix, index, iface)),
+                    None => Json::String(format!("{}{}", prefix, index)),
+                }
+            }
+        }
+    }
+}
+
+/// serialize `value` into a smallcaps body, and the slots it refers to
+pub fn serialize(value: &Value) -> VatCapData {
+    let mut encoder = Encoder::default();
+    let body = format!("#{}", encoder.encode(value));
+    VatCapData::new(body.as_bytes(), encoder.slots)
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn decode_string(s: &str) -> Result<String, MarshalError> {
+    match s.strip_prefix('!') {
+        Some(rest) => Ok(rest.to_string()),
+        None if is_special(s) => fail(format!("{:?} is not a plain string", s)),
+        None => Ok(s.to_string()),
+    }
+}
+
+fn decode_slot(
+    encoded: &str,
+    slots: &[VatArgSlot],
+    promise: bool,
+) -> Result<Value, MarshalError> {
+    let (index, iface) = match encoded[1..].find('.') {
+        Some(dot) => (&encoded[1..=dot], Some(encoded[dot + 2..].to_string())),
+        None => (&encoded[1..], None),
+    };
+    let slot = match index.parse::<usize>().ok().and_then(|i| slots.get(i)) {
+        Some(slot) if is_digits(index) => *slot,
+        _ => return fail(format!("bad slot reference {:?}", encoded)),
+    };
+    if let VatArgSlot::Promise(_) = slot {
+        if !promise {
+            return fail(format!("{:?} refers to a promise", encoded));
+        }
+    } else if promise {
+        return fail(format!("{:?} does not refer to a promise", encoded));
+    }
+    Ok(Value::Slot { slot, iface })
+}
+
+fn decode(json: &Json, slots: &[VatArgSlot]) -> Result<Value, MarshalError> {
+    Ok(match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Int(n) => Value::Number(*n as f64),
+        Json::UInt(n) => Value::Number(*n as f64),
+        Json::Float(n) => Value::Number(*n),
+        Json::String(s) => match s.chars().next() {
+            Some('#') => match s.as_str() {
+                "#undefined" => Value::Undefined,
+                "#NaN" => Value::Number(f64::NAN),
+                "#Infinity" => Value::Number(f64::INFINITY),
+                "#-Infinity" => Value::Number(f64::NEG_INFINITY),
+                _ => return fail(format!("unknown constant {:?}", s)),
+            },
+            Some('+') | Some('-') => {
+                let n = s.strip_prefix('+').unwrap_or(s);
+                match n.parse::<i128>() {
+                    Ok(n) if is_digits(&s[1..]) => Value::BigInt(n),
+                    _ => return fail(format!("bad (or too large) bigint {:?}", s)),
+                }
+            }
+            Some('$') => decode_slot(s, slots, false)?,
+            Some('&') => decode_slot(s, slots, true)?,
+            Some('%') => return fail(format!("symbols are not supported: {:?}", s)),
+            _ => Value::String(decode_string(s)?),
+        },
+        Json::Array(items) => Value::List(
+            items
+                .iter()
+                .map(|i| decode(i, slots))
+                .collect::<Result<_, _>>()?,
+        ),
+        Json::Object(members) => {
+            let get = |key: &str| members.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+            let string = |value: Option<&Json>| match value {
+                Some(Json::String(s)) => decode_string(s),
+                _ => fail(format!("malformed {}", json)),
+            };
+            if let Some(tag) = get("#tag") {
+                let payload = match get("payload") {
+                    Some(payload) => decode(payload, slots)?,
+                    None => {
+                        return fail(format!("tagged value without payload: {}", json))
+                    }
+                };
+                Value::Tagged(string(Some(tag))?, Box::new(payload))
+            } else if let Some(message) = get("#error") {
+                Value::Error {
+                    name: string(get("name"))?,
+                    message: string(Some(message))?,
+                }
+            } else {
+                let mut record = BTreeMap::new();
+                for (k, v) in members {
+                    record.insert(decode_string(k)?, decode(v, slots)?);
+                }
+                Value::Record(record)
+            }
+        }
+    })
+}
+
+/// unserialize a smallcaps body, resolving its slot references against
+/// `data.slots`
+pub fn unserialize(data: &VatCapData) -> Result<Value, MarshalError> {
+    let body = match std::str::from_utf8(&data.body) {
+        Ok(body) => body,
+        Err(_) => return fail("body is not UTF-8".to_string()),
+    };
+    let json = match body.strip_prefix('#') {
+        Some(json) => json,
+        None => return fail("body is not smallcaps (no leading '#')".to_string()),
+    };
+    match Json::parse(json) {
+        Ok(json) => decode(&json, &data.slots),
+        Err(e) => fail(e),
+    }
+}
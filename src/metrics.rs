@@ -0,0 +1,236 @@
+use super::kernel::KernelData;
+use super::kernel_types::VatName;
+use super::promise::KernelPromise;
+use super::report::CrankReport;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// upper bounds of the crank-duration histogram buckets, in seconds
+const CRANK_DURATION_BUCKETS: [f64; 10] =
+    [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Histogram counts observations into buckets by upper bound, like a
+/// Prometheus histogram. `counts[i]` is the number of observations no larger
+/// than `bounds[i]` (and larger than the previous bound): it is not
+/// cumulative. Anything above the last bound is only in `count`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Histogram {
+    pub bounds: Vec<f64>,
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[f64]) -> Self {
+        Histogram {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(i) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[i] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+/// the counters maintained by the kernel as it runs cranks
+#[derive(Debug)]
+pub(crate) struct MetricCounters {
+    cranks: u64,
+    deliveries: BTreeMap<(VatName, &'static str), u64>,
+    syscalls: BTreeMap<(VatName, &'static str), u64>,
+    crank_duration: Histogram,
+}
+
+impl Default for MetricCounters {
+    fn default() -> Self {
+        MetricCounters {
+            cranks: 0,
+            deliveries: BTreeMap::new(),
+            syscalls: BTreeMap::new(),
+            crank_duration: Histogram::new(&CRANK_DURATION_BUCKETS),
+        }
+    }
+}
+
+impl MetricCounters {
+    /// only the vat being delivered to can make syscalls, so the whole
+    /// crank can be counted at once
+    pub(crate) fn record_crank(&mut self, report: &CrankReport) {
+        let vat = &report.delivery.vat;
+        self.cranks += 1;
+        *self
+            .deliveries
+            .entry((vat.clone(), report.delivery.kind.method_name()))
+            .or_default() += 1;
+        for s in &report.syscalls {
+            *self.syscalls.entry((vat.clone(), s.name())).or_default() += 1;
+        }
+        self.crank_duration.observe(report.elapsed.as_secs_f64());
+    }
+}
+
+/// KernelMetrics is a reading of the kernel's counters and gauges, obtained
+/// from Controller::metrics. Counters cover the life of the kernel, and
+/// gauges describe its current state.
+#[derive(Debug, PartialEq, Clone)]
+pub struct KernelMetrics {
+    pub cranks: u64,
+    /// deliveries made, by vat and Dispatch method name
+    pub deliveries: BTreeMap<(VatName, &'static str), u64>,
+    /// syscalls made, by vat and Syscall method name
+    pub syscalls: BTreeMap<(VatName, &'static str), u64>,
+    pub run_queue_length: usize,
+    pub unresolved_promises: usize,
+    /// clist entries, by vat and table ("import", "promise", "resolver", or
+    /// "device")
+    pub clist_entries: BTreeMap<(VatName, &'static str), usize>,
+    pub crank_duration: Histogram,
+}
+
+impl From<&KernelData> for KernelMetrics {
+    fn from(kd: &KernelData) -> KernelMetrics {
+        let mut clist_entries = BTreeMap::new();
+        for vd in kd.vat_data.values() {
+            let name = &vd.name;
+            clist_entries
+                .insert((name.clone(), "import"), vd.import_clist.outbound.len());
+            clist_entries
+                .insert((name.clone(), "promise"), vd.promise_clist.outbound.len());
+            clist_entries
+                .insert((name.clone(), "resolver"), vd.resolver_clist.outbound.len());
+            clist_entries
+                .insert((name.clone(), "device"), vd.device_clist.outbound.len());
+        }
+        let unresolved_promises = kd
+            .promises
+            .values()
+            .filter(|p| matches!(p, KernelPromise::Unresolved { .. }))
+            .count();
+        let counters = &kd.metrics;
+        KernelMetrics {
+            cranks: counters.cranks,
+            deliveries: counters.deliveries.clone(),
+            syscalls: counters.syscalls.clone(),
+            run_queue_length: kd.run_queue.0.len(),
+            unresolved_promises,
+            clist_entries,
+            crank_duration: counters.crank_duration.clone(),
+        }
+    }
+}
+
+fn label_value(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, metric_type).unwrap();
+}
+
+fn per_vat<T: std::fmt::Display>(
+    out: &mut String,
+    name: &str,
+    label: &str,
+    values: &BTreeMap<(VatName, &'static str), T>,
+) {
+    for ((vat, key), value) in values {
+        writeln!(
+            out,
+            "{}{{vat=\"{}\",{}=\"{}\"}} {}",
+            name,
+            label_value(&vat.0),
+            label,
+            key,
+            value
+        )
+        .unwrap();
+    }
+}
+
+impl KernelMetrics {
+    /// render everything in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let name = "swingset_cranks_total";
+        header(&mut out, name, "counter", "Cranks performed.");
+        writeln!(out, "{} {}", name, self.cranks).unwrap();
+
+        let name = "swingset_deliveries_total";
+        header(
+            &mut out,
+            name,
+            "counter",
+            "Deliveries made, by vat and kind.",
+        );
+        per_vat(&mut out, name, "kind", &self.deliveries);
+
+        let name = "swingset_syscalls_total";
+        header(&mut out, name, "counter", "Syscalls made, by vat and kind.");
+        per_vat(&mut out, name, "kind", &self.syscalls);
+
+        let name = "swingset_run_queue_length";
+        header(
+            &mut out,
+            name,
+            "gauge",
+            "Deliveries waiting on the run queue.",
+        );
+        writeln!(out, "{} {}", name, self.run_queue_length).unwrap();
+
+        let name = "swingset_unresolved_promises";
+        header(&mut out, name, "gauge", "Kernel promises not yet resolved.");
+        writeln!(out, "{} {}", name, self.unresolved_promises).unwrap();
+
+        let name = "swingset_clist_entries";
+        header(&mut out, name, "gauge", "C-list entries, by vat and table.");
+        per_vat(&mut out, name, "table", &self.clist_entries);
+
+        let name = "swingset_crank_duration_seconds";
+        let h = &self.crank_duration;
+        header(
+            &mut out,
+            name,
+            "histogram",
+            "Wall-clock time of each crank.",
+        );
+        let mut cumulative = 0;
+        for (bound, count) in h.bounds.iter().zip(h.counts.iter()) {
+            cumulative += count;
+            writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative).unwrap();
+        }
+        writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, h.count).unwrap();
+        writeln!(out, "{}_sum {}", name, h.sum).unwrap();
+        writeln!(out, "{}_count {}", name, h.count).unwrap();
+        out
+    }
+
+    /// write the Prometheus text to a file, e.g. for node_exporter's
+    /// textfile collector
+    pub fn write_prometheus<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_prometheus())
+    }
+
+    /// the mean crank duration, if any cranks have been run
+    pub fn mean_crank_duration(&self) -> Option<Duration> {
+        let h = &self.crank_duration;
+        match h.count {
+            0 => None,
+            n => Some(Duration::from_secs_f64(h.sum / n as f64)),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use super::syscall::Syscall;
+use super::vat_types::{
+    OutboundVatMessage, VatCapData, VatDeviceID, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
+};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// a syscall made to a MockSyscall, with what it returned (if anything)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedSyscall {
+    Send {
+        target: VatSendTarget,
+        method: String,
+        args: VatCapData,
+        result: VatPromiseID,
+    },
+    SendOnly {
+        target: VatSendTarget,
+        method: String,
+        args: VatCapData,
+    },
+    Invoke {
+        target: VatDeviceID,
+        method: String,
+        args: VatCapData,
+        result: VatCapData,
+    },
+    AllocatePromiseAndResolver {
+        promise: VatPromiseID,
+        resolver: VatResolverID,
+    },
+    Subscribe {
+        promise: VatPromiseID,
+    },
+    FulfillToTarget {
+        resolver: VatResolverID,
+        target: VatResolveTarget,
+    },
+    FulfillToData {
+        resolver: VatResolverID,
+        data: VatCapData,
+    },
+    Reject {
+        resolver: VatResolverID,
+        data: VatCapData,
+    },
+    Forward {
+        resolver: VatResolverID,
+        target: VatPromiseID,
+    },
+}
+
+#[derive(Debug)]
+struct MockState {
+    calls: Vec<RecordedSyscall>,
+    next_promise: u32,
+    next_resolver: u32,
+    invoke_results: VecDeque<VatCapData>,
+}
+
+/// MockSyscall stands in for the kernel when unit-testing a single vat's
+/// Dispatch. It records every syscall, and hands out promise and resolver
+/// IDs counting up from zero (or from wherever `first_ids` says). Clones
+/// share their state, so a test can keep one and give the vat another:
+///
+/// ```
+/// use swingset::{MockSyscall, RecordedSyscall, Syscall};
+/// let mock = MockSyscall::new();
+/// let mut syscall: Box<dyn Syscall> = Box::new(mock.clone());
+/// let (promise, resolver) = syscall.allocate_promise_and_resolver();
+/// mock.assert_calls(vec![RecordedSyscall::AllocatePromiseAndResolver { promise, resolver }]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockSyscall {
+    state: Rc<RefCell<MockState>>,
+}
+
+impl Default for MockSyscall {
+    fn default() -> Self {
+        MockSyscall::new()
+    }
+}
+
+impl MockSyscall {
+    pub fn new() -> Self {
+        MockSyscall {
+            state: Rc::new(RefCell::new(MockState {
+                calls: vec![],
+                next_promise: 0,
+                next_resolver: 0,
+                invoke_results: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// start handing out IDs from these, e.g. to stay clear of the IDs a
+    /// test uses in the deliveries it makes
+    pub fn first_ids(&self, promise: u32, resolver: u32) {
+        let mut state = self.state.borrow_mut();
+        state.next_promise = promise;
+        state.next_resolver = resolver;
+    }
+
+    /// the result of the next (not yet scripted) syscall.invoke
+    pub fn script_invoke(&self, result: VatCapData) {
+        self.state.borrow_mut().invoke_results.push_back(result);
+    }
+
+    /// every syscall recorded so far
+    pub fn calls(&self) -> Vec<RecordedSyscall> {
+        self.state.borrow().calls.clone()
+    }
+
+    /// the syscalls recorded since the last `take`
+    pub fn take(&self) -> Vec<RecordedSyscall> {
+        std::mem::take(&mut self.state.borrow_mut().calls)
+    }
+
+    /// assert that exactly these syscalls were made since the last `take`
+    pub fn assert_calls(&self, expected: Vec<RecordedSyscall>) {
+        assert_eq!(self.take(), expected);
+    }
+
+    /// assert that no syscalls were made since the last `take`
+    pub fn assert_no_calls(&self) {
+        self.assert_calls(vec![]);
+    }
+
+    fn record(&self, call: RecordedSyscall) {
+        self.state.borrow_mut().calls.push(call);
+    }
+
+    fn allocate_promise(&self) -> VatPromiseID {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_promise;
+        state.next_promise += 1;
+        VatPromiseID(id)
+    }
+
+    fn allocate_resolver(&self) -> VatResolverID {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_resolver;
+        state.next_resolver += 1;
+        VatResolverID(id)
+    }
+}
+
+impl Syscall for MockSyscall {
+    fn send(&mut self, target: VatSendTarget, vmsg: OutboundVatMessage) -> VatPromiseID {
+        let result = self.allocate_promise();
+        self.record(RecordedSyscall::Send {
+            target,
+            method: vmsg.name,
+            args: vmsg.args,
+            result,
+        });
+        result
+    }
+
+    fn send_only(&mut self, target: VatSendTarget, vmsg: OutboundVatMessage) {
+        self.record(RecordedSyscall::SendOnly {
+            target,
+            method: vmsg.name,
+            args: vmsg.args,
+        });
+    }
+
+    fn invoke(&mut self, target: VatDeviceID, vmsg: OutboundVatMessage) -> VatCapData {
+        let result = self.state.borrow_mut().invoke_results.pop_front();
+        let result = match result {
+            Some(result) => result,
+            None => panic!("no result scripted for invoke({}, {})", target, vmsg.name),
+        };
+        self.record(RecordedSyscall::Invoke {
+            target,
+            method: vmsg.name,
+            args: vmsg.args,
+            result: result.clone(),
+        });
+        result
+    }
+
+    fn allocate_promise_and_resolver(&mut self) -> (VatPromiseID, VatResolverID) {
+        let promise = self.allocate_promise();
+        let resolver = self.allocate_resolver();
+        self.record(RecordedSyscall::AllocatePromiseAndResolver { promise, resolver });
+        (promise, resolver)
+    }
+
+    fn subscribe(&mut self, promise: VatPromiseID) {
+        self.record(RecordedSyscall::Subscribe { promise });
+    }
+
+    fn fulfill_to_target(&mut self, resolver: VatResolverID, target: VatResolveTarget) {
+        self.record(RecordedSyscall::FulfillToTarget { resolver, target });
+    }
+
+    fn fulfill_to_data(&mut self, resolver: VatResolverID, data: VatCapData) {
+        self.record(RecordedSyscall::FulfillToData { resolver, data });
+    }
+
+    fn reject(&mut self, resolver: VatResolverID, data: VatCapData) {
+        self.record(RecordedSyscall::Reject { resolver, data });
+    }
+
+    fn forward(&mut self, resolver: VatResolverID, target: VatPromiseID) {
+        self.record(RecordedSyscall::Forward { resolver, target });
+    }
+}
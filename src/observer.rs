@@ -0,0 +1,118 @@
+use super::host::{HostCapData, KernelPromiseHandle, PromiseStatus};
+use super::kernel_types::VatName;
+use super::report::{CrankReport, DeliveryReport, SyscallReport};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// KernelEvent is everything the kernel tells its observers about
+#[derive(Debug, Clone)]
+pub enum KernelEvent {
+    VatCreated {
+        vat: VatName,
+    },
+    /// vats are terminated when the kernel is shut down
+    VatTerminated {
+        vat: VatName,
+    },
+    CrankStart {
+        crank: u64,
+    },
+    /// the delivery made by the current crank, with the message arguments
+    /// (or the resolution data, for notifications) and result promise
+    Delivery {
+        delivery: DeliveryReport,
+        args: HostCapData,
+        result: Option<KernelPromiseHandle>,
+    },
+    Syscall {
+        vat: VatName,
+        syscall: SyscallReport,
+    },
+    /// a message or notification was added to the run queue, by the host or
+    /// by a syscall of the current crank
+    Queued {
+        delivery: DeliveryReport,
+    },
+    /// a promise was created, or changed state
+    PromiseState {
+        promise: KernelPromiseHandle,
+        status: PromiseStatus,
+    },
+    /// a promise was retired by syscall.forward
+    PromiseForwarded {
+        promise: KernelPromiseHandle,
+        target: KernelPromiseHandle,
+    },
+    CrankFinish {
+        report: CrankReport,
+    },
+}
+
+/// A KernelObserver is registered with Config::add_observer or
+/// Controller::add_observer, and is told about every KernelEvent as it
+/// happens. Observers are called while the kernel is busy, so they must not
+/// call back into it.
+pub trait KernelObserver {
+    fn observe(&mut self, event: &KernelEvent);
+}
+
+/// NullObserver ignores everything
+#[derive(Debug, Default)]
+pub struct NullObserver;
+impl KernelObserver for NullObserver {
+    fn observe(&mut self, _event: &KernelEvent) {}
+}
+
+/// StderrLogger writes one line per event to stderr
+#[derive(Debug, Default)]
+pub struct StderrLogger;
+impl KernelObserver for StderrLogger {
+    fn observe(&mut self, event: &KernelEvent) {
+        eprintln!("{}", event);
+    }
+}
+
+/// lets the host keep a handle on an observer after handing it to the kernel
+impl<T: KernelObserver> KernelObserver for Rc<RefCell<T>> {
+    fn observe(&mut self, event: &KernelEvent) {
+        self.borrow_mut().observe(event);
+    }
+}
+
+impl fmt::Display for KernelEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use KernelEvent::*;
+        match self {
+            VatCreated { vat } => write!(f, "vat-created {}", vat),
+            VatTerminated { vat } => write!(f, "vat-terminated {}", vat),
+            CrankStart { crank } => write!(f, "crank-start {}", crank),
+            Delivery {
+                delivery,
+                args,
+                result,
+            } => {
+                write!(f, "deliver {} {}", delivery, args)?;
+                if let Some(result) = result {
+                    write!(f, " -> {}", result)?;
+                }
+                Ok(())
+            }
+            Syscall { vat, syscall } => write!(f, "syscall {} {}", vat, syscall),
+            Queued { delivery } => write!(f, "queued {}", delivery),
+            PromiseState { promise, status } => {
+                write!(f, "promise {} {}", promise, status)
+            }
+            PromiseForwarded { promise, target } => {
+                write!(f, "promise {} forwarded to {}", promise, target)
+            }
+            CrankFinish { report } => write!(
+                f,
+                "crank-finish {} ({} syscalls, {:?})",
+                report.crank,
+                report.syscalls.len(),
+                report.elapsed
+            ),
+        }
+    }
+}
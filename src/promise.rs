@@ -1,9 +1,9 @@
 use super::kernel_types::{KernelCapData, KernelExport, VatID};
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
 pub(crate) enum KernelPromise {
     Unresolved {
-        subscribers: HashSet<VatID>,
+        subscribers: BTreeSet<VatID>,
         decider: VatID,
     },
     FulfilledToTarget(KernelExport),
@@ -0,0 +1,206 @@
+use super::async_vat::{Fulfillment, PromiseFuture, VatHandle};
+use super::marshal::{serialize, unserialize, MarshalError, Value};
+use super::syscall::Syscall;
+use super::typed::{from_value, Slot};
+use super::vat_types::{
+    OutboundVatMessage, VatArgSlot, VatCapData, VatPromiseID, VatResolveTarget,
+    VatSendTarget,
+};
+use serde::de::{self, DeserializeOwned};
+use serde::ser::{self, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Remote is a reference to an object in another vat (or a promise for one)
+/// which implements the interface `I`, usually a `dyn Trait`. Its methods
+/// come from the `TraitRemote` trait which `#[far_interface]` generates for
+/// it, so a misspelled method or a wrong argument does not compile. In
+/// message arguments, a Remote travels as a Slot.
+pub struct Remote<I: ?Sized> {
+    target: VatSendTarget,
+    interface: PhantomData<fn() -> Box<I>>,
+}
+
+impl<I: ?Sized> Remote<I> {
+    pub fn new(target: VatSendTarget) -> Self {
+        Remote {
+            target,
+            interface: PhantomData,
+        }
+    }
+
+    pub fn target(&self) -> VatSendTarget {
+        self.target
+    }
+
+    /// The message calling `method` with `args`, for the stubs which
+    /// `#[far_interface]` generates. Their argument types are fixed, so
+    /// failing to marshal them is a bug.
+    pub fn call<A: Serialize + ?Sized, T>(&self, method: &str, args: &A) -> Call<T> {
+        let message = match OutboundVatMessage::from_serde(method, args) {
+            Ok(message) => message,
+            Err(e) => panic!("cannot marshal arguments to {}: {}", method, e),
+        };
+        Call {
+            target: self.target,
+            message,
+            result: PhantomData,
+        }
+    }
+}
+
+impl<I: ?Sized> Clone for Remote<I> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I: ?Sized> Copy for Remote<I> {}
+
+impl<I: ?Sized> PartialEq for Remote<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.target == other.target
+    }
+}
+
+impl<I: ?Sized> Eq for Remote<I> {}
+
+impl<I: ?Sized> fmt::Debug for Remote<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Remote({:?})", self.target)
+    }
+}
+
+impl<I: ?Sized> Serialize for Remote<I> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let slot = match self.target {
+            VatSendTarget::Import(i) => VatArgSlot::Import(i),
+            VatSendTarget::Promise(p) => VatArgSlot::Promise(p),
+        };
+        Slot(slot).serialize(serializer)
+    }
+}
+
+impl<'de, I: ?Sized> de::Deserialize<'de> for Remote<I> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Slot::deserialize(deserializer)? {
+            Slot(VatArgSlot::Import(i)) => Ok(Remote::new(VatSendTarget::Import(i))),
+            Slot(VatArgSlot::Promise(p)) => Ok(Remote::new(VatSendTarget::Promise(p))),
+            Slot(VatArgSlot::Export(e)) => Err(de::Error::custom(format!(
+                "export {} is local, not a Remote",
+                e
+            ))),
+        }
+    }
+}
+
+/// Call is a message ready to send to a Remote, whose result is a `T`
+pub struct Call<T> {
+    target: VatSendTarget,
+    message: OutboundVatMessage,
+    result: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Call<T> {
+    pub fn target(&self) -> VatSendTarget {
+        self.target
+    }
+
+    pub fn message(&self) -> &OutboundVatMessage {
+        &self.message
+    }
+
+    pub fn send(self, syscall: &mut dyn Syscall) -> Promised<T> {
+        let method = self.message.name.clone();
+        let promise = syscall.send(self.target, self.message);
+        Promised {
+            promise,
+            method,
+            result: PhantomData,
+        }
+    }
+
+    /// send the message without asking for its result
+    pub fn send_only(self, syscall: &mut dyn Syscall) {
+        syscall.send_only(self.target, self.message);
+    }
+}
+
+/// Promised is the result promise of a sent Call
+pub struct Promised<T> {
+    promise: VatPromiseID,
+    method: String,
+    result: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Promised<T> {
+    pub fn promise(&self) -> VatPromiseID {
+        self.promise
+    }
+
+    /// the promise as a Remote, for pipelining
+    pub fn remote<J: ?Sized>(&self) -> Remote<J> {
+        Remote::new(VatSendTarget::Promise(self.promise))
+    }
+
+    /// the result, from what the promise was fulfilled to: a result which
+    /// is an object needs a `T` which accepts a Slot, like a Remote
+    pub fn decode(&self, fulfillment: Fulfillment) -> Result<T, MarshalError> {
+        let value = match fulfillment {
+            Fulfillment::Data(data) => unserialize(&data)?,
+            Fulfillment::Target(VatResolveTarget::Import(i)) => {
+                Value::slot(VatArgSlot::Import(i))
+            }
+            Fulfillment::Target(VatResolveTarget::Export(e)) => {
+                Value::slot(VatArgSlot::Export(e))
+            }
+        };
+        from_value(value)
+    }
+}
+
+impl VatHandle {
+    /// send `call`, returning a future for its decoded result
+    pub fn call<T: DeserializeOwned>(&self, call: Call<T>) -> RemoteFuture<T> {
+        let promised = self.with_syscall(|syscall| call.send(syscall));
+        RemoteFuture {
+            future: self.when(promised.promise()),
+            promised,
+        }
+    }
+}
+
+/// A Future for the result of a Call sent from an AsyncVat. A result that
+/// does not decode as a `T` is treated as a rejection.
+pub struct RemoteFuture<T> {
+    future: PromiseFuture,
+    promised: Promised<T>,
+}
+
+impl<T: DeserializeOwned> RemoteFuture<T> {
+    /// the result promise as a Remote, for pipelining
+    pub fn remote<J: ?Sized>(&self) -> Remote<J> {
+        self.promised.remote()
+    }
+}
+
+impl<T: DeserializeOwned> Future for RemoteFuture<T> {
+    type Output = Result<T, VatCapData>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let settlement = match Pin::new(&mut this.future).poll(cx) {
+            Poll::Ready(settlement) => settlement,
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(settlement.and_then(|fulfillment| {
+            this.promised.decode(fulfillment).map_err(|e| {
+                let message = format!("bad result from {}: {}", this.promised.method, e);
+                serialize(&Value::error("TypeError", &message))
+            })
+        }))
+    }
+}
@@ -0,0 +1,343 @@
+use super::host::{HostArgSlot, HostCapData, KernelExportHandle, KernelPromiseHandle};
+use super::json::Json;
+use super::kernel_types::{DeviceName, VatName};
+use std::fmt;
+use std::time::Duration;
+
+/// which Dispatch method a run-queue entry will invoke
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum DeliveryKind {
+    Deliver,
+    DeliverPromise,
+    NotifyFulfillToData,
+    NotifyFulfillToTarget,
+    NotifyReject,
+}
+
+/// every run-queue entry is given a MessageID when it is queued, unique for
+/// the life of the kernel
+#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct MessageID(pub u64);
+
+/// why a run-queue entry was queued
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum Cause {
+    /// the host queued it, through the Controller
+    Host,
+    /// A syscall made while delivering `message` (in `crank`) queued it:
+    /// a send, or the resolution (or subscription to an already-resolved
+    /// promise) which triggered a notification. `syscall` is an index into
+    /// the `syscalls` of that crank's CrankReport.
+    Syscall {
+        message: MessageID,
+        crank: u64,
+        syscall: usize,
+    },
+    /// The kernel refused to deliver `message` (in `crank`) because it did
+    /// not match its target's Interface, and queued the notifications of
+    /// the rejections which followed.
+    Refused { message: MessageID, crank: u64 },
+}
+
+/// DeliveryReport describes one run-queue entry, in kernel-level terms. The
+/// target is an export for Deliver, and a promise for everything else.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DeliveryReport {
+    pub id: MessageID,
+    pub cause: Cause,
+    pub vat: VatName,
+    pub kind: DeliveryKind,
+    pub target: HostArgSlot,
+    pub method: Option<String>,
+}
+
+/// SyscallReport describes one syscall made during a crank, with the
+/// arguments and results translated from vat-level to kernel-level
+/// references
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum SyscallReport {
+    Send {
+        target: HostArgSlot,
+        method: String,
+        args: HostCapData,
+        result: Option<KernelPromiseHandle>,
+    },
+    Invoke {
+        device: DeviceName,
+        method: String,
+        args: HostCapData,
+        result: HostCapData,
+    },
+    AllocatePromiseAndResolver {
+        promise: KernelPromiseHandle,
+    },
+    Subscribe {
+        promise: KernelPromiseHandle,
+    },
+    FulfillToTarget {
+        promise: KernelPromiseHandle,
+        target: KernelExportHandle,
+    },
+    FulfillToData {
+        promise: KernelPromiseHandle,
+        data: HostCapData,
+    },
+    Reject {
+        promise: KernelPromiseHandle,
+        data: HostCapData,
+    },
+    Forward {
+        promise: KernelPromiseHandle,
+        target: KernelPromiseHandle,
+    },
+}
+
+/// CrankReport is returned by Controller::step, and describes everything
+/// that happened while processing a single run-queue entry
+#[derive(Debug, Clone)]
+pub struct CrankReport {
+    /// cranks are numbered from 0 for the life of the kernel
+    pub crank: u64,
+    pub delivery: DeliveryReport,
+    pub syscalls: Vec<SyscallReport>,
+    /// run-queue entries added by this crank, in order
+    pub queued: Vec<DeliveryReport>,
+    pub promises_created: Vec<KernelPromiseHandle>,
+    /// promises that were fulfilled, rejected, or forwarded
+    pub promises_resolved: Vec<KernelPromiseHandle>,
+    pub elapsed: Duration,
+}
+
+impl CrankReport {
+    /// A deterministic measure of the work done by this crank, for
+    /// Controller::run_block. Each delivery costs one computron, and each
+    /// syscall it makes costs one more.
+    pub fn computrons(&self) -> u64 {
+        1 + self.syscalls.len() as u64
+    }
+}
+
+/// RunSummary is returned by Controller::run, and aggregates the
+/// CrankReports of every crank it performed
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct RunSummary {
+    pub cranks: u64,
+    pub syscalls: u64,
+    pub queued: u64,
+    pub promises_created: u64,
+    pub promises_resolved: u64,
+    pub computrons: u64,
+    pub elapsed: Duration,
+}
+impl RunSummary {
+    pub fn add(&mut self, report: &CrankReport) {
+        self.cranks += 1;
+        self.syscalls += report.syscalls.len() as u64;
+        self.queued += report.queued.len() as u64;
+        self.promises_created += report.promises_created.len() as u64;
+        self.promises_resolved += report.promises_resolved.len() as u64;
+        self.computrons += report.computrons();
+        self.elapsed += report.elapsed;
+    }
+}
+
+/// the parts of a CrankReport accumulated by the kernel and its syscalls
+/// while the crank is in progress
+#[derive(Debug, Default)]
+pub(crate) struct CrankRecord {
+    pub(crate) syscalls: Vec<SyscallReport>,
+    pub(crate) queued: Vec<DeliveryReport>,
+    pub(crate) promises_created: Vec<KernelPromiseHandle>,
+    pub(crate) promises_resolved: Vec<KernelPromiseHandle>,
+}
+
+impl fmt::Display for MessageID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m{}", self.0)
+    }
+}
+
+impl fmt::Display for DeliveryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:?} {}",
+            self.id, self.vat, self.kind, self.target
+        )?;
+        if let Some(method) = &self.method {
+            write!(f, ".{}", method)?;
+        }
+        Ok(())
+    }
+}
+
+impl DeliveryReport {
+    /// like Display, but with kernel-level reference strings
+    pub(crate) fn krefs(&self) -> String {
+        let mut s = format!(
+            "{} {} {} {}",
+            self.id,
+            self.vat.0,
+            self.kind.method_name(),
+            self.target.kref()
+        );
+        if let Some(method) = &self.method {
+            s.push('.');
+            s.push_str(method);
+        }
+        s
+    }
+}
+
+impl fmt::Display for SyscallReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SyscallReport::*;
+        match self {
+            Send {
+                target,
+                method,
+                args,
+                result,
+            } => {
+                write!(f, "send {}.{} {}", target, method, args)?;
+                if let Some(result) = result {
+                    write!(f, " -> {}", result)?;
+                }
+                Ok(())
+            }
+            Invoke {
+                device,
+                method,
+                args,
+                result,
+            } => write!(f, "invoke {}.{} {} -> {}", device, method, args, result),
+            AllocatePromiseAndResolver { promise } => {
+                write!(f, "allocate_promise_and_resolver -> {}", promise)
+            }
+            Subscribe { promise } => write!(f, "subscribe {}", promise),
+            FulfillToTarget { promise, target } => {
+                write!(f, "fulfill_to_target {} {}", promise, target)
+            }
+            FulfillToData { promise, data } => {
+                write!(f, "fulfill_to_data {} {}", promise, data)
+            }
+            Reject { promise, data } => write!(f, "reject {} {}", promise, data),
+            Forward { promise, target } => write!(f, "forward {} {}", promise, target),
+        }
+    }
+}
+
+impl SyscallReport {
+    /// the name of the Syscall method that was called
+    pub fn name(&self) -> &'static str {
+        use SyscallReport::*;
+        match self {
+            Send { result: None, .. } => "send_only",
+            Send { .. } => "send",
+            Invoke { .. } => "invoke",
+            AllocatePromiseAndResolver { .. } => "allocate_promise_and_resolver",
+            Subscribe { .. } => "subscribe",
+            FulfillToTarget { .. } => "fulfill_to_target",
+            FulfillToData { .. } => "fulfill_to_data",
+            Reject { .. } => "reject",
+            Forward { .. } => "forward",
+        }
+    }
+}
+
+impl DeliveryKind {
+    /// the name of the Dispatch method this kind of delivery invokes
+    pub fn method_name(&self) -> &'static str {
+        use DeliveryKind::*;
+        match self {
+            Deliver => "deliver",
+            DeliverPromise => "deliver_promise",
+            NotifyFulfillToData => "notify_fulfill_to_data",
+            NotifyFulfillToTarget => "notify_fulfill_to_target",
+            NotifyReject => "notify_reject",
+        }
+    }
+}
+
+impl From<&Cause> for Json {
+    fn from(cause: &Cause) -> Json {
+        match cause {
+            Cause::Host => Json::Null,
+            Cause::Syscall {
+                message,
+                crank,
+                syscall,
+            } => Json::object()
+                .with("msgID", message.to_string())
+                .with("crankNum", *crank)
+                .with("syscallNum", *syscall),
+            Cause::Refused { message, crank } => Json::object()
+                .with("msgID", message.to_string())
+                .with("crankNum", *crank),
+        }
+    }
+}
+
+impl From<&DeliveryReport> for Json {
+    fn from(d: &DeliveryReport) -> Json {
+        Json::object()
+            .with("msgID", d.id.to_string())
+            .with("cause", &d.cause)
+            .with("vatName", d.vat.0.as_str())
+            .with("kind", d.kind.method_name())
+            .with("target", d.target.kref())
+            .with("method", d.method.clone())
+    }
+}
+
+impl From<&SyscallReport> for Json {
+    fn from(s: &SyscallReport) -> Json {
+        use SyscallReport::*;
+        match s {
+            Send {
+                target,
+                method,
+                args,
+                result,
+            } => Json::object()
+                .with("syscall", "send")
+                .with("target", target.kref())
+                .with("method", method.as_str())
+                .with("args", args)
+                .with("result", result.map(|p| p.kref())),
+            Invoke {
+                device,
+                method,
+                args,
+                result,
+            } => Json::object()
+                .with("syscall", "invoke")
+                .with("device", device.0.as_str())
+                .with("method", method.as_str())
+                .with("args", args)
+                .with("result", result),
+            AllocatePromiseAndResolver { promise } => Json::object()
+                .with("syscall", "allocate_promise_and_resolver")
+                .with("result", promise.kref()),
+            Subscribe { promise } => Json::object()
+                .with("syscall", "subscribe")
+                .with("promise", promise.kref()),
+            FulfillToTarget { promise, target } => Json::object()
+                .with("syscall", "fulfill_to_target")
+                .with("promise", promise.kref())
+                .with("target", target.kref()),
+            FulfillToData { promise, data } => Json::object()
+                .with("syscall", "fulfill_to_data")
+                .with("promise", promise.kref())
+                .with("data", data),
+            Reject { promise, data } => Json::object()
+                .with("syscall", "reject")
+                .with("promise", promise.kref())
+                .with("data", data),
+            Forward { promise, target } => Json::object()
+                .with("syscall", "forward")
+                .with("promise", promise.kref())
+                .with("target", target.kref()),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use super::kernel::{
+    KernelData, PendingDelivery, RunQueue, RunQueueEntry, SendReference,
+};
+use super::kernel_types::KernelPromiseResolverID;
+use super::promise::KernelPromise;
+use super::report::MessageID;
+use std::collections::{HashMap, HashSet};
+
+// The kernel promises less than its FIFO run queue delivers. Messages sent
+// through the same reference (by one vat, or by the host) arrive in the
+// order they were sent, and a message pipelined on a result promise arrives
+// after the message whose result it is (whose delivery hands the resolver
+// to the decider). Nothing else is ordered: notifications, and messages
+// sent through different references, may arrive in any order.
+
+/// xorshift64*: small, fast, and the same everywhere, so a seed always
+/// replays the same schedule
+#[derive(Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // the state must never be zero
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn result(entry: &RunQueueEntry) -> Option<KernelPromiseResolverID> {
+    match &entry.pd {
+        PendingDelivery::Deliver { message, .. }
+        | PendingDelivery::DeliverPromise { message, .. } => message.resolver,
+        _ => None,
+    }
+}
+
+fn pipelined_on(entry: &RunQueueEntry) -> Option<KernelPromiseResolverID> {
+    match &entry.pd {
+        PendingDelivery::DeliverPromise { target, .. } => Some(*target),
+        _ => None,
+    }
+}
+
+/// the indices of the run-queue entries which could be delivered next
+/// without breaking any ordering the kernel promises. The head of the queue
+/// is always among them.
+pub(crate) fn eligible(queue: &RunQueue) -> Vec<usize> {
+    let mut references = HashSet::new();
+    let mut results = HashSet::new();
+    let mut eligible = vec![];
+    for (i, entry) in queue.0.iter().enumerate() {
+        let waiting_on_reference = match entry.reference {
+            Some(reference) => !references.insert(reference),
+            None => false,
+        };
+        let waiting_on_result = match pipelined_on(entry) {
+            Some(target) => results.contains(&target),
+            None => false,
+        };
+        if !waiting_on_reference && !waiting_on_result {
+            eligible.push(i);
+        }
+        if let Some(kprid) = result(entry) {
+            results.insert(kprid);
+        }
+    }
+    eligible
+}
+
+/// the Scheduler picks which run-queue entry each crank delivers
+#[derive(Debug)]
+pub(crate) enum Scheduler {
+    /// strictly first-in, first-out
+    Fifo,
+    /// any eligible entry, chosen at random
+    Chaos(Rng),
+}
+
+impl Scheduler {
+    pub(crate) fn next(&mut self, queue: &RunQueue) -> Option<usize> {
+        if queue.0.is_empty() {
+            return None;
+        }
+        match self {
+            Scheduler::Fifo => Some(0),
+            Scheduler::Chaos(rng) => {
+                let eligible = eligible(queue);
+                Some(eligible[rng.below(eligible.len())])
+            }
+        }
+    }
+}
+
+/// EOrderChecker watches deliveries (whatever chose them) for any that break
+/// the ordering the kernel promises
+#[derive(Debug, Default)]
+pub(crate) struct EOrderChecker {
+    // the most recent delivery sent through each reference
+    delivered: HashMap<SendReference, MessageID>,
+}
+
+impl EOrderChecker {
+    /// check `entry`, which has just been taken off the run queue for
+    /// delivery. A message only counts as arrived once it reaches its final
+    /// target: one handed to a vat that no longer decides the promise it
+    /// was sent to should have been sent on instead.
+    pub(crate) fn check(
+        &mut self,
+        entry: &RunQueueEntry,
+        kd: &KernelData,
+    ) -> Result<(), String> {
+        if let PendingDelivery::DeliverPromise { vat_id, target, .. } = entry.pd {
+            let deciding = matches!(
+                kd.promises.get(&target),
+                Some(KernelPromise::Unresolved { decider, .. }) if *decider == vat_id
+            );
+            if !deciding {
+                return Err(format!(
+                    "{} was delivered to {} through {}, which it no longer decides",
+                    entry.id, vat_id, target
+                ));
+            }
+        }
+        let queue = &kd.run_queue;
+        if let Some(reference) = entry.reference {
+            if let Some(previous) = self.delivered.insert(reference, entry.id) {
+                if previous.0 > entry.id.0 {
+                    return Err(format!(
+                        "{} was delivered after {}, but was sent before it through {}",
+                        entry.id, previous, reference
+                    ));
+                }
+            }
+        }
+        if let Some(target) = pipelined_on(entry) {
+            if let Some(earlier) = queue.0.iter().find(|e| result(e) == Some(target)) {
+                return Err(format!(
+                    "{} was delivered to {} before {}, which carries its resolver",
+                    entry.id, target, earlier.id
+                ));
+            }
+        }
+        Ok(())
+    }
+}
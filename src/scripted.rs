@@ -0,0 +1,316 @@
+use super::config::{Config, Setup};
+use super::dispatch::Dispatch;
+use super::kernel_types::VatName;
+use super::syscall::Syscall;
+use super::vat_types::{
+    InboundVatMessage, VatCapData, VatExportID, VatPromiseID, VatResolveTarget,
+    VatResolverID,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+/// a delivery made to a ScriptedVat, as one of the Dispatch methods
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery {
+    Deliver {
+        target: VatExportID,
+        message: InboundVatMessage,
+    },
+    DeliverPromise {
+        target: VatResolverID,
+        message: InboundVatMessage,
+    },
+    NotifyFulfillToTarget {
+        promise: VatPromiseID,
+        target: VatResolveTarget,
+    },
+    NotifyFulfillToData {
+        promise: VatPromiseID,
+        data: VatCapData,
+    },
+    NotifyReject {
+        promise: VatPromiseID,
+        data: VatCapData,
+    },
+}
+
+impl Delivery {
+    /// the message delivered, if this is not a notification
+    pub fn message(&self) -> Option<&InboundVatMessage> {
+        match self {
+            Delivery::Deliver { message, .. }
+            | Delivery::DeliverPromise { message, .. } => Some(message),
+            _ => None,
+        }
+    }
+
+    /// the resolver for the result of the message delivered: panics if
+    /// there is none
+    pub fn resolver(&self) -> VatResolverID {
+        match self.message() {
+            Some(InboundVatMessage {
+                resolver: Some(r), ..
+            }) => *r,
+            _ => panic!("{} has no result", self),
+        }
+    }
+
+    /// the promise this notification is about
+    pub fn promise(&self) -> Option<VatPromiseID> {
+        match self {
+            Delivery::NotifyFulfillToTarget { promise, .. }
+            | Delivery::NotifyFulfillToData { promise, .. }
+            | Delivery::NotifyReject { promise, .. } => Some(*promise),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Delivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Delivery::*;
+        match self {
+            Deliver { message, .. } => write!(f, "deliver {}", message.name),
+            DeliverPromise { message, .. } => {
+                write!(f, "deliver_promise {}", message.name)
+            }
+            NotifyFulfillToTarget { promise, .. } => {
+                write!(f, "notify_fulfill_to_target p{}", promise.0)
+            }
+            NotifyFulfillToData { promise, .. } => {
+                write!(f, "notify_fulfill_to_data p{}", promise.0)
+            }
+            NotifyReject { promise, .. } => write!(f, "notify_reject p{}", promise.0),
+        }
+    }
+}
+
+/// EventLog collects what happens to a group of ScriptedVats, in order:
+/// each delivery as "VAT: DELIVERY", and anything the scripts add. Clones
+/// share the log.
+#[derive(Debug, Default, Clone)]
+pub struct EventLog(Rc<RefCell<Vec<String>>>);
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog::default()
+    }
+
+    pub fn push(&self, entry: String) {
+        self.0.borrow_mut().push(entry);
+    }
+
+    /// the events logged since the last `take`
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+/// Script is what a ScriptedVat's handlers work with: it dereferences to the
+/// vat's Syscall, and remembers promises and resolvers by name from one
+/// handler to the next.
+pub struct Script<'a> {
+    vat: &'a str,
+    syscall: &'a mut dyn Syscall,
+    log: &'a EventLog,
+    promises: &'a mut HashMap<String, VatPromiseID>,
+    resolvers: &'a mut HashMap<String, VatResolverID>,
+}
+
+impl<'a> Script<'a> {
+    /// add an event to the log, as "VAT: ENTRY"
+    pub fn log(&mut self, entry: &str) {
+        self.log.push(format!("{}: {}", self.vat, entry));
+    }
+
+    pub fn keep_promise(&mut self, name: &str, promise: VatPromiseID) {
+        self.promises.insert(name.to_string(), promise);
+    }
+
+    pub fn promise(&self, name: &str) -> VatPromiseID {
+        match self.promises.get(name) {
+            Some(p) => *p,
+            None => panic!("{} kept no promise named {}", self.vat, name),
+        }
+    }
+
+    pub fn keep_resolver(&mut self, name: &str, resolver: VatResolverID) {
+        self.resolvers.insert(name.to_string(), resolver);
+    }
+
+    pub fn resolver(&self, name: &str) -> VatResolverID {
+        match self.resolvers.get(name) {
+            Some(r) => *r,
+            None => panic!("{} kept no resolver named {}", self.vat, name),
+        }
+    }
+}
+
+impl<'a> Deref for Script<'a> {
+    type Target = dyn Syscall + 'a;
+    fn deref(&self) -> &Self::Target {
+        self.syscall
+    }
+}
+
+impl<'a> DerefMut for Script<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.syscall
+    }
+}
+
+type Handler = Box<dyn FnOnce(&mut Script, &Delivery)>;
+
+enum Expect {
+    Method(String),
+    Target(VatExportID),
+    Promise(VatPromiseID),
+    Notify,
+}
+
+impl Expect {
+    fn matches(&self, delivery: &Delivery) -> bool {
+        match self {
+            Expect::Method(name) => delivery.message().map(|m| &m.name) == Some(name),
+            Expect::Target(export) => match delivery {
+                Delivery::Deliver { target, .. } => target == export,
+                _ => false,
+            },
+            Expect::Promise(promise) => delivery.promise() == Some(*promise),
+            Expect::Notify => delivery.promise().is_some(),
+        }
+    }
+}
+
+/// ScriptedVat builds a vat for integration tests out of the deliveries it
+/// expects, each paired with a handler that makes its syscalls. Every
+/// delivery goes to the first remaining handler that expects it, and uses
+/// it up. A delivery which no handler expects fails the test.
+pub struct ScriptedVat {
+    name: String,
+    log: EventLog,
+    handlers: Vec<(Expect, Handler)>,
+}
+
+impl ScriptedVat {
+    pub fn new(name: &str, log: &EventLog) -> Self {
+        ScriptedVat {
+            name: name.to_string(),
+            log: log.clone(),
+            handlers: vec![],
+        }
+    }
+
+    fn expect<F>(mut self, expect: Expect, handler: F) -> Self
+    where
+        F: FnOnce(&mut Script, &Delivery) + 'static,
+    {
+        self.handlers.push((expect, Box::new(handler)));
+        self
+    }
+
+    /// expect a message (to an export or a promise) by method name
+    pub fn on_method<F>(self, method: &str, handler: F) -> Self
+    where
+        F: FnOnce(&mut Script, &Delivery) + 'static,
+    {
+        self.expect(Expect::Method(method.to_string()), handler)
+    }
+
+    /// expect a message to one of this vat's exports
+    pub fn on_target<F>(self, target: VatExportID, handler: F) -> Self
+    where
+        F: FnOnce(&mut Script, &Delivery) + 'static,
+    {
+        self.expect(Expect::Target(target), handler)
+    }
+
+    /// expect a notification about one promise
+    pub fn on_promise<F>(self, promise: VatPromiseID, handler: F) -> Self
+    where
+        F: FnOnce(&mut Script, &Delivery) + 'static,
+    {
+        self.expect(Expect::Promise(promise), handler)
+    }
+
+    /// expect a notification about any promise
+    pub fn on_notify<F>(self, handler: F) -> Self
+    where
+        F: FnOnce(&mut Script, &Delivery) + 'static,
+    {
+        self.expect(Expect::Notify, handler)
+    }
+
+    /// the Setup for Config::add_vat
+    pub fn setup(self) -> Box<Setup> {
+        Box::new(move |syscall| -> Box<dyn Dispatch> {
+            Box::new(ScriptedDispatch {
+                vat: self,
+                syscall,
+                promises: HashMap::new(),
+                resolvers: HashMap::new(),
+            })
+        })
+    }
+
+    /// add this vat to `cfg`, under its name
+    pub fn install(self, cfg: &mut Config) {
+        let name = VatName(self.name.clone());
+        cfg.add_vat(&name, self.setup());
+    }
+}
+
+struct ScriptedDispatch {
+    vat: ScriptedVat,
+    syscall: Box<dyn Syscall>,
+    promises: HashMap<String, VatPromiseID>,
+    resolvers: HashMap<String, VatResolverID>,
+}
+
+impl ScriptedDispatch {
+    fn dispatch(&mut self, delivery: Delivery) {
+        let vat = &mut self.vat;
+        vat.log.push(format!("{}: {}", vat.name, delivery));
+        let handler = match vat.handlers.iter().position(|(e, _)| e.matches(&delivery)) {
+            Some(i) => vat.handlers.remove(i).1,
+            None => panic!("{} did not expect {:?}", vat.name, delivery),
+        };
+        let mut script = Script {
+            vat: &vat.name,
+            syscall: &mut *self.syscall,
+            log: &vat.log,
+            promises: &mut self.promises,
+            resolvers: &mut self.resolvers,
+        };
+        handler(&mut script, &delivery);
+    }
+}
+
+impl Dispatch for ScriptedDispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        self.dispatch(Delivery::Deliver { target, message });
+    }
+
+    fn deliver_promise(&mut self, target: VatResolverID, message: InboundVatMessage) {
+        self.dispatch(Delivery::DeliverPromise { target, message });
+    }
+
+    fn notify_fulfill_to_target(
+        &mut self,
+        promise: VatPromiseID,
+        target: VatResolveTarget,
+    ) {
+        self.dispatch(Delivery::NotifyFulfillToTarget { promise, target });
+    }
+
+    fn notify_fulfill_to_data(&mut self, promise: VatPromiseID, data: VatCapData) {
+        self.dispatch(Delivery::NotifyFulfillToData { promise, data });
+    }
+
+    fn notify_reject(&mut self, promise: VatPromiseID, data: VatCapData) {
+        self.dispatch(Delivery::NotifyReject { promise, data });
+    }
+}
@@ -0,0 +1,143 @@
+use super::json::Json;
+use super::observer::{KernelEvent, KernelObserver};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A Clock supplies the timestamps written into slog entries, in seconds.
+/// Tests can inject a deterministic one: any `FnMut() -> f64` will do.
+pub trait Clock {
+    fn now(&mut self) -> f64;
+}
+
+/// SystemClock reports seconds since the unix epoch
+#[derive(Debug, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&mut self) -> f64 {
+        let d = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        d.as_secs_f64()
+    }
+}
+
+impl<F: FnMut() -> f64> Clock for F {
+    fn now(&mut self) -> f64 {
+        self()
+    }
+}
+
+/// SlogWriter is a KernelObserver that writes a "slog": one JSON object per
+/// line for every kernel event, in the style of the JS SwingSet slogfile.
+/// Every entry has a "type" and a "time", and entries written during a
+/// crank also carry its "crankNum".
+pub struct SlogWriter<W: Write> {
+    out: W,
+    clock: Box<dyn Clock>,
+    crank: Option<u64>,
+    error: Option<io::Error>,
+}
+
+impl SlogWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(SlogWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> SlogWriter<W> {
+    pub fn new(out: W) -> Self {
+        SlogWriter::with_clock(out, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(out: W, clock: Box<dyn Clock>) -> Self {
+        SlogWriter {
+            out,
+            clock,
+            crank: None,
+            error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /// Observers cannot report failures to the kernel, so the first write
+    /// error is kept here, and nothing more is written after it.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    fn entry(&mut self, slog_type: &str) -> Json {
+        let entry = Json::object()
+            .with("type", slog_type)
+            .with("time", self.clock.now());
+        match self.crank {
+            Some(crank) => entry.with("crankNum", crank),
+            None => entry,
+        }
+    }
+
+    fn write(&mut self, entry: Json) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.out, "{}", entry) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+impl<W: Write> KernelObserver for SlogWriter<W> {
+    fn observe(&mut self, event: &KernelEvent) {
+        use KernelEvent::*;
+        let entry = match event {
+            VatCreated { vat } => {
+                self.entry("create-vat").with("vatName", vat.0.as_str())
+            }
+            VatTerminated { vat } => {
+                self.entry("terminate-vat").with("vatName", vat.0.as_str())
+            }
+            CrankStart { crank } => {
+                self.crank = Some(*crank);
+                self.entry("crank-start")
+            }
+            Delivery {
+                delivery,
+                args,
+                result,
+            } => self
+                .entry("deliver")
+                .merge(delivery.into())
+                .with("args", args)
+                .with("result", result.map(|p| p.kref())),
+            Syscall { vat, syscall } => self
+                .entry("syscall")
+                .with("vatName", vat.0.as_str())
+                .merge(syscall.into()),
+            Queued { delivery } => self.entry("queue").merge(delivery.into()),
+            PromiseState { promise, status } => self
+                .entry("promise")
+                .with("kpid", promise.kref())
+                .merge(status.into()),
+            PromiseForwarded { promise, target } => self
+                .entry("forward")
+                .with("kpid", promise.kref())
+                .with("target", target.kref()),
+            CrankFinish { report } => {
+                let entry = self
+                    .entry("crank-finish")
+                    .with("syscalls", report.syscalls.len())
+                    .with("queued", report.queued.len());
+                self.crank = None;
+                entry
+            }
+        };
+        self.write(entry);
+    }
+}
+
+impl<W: Write> Drop for SlogWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
@@ -0,0 +1,339 @@
+use super::host::{
+    HostArgSlot, HostCapData, KernelExportHandle, KernelPromiseHandle, PromiseStatus,
+};
+use super::json::Json;
+use super::kernel::KernelData;
+use super::kernel_types::{DeviceName, KernelExport, VatName};
+use super::promise::KernelPromise;
+use super::report::DeliveryReport;
+use super::vat_types::{
+    VatDeviceID, VatExportID, VatImportID, VatPromiseID, VatResolverID,
+};
+use std::collections::HashSet;
+use std::fmt;
+
+/// KernelSnapshotView is a copy of the kernel's state, obtained from
+/// Controller::dump_state. Everything is sorted (vats by name, clist
+/// entries by vat-side index, promises by kernel index), so two kernels in
+/// the same state produce identical snapshots.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct KernelSnapshotView {
+    /// the number of the next crank to be run
+    pub crank: u64,
+    pub vats: Vec<VatSnapshot>,
+    pub promises: Vec<PromiseSnapshot>,
+    /// promises retired by syscall.forward, and where they went
+    pub forwarded: Vec<(KernelPromiseHandle, KernelPromiseHandle)>,
+    pub run_queue: Vec<QueuedDelivery>,
+}
+
+/// the clists of a single vat
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct VatSnapshot {
+    pub name: VatName,
+    /// this vat's exports which are referenced from elsewhere in the kernel:
+    /// other vats' clists, promise resolutions, or the run queue
+    pub exports: Vec<(VatExportID, KernelExportHandle)>,
+    pub imports: Vec<(VatImportID, KernelExportHandle)>,
+    pub promises: Vec<(VatPromiseID, KernelPromiseHandle)>,
+    pub resolvers: Vec<(VatResolverID, KernelPromiseHandle)>,
+    pub devices: Vec<(VatDeviceID, DeviceName)>,
+}
+
+/// a kernel promise. Only unresolved promises have a decider and
+/// subscribers.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PromiseSnapshot {
+    pub promise: KernelPromiseHandle,
+    pub status: PromiseStatus,
+    pub decider: Option<VatName>,
+    pub subscribers: Vec<VatName>,
+}
+
+/// a run-queue entry which has not been delivered yet
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct QueuedDelivery {
+    pub delivery: DeliveryReport,
+    pub args: HostCapData,
+    pub result: Option<KernelPromiseHandle>,
+}
+
+impl KernelSnapshotView {
+    /// serialize the snapshot as a single JSON object, using the same
+    /// kernel-reference strings ("ko1.2", "kp3") as the slog
+    pub fn to_json(&self) -> String {
+        Json::from(self).to_string()
+    }
+
+    pub fn vat(&self, name: &str) -> Option<&VatSnapshot> {
+        self.vats.iter().find(|v| v.name.0 == name)
+    }
+
+    pub fn promise(&self, promise: KernelPromiseHandle) -> Option<&PromiseSnapshot> {
+        self.promises.iter().find(|p| p.promise == promise)
+    }
+}
+
+/// clist entries, sorted by their vat-side index
+fn sorted<K, V>(
+    entries: impl Iterator<Item = (K, V)>,
+    index: fn(&K) -> u32,
+) -> Vec<(K, V)> {
+    let mut entries: Vec<(K, V)> = entries.collect();
+    entries.sort_by_key(|(k, _)| index(k));
+    entries
+}
+
+/// every export mentioned in some data: a resolution, or queued arguments
+fn data_exports<'a>(data: &'a HostCapData) -> impl Iterator<Item = KernelExport> + 'a {
+    data.slots.iter().filter_map(|slot| match slot {
+        HostArgSlot::Export(ke) => Some(ke.0),
+        HostArgSlot::Promise(_) => None,
+    })
+}
+
+impl From<&KernelData> for KernelSnapshotView {
+    fn from(kd: &KernelData) -> KernelSnapshotView {
+        let vat_name = |vat_id| kd.vat_data.get(vat_id).unwrap().name.clone();
+
+        let run_queue: Vec<QueuedDelivery> = kd
+            .run_queue
+            .0
+            .iter()
+            .map(|entry| {
+                let (args, result) = kd.delivery_args(&entry.pd);
+                QueuedDelivery {
+                    delivery: kd.describe(entry),
+                    args,
+                    result,
+                }
+            })
+            .collect();
+
+        let mut promises: Vec<PromiseSnapshot> = kd
+            .promises
+            .iter()
+            .map(|(kprid, p)| {
+                let (decider, mut subscribers) = match p {
+                    KernelPromise::Unresolved {
+                        subscribers,
+                        decider,
+                    } => (
+                        Some(vat_name(decider)),
+                        subscribers.iter().map(vat_name).collect(),
+                    ),
+                    _ => (None, vec![]),
+                };
+                subscribers.sort();
+                PromiseSnapshot {
+                    promise: KernelPromiseHandle(*kprid),
+                    status: p.into(),
+                    decider,
+                    subscribers,
+                }
+            })
+            .collect();
+        promises.sort_by_key(|p| (p.promise.0).0);
+
+        let mut forwarded: Vec<(KernelPromiseHandle, KernelPromiseHandle)> = kd
+            .forwarded
+            .iter()
+            .map(|(old, new)| (KernelPromiseHandle(*old), KernelPromiseHandle(*new)))
+            .collect();
+        forwarded.sort_by_key(|(old, _)| (old.0).0);
+
+        // vats don't tell the kernel about their exports until they send
+        // them somewhere, so find every export the kernel has seen
+        let mut exports: HashSet<KernelExport> = HashSet::new();
+        for vd in kd.vat_data.values() {
+            exports.extend(vd.import_clist.inbound.keys());
+        }
+        for p in &promises {
+            match &p.status {
+                PromiseStatus::Unresolved => (),
+                PromiseStatus::FulfilledToTarget(ke) => {
+                    exports.insert(ke.0);
+                }
+                PromiseStatus::FulfilledToData(data) | PromiseStatus::Rejected(data) => {
+                    exports.extend(data_exports(data));
+                }
+            }
+        }
+        for q in &run_queue {
+            if let HostArgSlot::Export(ke) = q.delivery.target {
+                exports.insert(ke.0);
+            }
+            exports.extend(data_exports(&q.args));
+        }
+
+        let mut vats: Vec<VatSnapshot> =
+            kd.vat_data
+                .values()
+                .map(|vd| VatSnapshot {
+                    name: vd.name.clone(),
+                    exports: sorted(
+                        exports
+                            .iter()
+                            .filter(|ke| ke.0 == vd.vat_id)
+                            .map(|ke| (VatExportID((ke.1).0), KernelExportHandle(*ke))),
+                        |v| v.0,
+                    ),
+                    imports: sorted(
+                        vd.import_clist
+                            .outbound
+                            .iter()
+                            .map(|(v, k)| (*v, KernelExportHandle(*k))),
+                        |v| v.0,
+                    ),
+                    promises: sorted(
+                        vd.promise_clist
+                            .outbound
+                            .iter()
+                            .chain(vd.promise_aliases.iter())
+                            .map(|(v, k)| (*v, KernelPromiseHandle(*k))),
+                        |v| v.0,
+                    ),
+                    resolvers: sorted(
+                        vd.resolver_clist
+                            .outbound
+                            .iter()
+                            .map(|(v, k)| (*v, KernelPromiseHandle(*k))),
+                        |v| v.0,
+                    ),
+                    devices: sorted(
+                        vd.device_clist.outbound.iter().map(|(v, k)| {
+                            (*v, kd.device_data.get(k).unwrap().name.clone())
+                        }),
+                        |v| v.0,
+                    ),
+                })
+                .collect();
+        vats.sort_by(|a, b| a.name.cmp(&b.name));
+
+        KernelSnapshotView {
+            crank: kd.crank_num,
+            vats,
+            promises,
+            forwarded,
+            run_queue,
+        }
+    }
+}
+
+fn clist_json<K, V>(
+    entries: &[(K, V)],
+    index: fn(&K) -> u32,
+    kref: fn(&V) -> String,
+) -> Json {
+    Json::Object(
+        entries
+            .iter()
+            .map(|(k, v)| (index(k).to_string(), kref(v).into()))
+            .collect(),
+    )
+}
+
+impl From<&VatSnapshot> for Json {
+    fn from(v: &VatSnapshot) -> Json {
+        Json::object()
+            .with("vatName", v.name.0.as_str())
+            .with("exports", clist_json(&v.exports, |k| k.0, |ke| ke.kref()))
+            .with("imports", clist_json(&v.imports, |k| k.0, |ke| ke.kref()))
+            .with("promises", clist_json(&v.promises, |k| k.0, |kp| kp.kref()))
+            .with(
+                "resolvers",
+                clist_json(&v.resolvers, |k| k.0, |kp| kp.kref()),
+            )
+            .with(
+                "devices",
+                clist_json(&v.devices, |k| k.0, |dn| dn.0.clone()),
+            )
+    }
+}
+
+impl From<&PromiseSnapshot> for Json {
+    fn from(p: &PromiseSnapshot) -> Json {
+        let subscribers: Vec<&str> = p.subscribers.iter().map(|s| s.0.as_str()).collect();
+        Json::object()
+            .with("kpid", p.promise.kref())
+            .merge((&p.status).into())
+            .with("decider", p.decider.as_ref().map(|d| d.0.as_str()))
+            .with("subscribers", subscribers)
+    }
+}
+
+impl From<&QueuedDelivery> for Json {
+    fn from(q: &QueuedDelivery) -> Json {
+        Json::from(&q.delivery)
+            .with("args", &q.args)
+            .with("result", q.result.map(|p| p.kref()))
+    }
+}
+
+impl From<&KernelSnapshotView> for Json {
+    fn from(s: &KernelSnapshotView) -> Json {
+        let vats: Vec<Json> = s.vats.iter().map(Json::from).collect();
+        let promises: Vec<Json> = s.promises.iter().map(Json::from).collect();
+        let forwarded: Vec<(String, Json)> = s
+            .forwarded
+            .iter()
+            .map(|(old, new)| (old.kref(), new.kref().into()))
+            .collect();
+        let run_queue: Vec<Json> = s.run_queue.iter().map(Json::from).collect();
+        Json::object()
+            .with("crankNum", s.crank)
+            .with("vats", vats)
+            .with("promises", promises)
+            .with("forwarded", Json::Object(forwarded))
+            .with("runQueue", run_queue)
+    }
+}
+
+impl fmt::Display for KernelSnapshotView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Kernel Dump (next crank {}):", self.crank)?;
+        for v in &self.vats {
+            writeln!(f, " {}:", v.name)?;
+            for (veid, ke) in &v.exports {
+                writeln!(f, "  export {} -> {}", veid.0, ke.kref())?;
+            }
+            for (viid, ke) in &v.imports {
+                writeln!(f, "  import {} -> {}", viid.0, ke.kref())?;
+            }
+            for (vpid, kp) in &v.promises {
+                writeln!(f, "  promise {} -> {}", vpid.0, kp.kref())?;
+            }
+            for (vrid, kp) in &v.resolvers {
+                writeln!(f, "  resolver {} -> {}", vrid.0, kp.kref())?;
+            }
+            for (vdid, dn) in &v.devices {
+                writeln!(f, "  device {} -> {}", vdid.0, dn)?;
+            }
+        }
+        writeln!(f, " promises:")?;
+        for p in &self.promises {
+            write!(f, "  {}: {}", p.promise.kref(), p.status.krefs())?;
+            if let Some(decider) = &p.decider {
+                write!(f, ", decider {}", decider)?;
+            }
+            if !p.subscribers.is_empty() {
+                let subscribers: Vec<String> =
+                    p.subscribers.iter().map(|s| s.to_string()).collect();
+                write!(f, ", subscribers [{}]", subscribers.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        for (old, new) in &self.forwarded {
+            writeln!(f, "  {}: forwarded to {}", old.kref(), new.kref())?;
+        }
+        writeln!(f, " run-queue:")?;
+        for q in &self.run_queue {
+            write!(f, "  {} {}", q.delivery.krefs(), q.args.krefs())?;
+            if let Some(result) = q.result {
+                write!(f, " -> {}", result.kref())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,12 @@
 use super::vat_types::{
-    OutboundVatMessage, VatCapData, VatPromiseID, VatResolveTarget, VatResolverID,
-    VatSendTarget,
+    OutboundVatMessage, VatCapData, VatDeviceID, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
 };
 
 pub trait Syscall {
     fn send(&mut self, target: VatSendTarget, vmsg: OutboundVatMessage) -> VatPromiseID;
     fn send_only(&mut self, target: VatSendTarget, vmsg: OutboundVatMessage);
-    //fn invoke(&mut self, target: VatDeviceID, vmsg: VatMessage) -> VatCapData;
+    fn invoke(&mut self, target: VatDeviceID, vmsg: OutboundVatMessage) -> VatCapData;
     fn allocate_promise_and_resolver(&mut self) -> (VatPromiseID, VatResolverID);
     fn subscribe(&mut self, id: VatPromiseID);
     fn fulfill_to_target(&mut self, resolver: VatResolverID, target: VatResolveTarget);
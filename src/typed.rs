@@ -0,0 +1,609 @@
+use super::marshal::{serialize, unserialize, MarshalError, Value};
+use super::vat_types::{
+    InboundVatMessage, OutboundVatMessage, VatArgSlot, VatCapData, VatExportID,
+    VatImportID, VatPromiseID,
+};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Rust types travel as marshal Values, and so as smallcaps bodies which
+// JavaScript vats can read: structs and maps become records, sequences and
+// tuples become lists, unit variants become strings, and other enum
+// variants become single-member records ({"Variant": value}). 64- and
+// 128-bit integers become bigints, since a Number cannot hold them.
+
+/// the newtype-struct name by which the (un)serializers recognize a Slot
+const SLOT: &str = "$swingset::Slot";
+
+/// Slot is a capability (an import, export, or promise) inside a typed
+/// message argument. Serializing collects it into the message's slots, and
+/// leaves a reference by index in the body.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct Slot(pub VatArgSlot);
+
+/// a Slot serializes as a newtype struct around this pair
+fn slot_pair(slot: VatArgSlot) -> (&'static str, u32) {
+    match slot {
+        VatArgSlot::Import(i) => ("import", i.0),
+        VatArgSlot::Export(e) => ("export", e.0),
+        VatArgSlot::Promise(p) => ("promise", p.0),
+    }
+}
+
+fn pair_slot(kind: &str, id: u32) -> Result<VatArgSlot, MarshalError> {
+    match kind {
+        "import" => Ok(VatArgSlot::Import(VatImportID(id))),
+        "export" => Ok(VatArgSlot::Export(VatExportID(id))),
+        "promise" => Ok(VatArgSlot::Promise(VatPromiseID(id))),
+        _ => Err(MarshalError(format!("unknown slot kind {:?}", kind))),
+    }
+}
+
+impl Serialize for Slot {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(SLOT, &slot_pair(self.0))
+    }
+}
+
+struct SlotVisitor;
+
+impl<'de> Visitor<'de> for SlotVisitor {
+    type Value = Slot;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a slot")
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Slot, D::Error> {
+        let (kind, id): (String, u32) = de::Deserialize::deserialize(deserializer)?;
+        match pair_slot(&kind, id) {
+            Ok(slot) => Ok(Slot(slot)),
+            Err(e) => Err(de::Error::custom(e.0)),
+        }
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Slot {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Slot, D::Error> {
+        deserializer.deserialize_newtype_struct(SLOT, SlotVisitor)
+    }
+}
+
+impl ser::Error for MarshalError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        MarshalError(msg.to_string())
+    }
+}
+
+impl de::Error for MarshalError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        MarshalError(msg.to_string())
+    }
+}
+
+/// convert any Serialize type into a marshal Value
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, MarshalError> {
+    value.serialize(ValueSerializer)
+}
+
+/// convert a marshal Value into any Deserialize type
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, MarshalError> {
+    T::deserialize(value)
+}
+
+/// serialize `value` into a smallcaps body and its slots
+pub fn to_capdata<T: Serialize + ?Sized>(value: &T) -> Result<VatCapData, MarshalError> {
+    Ok(serialize(&to_value(value)?))
+}
+
+/// unserialize a smallcaps body (and its slots) into any Deserialize type
+pub fn from_capdata<T: DeserializeOwned>(data: &VatCapData) -> Result<T, MarshalError> {
+    from_value(unserialize(data)?)
+}
+
+impl OutboundVatMessage {
+    /// a message whose arguments are `args`, marshalled
+    pub fn from_serde<T: Serialize + ?Sized>(
+        method: &str,
+        args: &T,
+    ) -> Result<Self, MarshalError> {
+        let data = to_capdata(args)?;
+        Ok(OutboundVatMessage::new(method, &data.body, data.slots))
+    }
+}
+
+impl InboundVatMessage {
+    /// unmarshal this message's arguments
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, MarshalError> {
+        from_capdata(&self.args)
+    }
+}
+
+struct ValueSerializer;
+
+struct SerializeList(Vec<Value>);
+
+struct SerializeVariant {
+    variant: &'static str,
+    items: Vec<Value>,
+}
+
+struct SerializeRecord {
+    members: BTreeMap<String, Value>,
+    key: Option<String>,
+}
+
+struct SerializeStructVariant {
+    variant: &'static str,
+    members: BTreeMap<String, Value>,
+}
+
+fn variant_record(variant: &str, value: Value) -> Value {
+    Value::record(vec![(variant, value)])
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = MarshalError;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant;
+    type SerializeMap = SerializeRecord;
+    type SerializeStruct = SerializeRecord;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, MarshalError> {
+        Ok(Value::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value, MarshalError> {
+        Ok(Value::BigInt(i128::from(v)))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Value, MarshalError> {
+        Ok(Value::BigInt(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value, MarshalError> {
+        Ok(Value::BigInt(i128::from(v)))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value, MarshalError> {
+        match i128::try_from(v) {
+            Ok(v) => Ok(Value::BigInt(v)),
+            Err(_) => Err(MarshalError(format!("{} is too large for a bigint", v))),
+        }
+    }
+    fn serialize_f32(self, v: f32) -> Result<Value, MarshalError> {
+        Ok(Value::Number(f64::from(v)))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value, MarshalError> {
+        Ok(Value::Number(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Value, MarshalError> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value, MarshalError> {
+        Ok(Value::from(v))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, MarshalError> {
+        Ok(Value::List(
+            v.iter().map(|b| Value::Number(f64::from(*b))).collect(),
+        ))
+    }
+    fn serialize_none(self) -> Result<Value, MarshalError> {
+        Ok(Value::Null)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<Value, MarshalError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Value, MarshalError> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, MarshalError> {
+        Ok(Value::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, MarshalError> {
+        Ok(Value::from(variant))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, MarshalError> {
+        if name != SLOT {
+            return value.serialize(self);
+        }
+        let (kind, id): (String, u32) = from_value(value.serialize(self)?)?;
+        Ok(Value::slot(pair_slot(&kind, id)?))
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, MarshalError> {
+        Ok(variant_record(variant, value.serialize(self)?))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, MarshalError> {
+        Ok(SerializeList(Vec::with_capacity(len.unwrap_or(0))))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, MarshalError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeList, MarshalError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant, MarshalError> {
+        Ok(SerializeVariant {
+            variant,
+            items: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeRecord, MarshalError> {
+        Ok(SerializeRecord {
+            members: BTreeMap::new(),
+            key: None,
+        })
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeRecord, MarshalError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeStructVariant, MarshalError> {
+        Ok(SerializeStructVariant {
+            variant,
+            members: BTreeMap::new(),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        Ok(Value::List(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        Ok(variant_record(self.variant, Value::List(self.items)))
+    }
+}
+
+impl ser::SerializeMap for SerializeRecord {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_key<T: Serialize + ?Sized>(
+        &mut self,
+        key: &T,
+    ) -> Result<(), MarshalError> {
+        match to_value(key)? {
+            Value::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            other => Err(MarshalError(format!(
+                "record keys must be strings, not {:?}",
+                other
+            ))),
+        }
+    }
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value before serialize_key");
+        self.members.insert(key, to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        Ok(Value::Record(self.members))
+    }
+}
+
+impl ser::SerializeStruct for SerializeRecord {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        self.members.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        Ok(Value::Record(self.members))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
+    type Error = MarshalError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), MarshalError> {
+        self.members.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, MarshalError> {
+        Ok(variant_record(self.variant, Value::Record(self.members)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, MarshalError> for Value {
+    type Deserializer = Value;
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+fn unexpected(value: &Value, expected: &str) -> MarshalError {
+    MarshalError(format!("expected {}, found {:?}", expected, value))
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = MarshalError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, MarshalError> {
+        match self {
+            Value::Undefined | Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            // integral Numbers are offered as integers, so integer types
+            // accept them
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 => {
+                visitor.visit_i64(n as i64)
+            }
+            Value::Number(n) => visitor.visit_f64(n),
+            Value::BigInt(n) => match i64::try_from(n) {
+                Ok(n) => visitor.visit_i64(n),
+                Err(_) => visitor.visit_i128(n),
+            },
+            Value::String(s) => visitor.visit_string(s),
+            Value::List(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Record(members) => {
+                let mut map = MapDeserializer::new(members.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Tagged(tag, payload) => {
+                let entries = vec![("tag", Value::String(tag)), ("payload", *payload)];
+                visitor.visit_map(MapDeserializer::new(entries.into_iter()))
+            }
+            Value::Error { name, message } => {
+                let entries = vec![("name", name), ("message", message)];
+                visitor.visit_map(MapDeserializer::new(entries.into_iter()))
+            }
+            slot @ Value::Slot { .. } => Err(unexpected(&slot, "anything but a Slot")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, MarshalError> {
+        match self {
+            Value::Undefined | Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, MarshalError> {
+        if name != SLOT {
+            return visitor.visit_newtype_struct(self);
+        }
+        match self {
+            Value::Slot { slot, .. } => {
+                let (kind, id) = slot_pair(slot);
+                let pair = vec![Value::from(kind), Value::Number(f64::from(id))];
+                visitor.visit_newtype_struct(Value::List(pair))
+            }
+            other => Err(unexpected(&other, "a Slot")),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, MarshalError> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(EnumDeserializer {
+                variant,
+                value: None,
+            }),
+            Value::Record(members) if members.len() == 1 => {
+                let (variant, value) = members.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            other => Err(unexpected(&other, "an enum variant")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = MarshalError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, VariantDeserializer), MarshalError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+struct VariantDeserializer(Option<Value>);
+
+impl VariantDeserializer {
+    fn value(self) -> Result<Value, MarshalError> {
+        match self.0 {
+            Some(value) => Ok(value),
+            None => Err(MarshalError("expected a variant with a value".to_string())),
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = MarshalError;
+
+    fn unit_variant(self) -> Result<(), MarshalError> {
+        match self.0 {
+            None | Some(Value::Null) => Ok(()),
+            Some(other) => Err(unexpected(&other, "a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<S::Value, MarshalError> {
+        seed.deserialize(self.value()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, MarshalError> {
+        de::Deserializer::deserialize_seq(self.value()?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, MarshalError> {
+        de::Deserializer::deserialize_map(self.value()?, visitor)
+    }
+}
@@ -1,16 +1,20 @@
-use super::kernel::{KernelData, PendingDelivery};
+use super::device::REGISTER_INBOUND_HANDLER;
+use super::host::{HostCapData, KernelExportHandle, KernelPromiseHandle};
+use super::kernel::{KernelData, PendingDelivery, SendReference};
 use super::kernel_types::{
-    KernelArgSlot, KernelCapData, KernelExport, KernelExportID, KernelMessage,
-    KernelPromiseResolverID, KernelTarget, VatID,
+    DeviceName, KernelArgSlot, KernelCapData, KernelExport, KernelExportID,
+    KernelMessage, KernelPromiseResolverID, KernelTarget, VatID,
 };
+use super::marshal::{serialize, Value};
 use super::promise::KernelPromise;
+use super::report::SyscallReport;
 use super::syscall::Syscall;
 use super::vat_types::{
-    OutboundVatMessage, VatArgSlot, VatCapData, VatPromiseID, VatResolveTarget,
-    VatResolverID, VatSendTarget,
+    OutboundVatMessage, VatArgSlot, VatCapData, VatDeviceID, VatPromiseID,
+    VatResolveTarget, VatResolverID, VatSendTarget,
 };
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use std::rc::Rc;
 
 enum TargetCategory {
@@ -40,7 +44,7 @@ impl VatSyscall {
                 KernelTarget::Export(ke)
             }
             VatSendTarget::Promise(vpid) => {
-                let kpid = vd.promise_clist.map_outbound(vpid);
+                let kpid = vd.map_outbound_promise(vpid);
                 KernelTarget::Promise(kpid)
             }
         }
@@ -69,10 +73,7 @@ impl VatSyscall {
         p: KernelPromise,
     ) -> (VatPromiseID, KernelPromiseResolverID) {
         let mut kd = self.kd.borrow_mut();
-        let id = kd.next_promise_resolver_id;
-        kd.next_promise_resolver_id = id + 1;
-        let kprid = KernelPromiseResolverID(id);
-        kd.promises.insert(kprid, p);
+        let kprid = kd.allocate_promise(p);
         let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
         let vpid = vd.promise_clist.map_inbound(kprid);
         (vpid, kprid)
@@ -83,7 +84,7 @@ impl VatSyscall {
         sender: VatID,
         receiver: VatID,
     ) -> (VatPromiseID, KernelPromiseResolverID) {
-        let mut subscribers = HashSet::new();
+        let mut subscribers = BTreeSet::new();
         subscribers.insert(sender);
         let p = KernelPromise::Unresolved {
             subscribers,
@@ -122,7 +123,7 @@ impl VatSyscall {
                 KernelArgSlot::Export(KernelExport(self.vat_id, keid))
             }
             VatArgSlot::Promise(vpid) => {
-                let kpid = vd.promise_clist.map_outbound(vpid);
+                let kpid = vd.map_outbound_promise(vpid);
                 KernelArgSlot::Promise(kpid)
             }
         }
@@ -139,16 +140,27 @@ impl VatSyscall {
         }
     }
 
-    fn map_outbound_message(
+    fn record(&self, report: SyscallReport) {
+        self.kd.borrow_mut().record_syscall(self.vat_id, report);
+    }
+
+    /// answer a malformed invoke with a TypeError instead of calling the
+    /// device. The slots are left out of the report, since they may not
+    /// map to anything.
+    fn refuse_invoke(
         &self,
+        device: DeviceName,
         vmsg: OutboundVatMessage,
-        okprid: Option<KernelPromiseResolverID>,
-    ) -> KernelMessage {
-        KernelMessage {
-            name: vmsg.name.to_string(),
-            args: self.map_outbound_capdata(vmsg.args),
-            resolver: okprid,
-        }
+        problem: &str,
+    ) -> VatCapData {
+        let result = serialize(&Value::error("TypeError", problem));
+        self.record(SyscallReport::Invoke {
+            device,
+            method: vmsg.name,
+            args: HostCapData::new(&vmsg.args.body, vec![]),
+            result: HostCapData::new(&result.body, vec![]),
+        });
+        result
     }
 
     fn do_send(
@@ -157,11 +169,10 @@ impl VatSyscall {
         vmsg: OutboundVatMessage,
         send_only: bool,
     ) -> Option<VatPromiseID> {
-        println!("syscall.send {}.{}", vtarget, vmsg.name);
-
-        // convert and classify the target
+        // convert and classify the target, and convert the arguments
         let ktarget = self.map_outbound_target(vtarget);
         let tc: TargetCategory = self.classify_target(ktarget);
+        let kargs = self.map_outbound_capdata(vmsg.args);
         use TargetCategory::*;
 
         // Now construct the return promise, if any. The state of the promise
@@ -182,27 +193,38 @@ impl VatSyscall {
             (Some(vpid), Some(kprid))
         };
 
+        self.record(SyscallReport::Send {
+            target: ktarget.into(),
+            method: vmsg.name.clone(),
+            args: (&kargs).into(),
+            result: okprid.map(KernelPromiseHandle),
+        });
+
         // now that we have the result promise, build the KernelMessage
         // around it, if necessary, and push it onto the run queue
 
+        let kmsg = KernelMessage {
+            name: vmsg.name,
+            args: kargs,
+            resolver: okprid,
+        };
+        let reference = SendReference::Vat(self.vat_id, vtarget);
         use PendingDelivery::*;
         match tc {
             Export(ke) => {
-                let kmsg = self.map_outbound_message(vmsg, okprid);
                 let pd = Deliver {
                     target: ke,
                     message: kmsg,
                 };
-                self.kd.borrow_mut().run_queue.0.push_back(pd);
+                self.kd.borrow_mut().queue_send(pd, reference);
             }
             Promise(vat_id, kprid) => {
-                let kmsg = self.map_outbound_message(vmsg, okprid);
                 let pd = DeliverPromise {
                     vat_id,
                     target: kprid,
                     message: kmsg,
                 };
-                self.kd.borrow_mut().run_queue.0.push_back(pd);
+                self.kd.borrow_mut().queue_send(pd, reference);
             }
             ToDataError | Rejected(..) => (),
         };
@@ -223,7 +245,7 @@ impl VatSyscall {
             result: ktarget,
         };
         let mut kd = self.kd.borrow_mut();
-        kd.run_queue.0.push_back(notification);
+        kd.queue(notification);
     }
 
     fn push_notify_fulfill_to_data(
@@ -238,7 +260,7 @@ impl VatSyscall {
             data: data.clone(),
         };
         let mut kd = self.kd.borrow_mut();
-        kd.run_queue.0.push_back(notification);
+        kd.queue(notification);
     }
 
     fn push_notify_reject(
@@ -253,7 +275,7 @@ impl VatSyscall {
             data: data.clone(),
         };
         let mut kd = self.kd.borrow_mut();
-        kd.run_queue.0.push_back(notification);
+        kd.queue(notification);
     }
 }
 
@@ -267,22 +289,102 @@ impl Syscall for VatSyscall {
         self.do_send(vtarget, vmsg, true);
     }
 
+    fn invoke(&mut self, vtarget: VatDeviceID, vmsg: OutboundVatMessage) -> VatCapData {
+        let (device, handler) = {
+            let mut kd = self.kd.borrow_mut();
+            let vd = kd.vat_data.get(&self.vat_id).unwrap();
+            let device_id = vd.device_clist.map_outbound(vtarget);
+            let device = kd.device_data.get(&device_id).unwrap().name.clone();
+            if vmsg.name == REGISTER_INBOUND_HANDLER {
+                let ke = match vmsg.args.slots[..] {
+                    [VatArgSlot::Export(veid)] => {
+                        KernelExport(self.vat_id, KernelExportID(veid.0))
+                    }
+                    [VatArgSlot::Import(viid)] => vd.import_clist.map_outbound(viid),
+                    [VatArgSlot::Promise(_)] => {
+                        drop(kd);
+                        return self.refuse_invoke(
+                            device,
+                            vmsg,
+                            "handler cannot be a promise",
+                        );
+                    }
+                    _ => {
+                        drop(kd);
+                        return self.refuse_invoke(
+                            device,
+                            vmsg,
+                            "handler must be a single slot",
+                        );
+                    }
+                };
+                let dd = kd.device_data.get_mut(&device_id).unwrap();
+                dd.inbound_handler = Some(ke);
+                let report = SyscallReport::Invoke {
+                    device,
+                    method: vmsg.name,
+                    args: HostCapData {
+                        body: vmsg.args.body,
+                        slots: vec![KernelArgSlot::Export(ke).into()],
+                    },
+                    result: HostCapData::new(b"", vec![]),
+                };
+                kd.record_syscall(self.vat_id, report);
+                return VatCapData {
+                    body: vec![],
+                    slots: vec![],
+                };
+            }
+            // the host cannot hold vat references, so bridge traffic is
+            // data-only in both directions
+            if !vmsg.args.slots.is_empty() {
+                drop(kd);
+                return self.refuse_invoke(
+                    device,
+                    vmsg,
+                    "bridge arguments cannot carry slots",
+                );
+            }
+            let dd = kd.device_data.get(&device_id).unwrap();
+            (device, dd.handler.clone())
+        };
+        let result = handler(&vmsg.name, vmsg.args.clone());
+        if !result.slots.is_empty() {
+            return self.refuse_invoke(device, vmsg, "bridge results cannot carry slots");
+        }
+        self.record(SyscallReport::Invoke {
+            device,
+            method: vmsg.name,
+            args: HostCapData::new(&vmsg.args.body, vec![]),
+            result: HostCapData::new(&result.body, vec![]),
+        });
+        result
+    }
+
     fn allocate_promise_and_resolver(&mut self) -> (VatPromiseID, VatResolverID) {
         let p = KernelPromise::Unresolved {
-            subscribers: HashSet::new(),
+            subscribers: BTreeSet::new(),
             decider: self.vat_id,
         };
         let (vpid, kprid) = self.allocate_promise(p);
         let mut kd = self.kd.borrow_mut();
         let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
         let vrid = vd.resolver_clist.map_inbound(kprid);
+        let report = SyscallReport::AllocatePromiseAndResolver {
+            promise: KernelPromiseHandle(kprid),
+        };
+        kd.record_syscall(self.vat_id, report);
         (vpid, vrid)
     }
 
     fn subscribe(&mut self, vpid: VatPromiseID) {
         let mut kd = self.kd.borrow_mut();
         let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
-        let kprid = vd.promise_clist.map_outbound(vpid);
+        let kprid = vd.map_outbound_promise(vpid);
+        let report = SyscallReport::Subscribe {
+            promise: KernelPromiseHandle(kprid),
+        };
+        kd.record_syscall(self.vat_id, report);
         let p: &mut KernelPromise = kd.promises.get_mut(&kprid).unwrap();
         use KernelPromise::*;
         use PendingDelivery::*;
@@ -310,7 +412,7 @@ impl Syscall for VatSyscall {
                 data: data.clone(),
             },
         };
-        kd.run_queue.0.push_back(pd);
+        kd.queue(pd);
     }
 
     fn fulfill_to_target(&mut self, resolver: VatResolverID, vtarget: VatResolveTarget) {
@@ -332,14 +434,25 @@ impl Syscall for VatSyscall {
             {
                 // resolvers are not transferrable
                 assert_eq!(*decider, self.vat_id);
-                // TODO: HashSet.iter is nondeterministic
                 subscribers = subs.iter().cloned().collect();
             } else {
                 panic!(); // TODO: DuplicateFulfillError
             }
+            let report = SyscallReport::FulfillToTarget {
+                promise: KernelPromiseHandle(kprid),
+                target: KernelExportHandle(ktarget),
+            };
+            kd.record_syscall(self.vat_id, report);
             let new_promise = FulfilledToTarget(ktarget);
             kd.promises.remove(&kprid);
             kd.promises.insert(kprid, new_promise);
+            kd.promise_resolved(kprid);
+            // messages queued to us as the decider go where it now leads
+            let queued = kd.take_queued_to(kprid);
+            kd.resend(queued.into());
+            // and with nothing left to decide, the resolver is spent
+            let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
+            vd.spend_resolver(kprid, resolver);
         };
 
         for s in subscribers {
@@ -364,14 +477,25 @@ impl Syscall for VatSyscall {
             {
                 // resolvers are not transferrable
                 assert_eq!(*decider, self.vat_id);
-                // TODO: HashSet.iter is nondeterministic
                 subscribers = subs.iter().cloned().collect();
             } else {
                 panic!(); // TODO: DuplicateFulfillError
             }
+            let report = SyscallReport::FulfillToData {
+                promise: KernelPromiseHandle(kprid),
+                data: (&kdata).into(),
+            };
+            kd.record_syscall(self.vat_id, report);
             let new_promise = FulfilledToData(kdata.clone());
             kd.promises.remove(&kprid);
             kd.promises.insert(kprid, new_promise);
+            kd.promise_resolved(kprid);
+            // messages queued to us as the decider go where it now leads
+            let queued = kd.take_queued_to(kprid);
+            kd.resend(queued.into());
+            // and with nothing left to decide, the resolver is spent
+            let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
+            vd.spend_resolver(kprid, resolver);
         };
         for s in subscribers {
             self.push_notify_fulfill_to_data(s, kprid, kdata.clone());
@@ -395,14 +519,25 @@ impl Syscall for VatSyscall {
             {
                 // resolvers are not transferrable
                 assert_eq!(*decider, self.vat_id);
-                // TODO: HashSet.iter is nondeterministic
                 subscribers = subs.iter().cloned().collect();
             } else {
                 panic!(); // TODO: DuplicateFulfillError
             }
+            let report = SyscallReport::Reject {
+                promise: KernelPromiseHandle(kprid),
+                data: (&kdata).into(),
+            };
+            kd.record_syscall(self.vat_id, report);
             let new_promise = Rejected(kdata.clone());
             kd.promises.remove(&kprid);
             kd.promises.insert(kprid, new_promise);
+            kd.promise_resolved(kprid);
+            // messages queued to us as the decider go where it now leads
+            let queued = kd.take_queued_to(kprid);
+            kd.resend(queued.into());
+            // and with nothing left to decide, the resolver is spent
+            let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
+            vd.spend_resolver(kprid, resolver);
         };
         for s in subscribers {
             self.push_notify_reject(s, kprid, kdata.clone());
@@ -411,6 +546,7 @@ impl Syscall for VatSyscall {
 
     fn forward(&mut self, resolver: VatResolverID, vtarget: VatPromiseID) {
         use KernelPromise::*;
+        use PendingDelivery::*;
 
         let mut kd = self.kd.borrow_mut();
         let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
@@ -429,25 +565,38 @@ impl Syscall for VatSyscall {
                 } => {
                     // resolvers are not transferrable
                     assert_eq!(*decider, self.vat_id);
-                    // TODO: HashSet.iter is nondeterministic
                     old_subscribers = subs.iter().cloned().collect();
                 }
                 _ => panic!(), // TODO: DuplicateFulfillError
             };
         }
+        let report = SyscallReport::Forward {
+            promise: KernelPromiseHandle(old_id),
+            target: KernelPromiseHandle(new_id),
+        };
+        kd.record_syscall(self.vat_id, report);
         kd.promises.remove(&old_id);
+        kd.forwarded.insert(old_id, new_id);
+        kd.promise_resolved(old_id);
 
         // Walk through all clists and replace every mention of the old
-        // promise with the new target
+        // promise with the new target. The resolver is spent, so it leaves
+        // this vat's clist.
         for vd in kd.vat_data.values_mut() {
             vd.forward_promise(old_id, new_id);
         }
+        let vd = kd.vat_data.get_mut(&self.vat_id).unwrap();
+        vd.spend_resolver(old_id, resolver);
+
+        // Messages already queued to the old promise must follow it to the
+        // new one.
+        let redirected = kd.forward_references(old_id, new_id);
+        kd.resend(redirected);
 
         // The new promise might have already been fulfilled, so the old
         // subscribers must be notified about the fulfillment. Or, if the new
         // promise is still unresolved, make the old subscribers watch the
         // new promise instead.
-        use PendingDelivery::*;
         let new_promise = kd.promises.get_mut(&new_id).unwrap();
         let pds: Vec<PendingDelivery> = match new_promise {
             Unresolved {
@@ -463,7 +612,7 @@ impl Syscall for VatSyscall {
                 .iter()
                 .map(|s| NotifyFulfillToTarget {
                     vat_id: *s,
-                    target: old_id,
+                    target: new_id,
                     result: *ktarget,
                 })
                 .collect(),
@@ -471,7 +620,7 @@ impl Syscall for VatSyscall {
                 .iter()
                 .map(|s| NotifyFulfillToData {
                     vat_id: *s,
-                    target: old_id,
+                    target: new_id,
                     data: data.clone(),
                 })
                 .collect(),
@@ -479,13 +628,13 @@ impl Syscall for VatSyscall {
                 .iter()
                 .map(|s| NotifyReject {
                     vat_id: *s,
-                    target: old_id,
+                    target: new_id,
                     data: data.clone(),
                 })
                 .collect(),
         };
         for pd in pds {
-            kd.run_queue.0.push_back(pd);
+            kd.queue(pd);
         }
     }
 }
@@ -8,6 +8,9 @@ pub struct VatResolverID(pub u32);
 pub struct VatExportID(pub u32);
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub struct VatImportID(pub u32);
+/// syscall.invoke must point at a VatDeviceID
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct VatDeviceID(pub u32);
 
 /// dispatch.notify_fulfill_to_target gives us a VatResolveTarget
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
@@ -74,6 +77,12 @@ impl fmt::Display for VatImportID {
     }
 }
 
+impl fmt::Display for VatDeviceID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VatDeviceID-{}", self.0)
+    }
+}
+
 impl fmt::Display for VatSendTarget {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use VatSendTarget::*;
@@ -97,11 +106,19 @@ impl fmt::Display for VatArgSlot {
 
 /// VatCapData is used for the arguments of syscall.send, dispatch.deliver,
 /// fulfill_to_data, and reject
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VatCapData {
     pub body: Vec<u8>,
     pub slots: Vec<VatArgSlot>,
 }
+impl VatCapData {
+    pub fn new(body: &[u8], slots: Vec<VatArgSlot>) -> Self {
+        VatCapData {
+            body: body.to_vec(),
+            slots,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct OutboundVatMessage {
@@ -120,7 +137,7 @@ impl OutboundVatMessage {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct InboundVatMessage {
     pub name: String,
     pub args: VatCapData,
@@ -142,4 +159,21 @@ impl InboundVatMessage {
             resolver,
         }
     }
+
+    /// a message with no slots and no result, to be built up with
+    /// `with_slots` and `with_resolver`: handy for driving a Dispatch by
+    /// hand in tests
+    pub fn call(name: &str, body: &[u8]) -> Self {
+        InboundVatMessage::new(name, body, vec![], None)
+    }
+
+    pub fn with_slots(mut self, slots: Vec<VatArgSlot>) -> Self {
+        self.args.slots = slots;
+        self
+    }
+
+    pub fn with_resolver(mut self, resolver: VatResolverID) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
 }
@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    e, AsyncVat, Config, Controller, Dispatch, Fulfillment, InboundVatMessage,
+    MockSyscall, Settlement, Task, VatCapData, VatExportID, VatHandle, VatImportID,
+    VatName, VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+fn data(body: &str) -> VatCapData {
+    VatCapData::new(body.as_bytes(), vec![])
+}
+
+fn body(data: &VatCapData) -> String {
+    String::from_utf8(data.body.clone()).unwrap()
+}
+
+fn describe(settlement: &Settlement) -> String {
+    match settlement {
+        Ok(Fulfillment::Data(d)) => body(d),
+        Ok(Fulfillment::Target(t)) => format!("{:?}", t),
+        Err(d) => format!("rejected: {}", body(d)),
+    }
+}
+
+// the bank vat: its root opens a purse (export 1), which holds a balance
+fn install_bank(cfg: &mut Config) {
+    let balance = Rc::new(RefCell::new(0u32));
+    let handler = move |_: &_, target: VatExportID, message: InboundVatMessage| {
+        let balance = balance.clone();
+        let amount: u32 = body(&message.args).parse().unwrap_or(0);
+        let task: Task = Box::pin(async move {
+            match (target.0, message.name.as_str()) {
+                (0, "open") => Ok(Fulfillment::Target(VatResolveTarget::Export(
+                    VatExportID(1),
+                ))),
+                (1, "deposit") => {
+                    *balance.borrow_mut() += amount;
+                    Ok(Fulfillment::Data(data(&balance.borrow().to_string())))
+                }
+                (1, "withdraw") if amount > *balance.borrow() => {
+                    Err(data("insufficient funds"))
+                }
+                (1, "withdraw") => {
+                    *balance.borrow_mut() -= amount;
+                    Ok(Fulfillment::Data(data(&balance.borrow().to_string())))
+                }
+                other => panic!("bank did not expect {:?}", other),
+            }
+        });
+        task
+    };
+    cfg.add_vat(&VatName("bank".to_string()), AsyncVat::setup(handler));
+}
+
+// the bootstrap vat opens a purse, pipelines a deposit on it, and awaits the
+// results in sequence
+fn install_bootstrap(cfg: &mut Config, log: &Log) {
+    let log = log.clone();
+    let handler = move |vat: &VatHandle, _, message: InboundVatMessage| {
+        assert_eq!(message.name, "bootstrap");
+        let vat = vat.clone();
+        let log = log.clone();
+        let task: Task = Box::pin(async move {
+            let bank = vat.far(VatSendTarget::Import(VatImportID(1)));
+            let p_purse = e(&bank).send("open", data(""));
+            let purse = p_purse.far();
+            let r = e(&purse).send("deposit", data("10")).await?;
+            log.borrow_mut()
+                .push(format!("deposit -> {}", describe(&Ok(r))));
+
+            let r = e(&purse).send("withdraw", data("25")).await;
+            log.borrow_mut()
+                .push(format!("withdraw 25 -> {}", describe(&r)));
+
+            // the purse promise resolved long ago: awaiting it now still works
+            let opened = p_purse.await?;
+            log.borrow_mut()
+                .push(format!("purse is {}", describe(&Ok(opened))));
+
+            let withdrawals = vec![
+                e(&purse).send("withdraw", data("3")),
+                e(&purse).send("withdraw", data("4")),
+            ];
+            for w in withdrawals {
+                let r = w.await;
+                log.borrow_mut()
+                    .push(format!("withdraw -> {}", describe(&r)));
+            }
+
+            // "?" turns a rejection into this task's result
+            e(&purse).send("withdraw", data("100")).await?;
+            log.borrow_mut().push("not reached".to_string());
+            Ok(Fulfillment::Data(data("")))
+        });
+        task
+    };
+    cfg.add_vat(&VatName("bootstrap".to_string()), AsyncVat::setup(handler));
+}
+
+#[test]
+fn test_async_vat() {
+    let log: Log = Rc::new(RefCell::new(vec![]));
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    install_bootstrap(&mut cfg, &log);
+    install_bank(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    c.add_import(&bootstrap, 1, &VatName("bank".to_string()), 0);
+    c.start();
+    c.run();
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "deposit -> 10",
+            "withdraw 25 -> rejected: insufficient funds",
+            "purse is Import(VatImportID(0))",
+            "withdraw -> 7",
+            "withdraw -> 3",
+        ]
+    );
+}
+
+// the same run, with the kernel delivering in a different (but legal) order
+#[test]
+fn test_async_vat_chaos() {
+    for seed in 0..20 {
+        let log: Log = Rc::new(RefCell::new(vec![]));
+        let mut cfg = Config::new();
+        cfg.check_invariants(true);
+        cfg.chaos_scheduler(seed);
+        install_bootstrap(&mut cfg, &log);
+        install_bank(&mut cfg);
+        let mut c = Controller::new(cfg);
+        let bootstrap = VatName("bootstrap".to_string());
+        c.add_import(&bootstrap, 1, &VatName("bank".to_string()), 0);
+        c.start();
+        c.run();
+        assert_eq!(log.borrow().len(), 5, "seed {}", seed);
+        assert_eq!(log.borrow()[0], "deposit -> 10", "seed {}", seed);
+    }
+}
+
+#[test]
+#[should_panic(expected = "no unresolved promise for resolver")]
+fn test_deliver_to_resolved_promise() {
+    let handler = |_: &VatHandle, _: VatExportID, message: InboundVatMessage| -> Task {
+        Box::pin(async move { Ok(Fulfillment::Data(data(&message.name))) })
+    };
+    let mut vat = AsyncVat::new(Box::new(MockSyscall::new()), handler);
+    let r = VatResolverID(3);
+    let call = || InboundVatMessage::call("echo", b"");
+    vat.deliver(VatExportID(0), call().with_resolver(r));
+    // the task has finished, so the promise is already resolved
+    vat.deliver_promise(r, call());
+}
@@ -13,7 +13,7 @@ struct Vat1Dispatch {
     p: Option<VatPromiseID>,
 }
 impl Dispatch for Vat1Dispatch {
-    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) -> () {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
         println!("Vat1.deliver {}", target);
         match target {
             VatExportID(0) => {
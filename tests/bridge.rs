@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    unserialize, BridgeError, BridgeHandler, Config, Controller, DeviceName, Dispatch,
+    EventLog, InboundVatMessage, OutboundVatMessage, ScriptedVat, Setup, Syscall, Value,
+    VatArgSlot, VatCapData, VatDeviceID, VatExportID, VatName, VatPromiseID,
+    VatResolveTarget, VatResolverID,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+    log: Rc<RefCell<Vec<u32>>>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        println!("Vat1.deliver {} .{}", target, message.name);
+        let bridge = VatDeviceID(3);
+        if target == VatExportID(0) && message.name == "bootstrap" {
+            let handler = VatArgSlot::Export(VatExportID(5));
+            let vmsg =
+                OutboundVatMessage::new("registerInboundHandler", b"", vec![handler]);
+            self.syscall.invoke(bridge, vmsg);
+            let vmsg = OutboundVatMessage::new("add", b"4", vec![]);
+            let res = self.syscall.invoke(bridge, vmsg);
+            assert_eq!(res.body, b"5");
+            assert_eq!(res.slots, vec![]);
+            self.log.borrow_mut().push(1);
+        } else if target == VatExportID(5) && message.name == "inbound" {
+            assert_eq!(message.args.body, b"from host");
+            assert_eq!(message.resolver, None);
+            self.log.borrow_mut().push(2);
+        } else {
+            panic!("unknown message {}.{}", target, message.name);
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+#[test]
+fn test_bridge() {
+    let mut cfg = Config::new();
+    let log: Vec<u32> = vec![];
+    let r = Rc::new(RefCell::new(log));
+    let r2 = r.clone();
+    let vn = VatName("bootstrap".to_string());
+    let setup =
+        |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall, log: r2 }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+
+    let calls: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(vec![]));
+    let calls2 = calls.clone();
+    let handler = move |method: &str, args: VatCapData| -> VatCapData {
+        calls2.borrow_mut().push(method.to_string());
+        let n: u32 = String::from_utf8(args.body).unwrap().parse().unwrap();
+        VatCapData {
+            body: format!("{}", n + 1).into_bytes(),
+            slots: vec![],
+        }
+    };
+    let dn = DeviceName("bridge".to_string());
+    let hb: Box<BridgeHandler> = Box::new(handler);
+    cfg.add_bridge(&dn, hb);
+
+    let mut c = Controller::new(cfg);
+    c.add_device(&vn, 3, &dn);
+    c.start();
+    c.run();
+    assert_eq!(*r.borrow(), vec![1]);
+    assert_eq!(*calls.borrow(), vec!["add".to_string()]);
+
+    c.bridge_inbound("bridge", "inbound", b"from host").unwrap();
+    c.run();
+    assert_eq!(*r.borrow(), vec![1, 2]);
+}
+
+fn echo_bridge(cfg: &mut Config, calls: &Rc<RefCell<Vec<String>>>) {
+    let calls = calls.clone();
+    let handler = move |method: &str, args: VatCapData| -> VatCapData {
+        calls.borrow_mut().push(method.to_string());
+        args
+    };
+    let hb: Box<BridgeHandler> = Box::new(handler);
+    cfg.add_bridge(&DeviceName("bridge".to_string()), hb);
+}
+
+#[test]
+fn test_bridge_inbound_errors() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    ScriptedVat::new("bootstrap", &log).install(&mut cfg);
+    echo_bridge(&mut cfg, &Rc::new(RefCell::new(vec![])));
+    let mut c = Controller::new(cfg);
+    c.add_device(
+        &VatName("bootstrap".to_string()),
+        3,
+        &DeviceName("bridge".to_string()),
+    );
+    assert_eq!(
+        c.bridge_inbound("nope", "inbound", b""),
+        Err(BridgeError::UnknownBridge(DeviceName("nope".to_string())))
+    );
+    let err = c.bridge_inbound("bridge", "inbound", b"").unwrap_err();
+    assert_eq!(
+        err,
+        BridgeError::NoInboundHandler(DeviceName("bridge".to_string()))
+    );
+    assert_eq!(
+        err.to_string(),
+        "dev-bridge has no inbound handler registered"
+    );
+}
+
+// a vat that misuses the bridge gets a TypeError back, and the host's
+// handler never sees the call
+#[test]
+fn test_bridge_refuses_slots() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let bridge = VatDeviceID(3);
+            let (p, _) = s.allocate_promise_and_resolver();
+            let attempts = vec![
+                OutboundVatMessage::new(
+                    "registerInboundHandler",
+                    b"",
+                    vec![VatArgSlot::Promise(p)],
+                ),
+                OutboundVatMessage::new("registerInboundHandler", b"", vec![]),
+                OutboundVatMessage::new(
+                    "add",
+                    b"#[\"$0\"]",
+                    vec![VatArgSlot::Export(VatExportID(1))],
+                ),
+            ];
+            for vmsg in attempts {
+                let result = s.invoke(bridge, vmsg);
+                match unserialize(&result) {
+                    Ok(Value::Error { name, message }) => {
+                        assert_eq!(name, "TypeError");
+                        s.log(&message);
+                    }
+                    other => panic!("expected an error, got {:?}", other),
+                }
+            }
+        })
+        .install(&mut cfg);
+    let calls = Rc::new(RefCell::new(vec![]));
+    echo_bridge(&mut cfg, &calls);
+    let mut c = Controller::new(cfg);
+    c.add_device(
+        &VatName("bootstrap".to_string()),
+        3,
+        &DeviceName("bridge".to_string()),
+    );
+    c.start();
+    c.run();
+    assert_eq!(
+        log.take(),
+        vec![
+            "bootstrap: deliver bootstrap",
+            "bootstrap: handler cannot be a promise",
+            "bootstrap: handler must be a single slot",
+            "bootstrap: bridge arguments cannot carry slots",
+        ]
+    );
+    assert!(calls.borrow().is_empty());
+    assert!(c.bridge_inbound("bridge", "inbound", b"").is_err());
+}
+
+// the host cannot hand the vat a reference either: a result with slots is
+// replaced by a TypeError
+#[test]
+fn test_bridge_refuses_result_slots() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let vmsg = OutboundVatMessage::new("leak", b"", vec![]);
+            let result = s.invoke(VatDeviceID(3), vmsg);
+            assert!(result.slots.is_empty());
+            match unserialize(&result) {
+                Ok(Value::Error { name, message }) => {
+                    assert_eq!(name, "TypeError");
+                    s.log(&message);
+                }
+                other => panic!("expected an error, got {:?}", other),
+            }
+        })
+        .install(&mut cfg);
+    let handler = |_: &str, _: VatCapData| -> VatCapData {
+        VatCapData::new(b"#\"$0\"", vec![VatArgSlot::Export(VatExportID(1))])
+    };
+    let hb: Box<BridgeHandler> = Box::new(handler);
+    cfg.add_bridge(&DeviceName("bridge".to_string()), hb);
+    let mut c = Controller::new(cfg);
+    c.add_device(
+        &VatName("bootstrap".to_string()),
+        3,
+        &DeviceName("bridge".to_string()),
+    );
+    c.start();
+    c.run();
+    assert_eq!(
+        log.take(),
+        vec![
+            "bootstrap: deliver bootstrap",
+            "bootstrap: bridge results cannot carry slots",
+        ]
+    );
+}
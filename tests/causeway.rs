@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Cause, CausewayTrace, Config, Controller, Dispatch, InboundVatMessage, MessageID,
+    OutboundVatMessage, Setup, Syscall, VatArgSlot, VatCapData, VatExportID, VatImportID,
+    VatName, VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        if target == VatExportID(0) {
+            let t = VatSendTarget::Import(VatImportID(1));
+            let arg1 = VatArgSlot::Export(VatExportID(22));
+            let vmsg = OutboundVatMessage::new("foo", b"body", vec![arg1]);
+            self.syscall.send(t, vmsg);
+        } else {
+            let res = VatCapData {
+                body: b"result".to_vec(),
+                slots: vec![],
+            };
+            self.syscall.fulfill_to_data(message.resolver.unwrap(), res);
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build(cfg: &mut Config) {
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+}
+
+#[test]
+fn test_causeway() {
+    let trace = Rc::new(RefCell::new(CausewayTrace::new()));
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(trace.clone()));
+    build(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let vn = VatName("bootstrap".to_string());
+    c.add_import(&vn, 1, &vn, 2);
+    c.start();
+    c.run();
+
+    let json = trace.borrow().to_json();
+    let lines: Vec<&str> = json.lines().collect();
+    let expected = vec![
+        r#"["#,
+        // the host queues bootstrap()
+        r#"{"class":["org.ref_send.log.Sent","org.ref_send.log.Event"],"anchor":{"number":1,"turn":{"loop":"host","number":1}},"trace":{"calls":[{"name":"ko0.0.bootstrap","source":"host"}]},"message":"m0"},"#,
+        // which sends foo()
+        r#"{"class":["org.ref_send.log.Got","org.ref_send.log.Event"],"anchor":{"number":1,"turn":{"loop":"bootstrap","number":0}},"trace":{"calls":[{"name":"ko0.0.bootstrap","source":"bootstrap"}]},"message":"m0"},"#,
+        r#"{"class":["org.ref_send.log.Sent","org.ref_send.log.Event"],"anchor":{"number":2,"turn":{"loop":"bootstrap","number":0}},"trace":{"calls":[{"name":"ko0.2.foo","source":"bootstrap"}]},"message":"m1"},"#,
+        // which fulfills the result, notifying the sender
+        r#"{"class":["org.ref_send.log.Got","org.ref_send.log.Event"],"anchor":{"number":1,"turn":{"loop":"bootstrap","number":1}},"trace":{"calls":[{"name":"ko0.2.foo","source":"bootstrap"}]},"message":"m1"},"#,
+        r#"{"class":["org.ref_send.log.Fulfilled","org.ref_send.log.Event"],"anchor":{"number":2,"turn":{"loop":"bootstrap","number":1}},"trace":{"calls":[{"name":"fulfill_to_data kp0","source":"bootstrap"}]},"condition":"kp0"},"#,
+        r#"{"class":["org.ref_send.log.SentIf","org.ref_send.log.Event"],"anchor":{"number":3,"turn":{"loop":"bootstrap","number":1}},"trace":{"calls":[{"name":"notify_fulfill_to_data kp0","source":"bootstrap"}]},"condition":"kp0","message":"m2"},"#,
+        r#"{"class":["org.ref_send.log.Got","org.ref_send.log.Event"],"anchor":{"number":1,"turn":{"loop":"bootstrap","number":2}},"trace":{"calls":[{"name":"notify_fulfill_to_data kp0","source":"bootstrap"}]},"message":"m2"}"#,
+        r#"]"#,
+    ];
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn test_message_causes() {
+    let mut c = {
+        let mut cfg = Config::new();
+        build(&mut cfg);
+        Controller::new(cfg)
+    };
+    let vn = VatName("bootstrap".to_string());
+    c.add_import(&vn, 1, &vn, 2);
+    c.start();
+    let r0 = c.step().unwrap();
+    assert_eq!(r0.delivery.id, MessageID(0));
+    assert_eq!(r0.delivery.cause, Cause::Host);
+    let r1 = c.step().unwrap();
+    assert_eq!(r1.delivery.id, r0.queued[0].id);
+    assert_eq!(
+        r1.delivery.cause,
+        Cause::Syscall {
+            message: r0.delivery.id,
+            crank: 0,
+            syscall: 0,
+        }
+    );
+    // the notification is caused by the fulfill_to_data syscall
+    let r2 = c.step().unwrap();
+    assert_eq!(
+        r2.delivery.cause,
+        Cause::Syscall {
+            message: r1.delivery.id,
+            crank: 1,
+            syscall: 0,
+        }
+    );
+    assert!(c.step().is_none());
+}
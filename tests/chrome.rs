@@ -0,0 +1,88 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    ChromeTrace, Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage,
+    Setup, Syscall, VatArgSlot, VatCapData, VatExportID, VatImportID, VatName,
+    VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        if target == VatExportID(0) {
+            let t = VatSendTarget::Import(VatImportID(1));
+            let arg1 = VatArgSlot::Export(VatExportID(22));
+            let vmsg = OutboundVatMessage::new("foo", b"body", vec![arg1]);
+            self.syscall.send(t, vmsg);
+        } else {
+            let res = VatCapData {
+                body: b"result".to_vec(),
+                slots: vec![],
+            };
+            self.syscall.fulfill_to_data(message.resolver.unwrap(), res);
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build(cfg: &mut Config) {
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+}
+
+#[test]
+fn test_chrome_trace() {
+    // each reading of the clock is one millisecond later
+    let mut t = 0.0;
+    let clock = move || {
+        t += 0.001;
+        t
+    };
+    let trace = Rc::new(RefCell::new(ChromeTrace::with_clock(Box::new(clock))));
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(trace.clone()));
+    build(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let vn = VatName("bootstrap".to_string());
+    c.add_import(&vn, 1, &vn, 2);
+    c.start();
+    c.run();
+
+    let json = trace.borrow().to_json();
+    let lines: Vec<&str> = json.lines().collect();
+    let expected = vec![
+        r#"{"displayTimeUnit":"ms","traceEvents":["#,
+        r#"{"name":"process_name","ph":"M","pid":1,"args":{"name":"swingset"}},"#,
+        r#"{"name":"thread_name","ph":"M","pid":1,"tid":0,"args":{"name":"host"}},"#,
+        r#"{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"vat-bootstrap"}},"#,
+        // the host queues bootstrap(), with an arrow to its delivery
+        r#"{"name":"bootstrap","cat":"queue","ph":"X","ts":1000,"dur":0,"pid":1,"tid":0},"#,
+        r#"{"name":"message","cat":"flow","ph":"s","id":0,"ts":1000,"pid":1,"tid":0},"#,
+        // which sends foo() to itself, and takes two milliseconds
+        r#"{"name":"message","cat":"flow","ph":"f","id":0,"ts":2000,"pid":1,"tid":1,"bp":"e"},"#,
+        r#"{"name":"message","cat":"flow","ph":"s","id":1,"ts":3000,"pid":1,"tid":1},"#,
+        r#"{"name":"bootstrap","cat":"deliver","ph":"X","ts":2000,"dur":2000,"pid":1,"tid":1,"args":{"crankNum":0,"msgID":"m0","target":"ko0.0","syscalls":1}},"#,
+        // foo() fulfills its result, notifying the sender
+        r#"{"name":"message","cat":"flow","ph":"f","id":1,"ts":5000,"pid":1,"tid":1,"bp":"e"},"#,
+        r#"{"name":"message","cat":"flow","ph":"s","id":2,"ts":6000,"pid":1,"tid":1},"#,
+        r#"{"name":"foo","cat":"deliver","ph":"X","ts":5000,"dur":2000,"pid":1,"tid":1,"args":{"crankNum":1,"msgID":"m1","target":"ko0.2","syscalls":1}},"#,
+        r#"{"name":"message","cat":"flow","ph":"f","id":2,"ts":8000,"pid":1,"tid":1,"bp":"e"},"#,
+        r#"{"name":"notify_fulfill_to_data","cat":"notify_fulfill_to_data","ph":"X","ts":8000,"dur":1000,"pid":1,"tid":1,"args":{"crankNum":2,"msgID":"m2","target":"kp0","syscalls":0}}"#,
+        r#"]}"#,
+    ];
+    assert_eq!(lines, expected);
+}
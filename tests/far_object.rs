@@ -0,0 +1,125 @@
+//! #[far_object], which needs the "macros" feature:
+//!
+//!     cargo test --features macros --test far_object
+
+use swingset::{
+    far_object, from_capdata, to_capdata, unserialize, Config, Controller, FarObject,
+    FarVat, HostCapData, PromiseStatus, Value, VatCapData, VatName,
+};
+
+struct Counter {
+    count: u64,
+}
+
+#[far_object]
+impl Counter {
+    fn new() -> Self {
+        Counter { count: 0 }
+    }
+
+    fn increment(&mut self, n: u64) -> u64 {
+        self.count += n;
+        self.count
+    }
+
+    fn decrement(&mut self, n: u64) -> Result<u64, String> {
+        if n > self.count {
+            return Err(format!("cannot take {} from {}", n, self.count));
+        }
+        self.count -= n;
+        Ok(self.count)
+    }
+
+    fn describe(&self, label: String, verbose: bool) -> String {
+        match verbose {
+            true => format!("{}: {} counted", label, self.count),
+            false => label,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+}
+
+fn call<T: serde::Serialize>(
+    obj: &mut dyn FarObject,
+    method: &str,
+    args: &T,
+) -> Result<VatCapData, VatCapData> {
+    obj.dispatch(method, &to_capdata(args).unwrap())
+}
+
+fn error_message(data: &VatCapData) -> String {
+    match unserialize(data) {
+        Ok(Value::Error { message, .. }) => message,
+        other => panic!("expected an error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_dispatch() {
+    let mut counter = Counter::new();
+    let r = call(&mut counter, "increment", &(5u64,)).unwrap();
+    assert_eq!(from_capdata::<u64>(&r), Ok(5));
+    let r = call(&mut counter, "decrement", &(2u64,)).unwrap();
+    assert_eq!(from_capdata::<u64>(&r), Ok(3));
+    let r = call(&mut counter, "describe", &("c", true)).unwrap();
+    assert_eq!(from_capdata::<String>(&r).unwrap(), "c: 3 counted");
+    let r = call(&mut counter, "reset", &[(); 0]).unwrap();
+    assert_eq!(from_capdata::<()>(&r), Ok(()));
+    assert_eq!(counter.count, 0);
+}
+
+#[test]
+fn test_rejections() {
+    let mut counter = Counter::new();
+    let e = call(&mut counter, "decrement", &(1u64,)).unwrap_err();
+    assert_eq!(error_message(&e), "cannot take 1 from 0");
+    let e = call(&mut counter, "explode", &[(); 0]).unwrap_err();
+    assert_eq!(error_message(&e), "no such method \"explode\"");
+    // associated functions are not methods
+    let e = call(&mut counter, "new", &[(); 0]).unwrap_err();
+    assert_eq!(error_message(&e), "no such method \"new\"");
+    // too few, too many, or the wrong kind of arguments
+    for args in &[
+        to_capdata(&[(); 0]).unwrap(),
+        to_capdata(&(1u64, 2u64)).unwrap(),
+        to_capdata(&("one",)).unwrap(),
+    ] {
+        let e = counter.dispatch("increment", args).unwrap_err();
+        assert!(
+            error_message(&e).starts_with("bad arguments to increment"),
+            "{}",
+            error_message(&e)
+        );
+    }
+    assert_eq!(counter.count, 0);
+}
+
+#[test]
+fn test_far_vat() {
+    let mut cfg = Config::new();
+    cfg.add_vat(
+        &VatName("counter".to_string()),
+        FarVat::setup(Counter::new()),
+    );
+    let mut c = Controller::new(cfg);
+    let counter = c.export_handle("counter", 0);
+    let args = |data: VatCapData| HostCapData::new(&data.body, vec![]);
+    let p1 = c.queue_to_export(counter, "increment", args(to_capdata(&(7u64,)).unwrap()));
+    let p2 = c.queue_to_export(counter, "decrement", args(to_capdata(&(9u64,)).unwrap()));
+    c.run();
+    let expected = to_capdata(&7u64).unwrap();
+    assert_eq!(
+        c.promise_status(p1),
+        PromiseStatus::FulfilledToData(args(expected))
+    );
+    match c.promise_status(p2) {
+        PromiseStatus::Rejected(data) => {
+            let data = VatCapData::new(&data.body, vec![]);
+            assert_eq!(error_message(&data), "cannot take 9 from 7");
+        }
+        other => panic!("expected a rejection, got {:?}", other),
+    }
+}
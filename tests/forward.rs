@@ -0,0 +1,376 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, PromiseStatus,
+    Syscall, VatArgSlot, VatCapData, VatExportID, VatImportID, VatName, VatPromiseID,
+    VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+// Alice (the bootstrap vat) sends foo() to Bob and pipelines bar() on its
+// result. Bob answers foo() with syscall.forward, so bar() (still queued to
+// Bob as the decider of foo's result) must follow it.
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+struct Alice {
+    syscall: Box<dyn Syscall>,
+    log: Log,
+    answer: ForwardTo,
+    names: HashMap<VatPromiseID, &'static str>,
+}
+impl Alice {
+    fn which(&self, id: VatPromiseID) -> &str {
+        self.names.get(&id).unwrap()
+    }
+
+    fn send(&mut self, target: VatSendTarget, method: &'static str) -> VatPromiseID {
+        let p = self.send_with(target, OutboundVatMessage::new(method, b"", vec![]));
+        self.names.insert(p, method);
+        p
+    }
+
+    fn send_with(
+        &mut self,
+        target: VatSendTarget,
+        message: OutboundVatMessage,
+    ) -> VatPromiseID {
+        self.syscall.send(target, message)
+    }
+}
+impl Dispatch for Alice {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        let p_foo = match (message.name.as_str(), self.answer) {
+            ("bootstrap", ForwardTo::Argument) => {
+                let p_make = self.send(VatSendTarget::Import(VatImportID(2)), "make");
+                let args = OutboundVatMessage::new(
+                    "foo",
+                    b"",
+                    vec![VatArgSlot::Promise(p_make)],
+                );
+                let p_foo = self.send_with(VatSendTarget::Import(VatImportID(1)), args);
+                self.names.insert(p_foo, "foo");
+                p_foo
+            }
+            ("bootstrap", _) => self.send(VatSendTarget::Import(VatImportID(1)), "foo"),
+            ("again", _) => {
+                // foo's result, now an alias for make's
+                let p_foo = *self.names.iter().find(|(_, n)| **n == "foo").unwrap().0;
+                self.send(VatSendTarget::Promise(p_foo), "qux");
+                return;
+            }
+            _ => panic!("unexpected message"),
+        };
+        let p_bar = self.send(VatSendTarget::Promise(p_foo), "bar");
+        if let ForwardTo::Argument = self.answer {
+            self.send(VatSendTarget::Promise(p_bar), "baz");
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+
+    fn notify_fulfill_to_target(&mut self, id: VatPromiseID, target: VatResolveTarget) {
+        let entry = format!("{} fulfilled to {:?}", self.which(id), target);
+        self.log.borrow_mut().push(entry);
+    }
+
+    fn notify_fulfill_to_data(&mut self, id: VatPromiseID, data: VatCapData) {
+        let body = String::from_utf8(data.body).unwrap();
+        let entry = format!("{} fulfilled {}", self.which(id), body);
+        self.log.borrow_mut().push(entry);
+    }
+
+    fn notify_reject(&mut self, id: VatPromiseID, data: VatCapData) {
+        let body = String::from_utf8(data.body).unwrap();
+        let entry = format!("{} rejected {}", self.which(id), body);
+        self.log.borrow_mut().push(entry);
+    }
+}
+
+/// how Bob answers foo()
+#[derive(Clone, Copy)]
+enum ForwardTo {
+    /// forward to the result of make(), sent to Carol
+    Carol,
+    /// forward to a promise of his own, already fulfilled to his export 1
+    Export,
+    /// forward to the promise passed in as foo's argument: the result of
+    /// make(), sent to Carol by Alice herself
+    Argument,
+}
+
+struct Bob {
+    syscall: Box<dyn Syscall>,
+    log: Log,
+    answer: ForwardTo,
+}
+impl Dispatch for Bob {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        let result = message.resolver;
+        self.log
+            .borrow_mut()
+            .push(format!("bob got {} on {}", message.name, target.0));
+        if message.name != "foo" {
+            let data = VatCapData {
+                body: b"bob".to_vec(),
+                slots: vec![],
+            };
+            self.syscall.fulfill_to_data(result.unwrap(), data);
+            return;
+        }
+        let p = match self.answer {
+            ForwardTo::Carol => {
+                let t = VatSendTarget::Import(VatImportID(1));
+                self.syscall
+                    .send(t, OutboundVatMessage::new("make", b"", vec![]))
+            }
+            ForwardTo::Export => {
+                let (p, r) = self.syscall.allocate_promise_and_resolver();
+                let t = VatResolveTarget::Export(VatExportID(1));
+                self.syscall.fulfill_to_target(r, t);
+                p
+            }
+            ForwardTo::Argument => match message.args.slots[..] {
+                [VatArgSlot::Promise(p)] => p,
+                _ => panic!("foo takes a promise"),
+            },
+        };
+        self.syscall.forward(result.unwrap(), p);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, message: InboundVatMessage) {
+        panic!("bob got {} through a promise", message.name);
+    }
+
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+struct Carol {
+    syscall: Box<dyn Syscall>,
+    log: Log,
+    r_make: Option<VatResolverID>,
+}
+impl Dispatch for Carol {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        match message.name.as_str() {
+            "make" => self.r_make = message.resolver,
+            "resolve" => {
+                let data = VatCapData {
+                    body: b"made".to_vec(),
+                    slots: vec![],
+                };
+                self.syscall.fulfill_to_data(self.r_make.unwrap(), data);
+            }
+            _ => panic!("unexpected message"),
+        }
+    }
+
+    fn deliver_promise(&mut self, target: VatResolverID, message: InboundVatMessage) {
+        assert_eq!(Some(target), self.r_make);
+        self.log
+            .borrow_mut()
+            .push(format!("carol got {} through make", message.name));
+        let data = VatCapData {
+            body: b"carol".to_vec(),
+            slots: vec![],
+        };
+        self.syscall
+            .fulfill_to_data(message.resolver.unwrap(), data);
+    }
+
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build(answer: ForwardTo, log: &Log) -> Controller {
+    let mut cfg = Config::new();
+    let l = log.clone();
+    let setup = move |syscall| -> Box<dyn Dispatch> {
+        Box::new(Alice {
+            syscall,
+            log: l,
+            answer,
+            names: HashMap::new(),
+        })
+    };
+    cfg.add_vat(&VatName("bootstrap".to_string()), Box::new(setup));
+    let l = log.clone();
+    let setup = move |syscall| -> Box<dyn Dispatch> {
+        Box::new(Bob {
+            syscall,
+            log: l,
+            answer,
+        })
+    };
+    cfg.add_vat(&VatName("bob".to_string()), Box::new(setup));
+    let l = log.clone();
+    let setup = move |syscall| -> Box<dyn Dispatch> {
+        Box::new(Carol {
+            syscall,
+            log: l,
+            r_make: None,
+        })
+    };
+    cfg.add_vat(&VatName("carol".to_string()), Box::new(setup));
+    let mut c = Controller::new(cfg);
+    let alice = VatName("bootstrap".to_string());
+    let bob = VatName("bob".to_string());
+    c.add_import(&alice, 1, &bob, 0);
+    let carol = VatName("carol".to_string());
+    c.add_import(&bob, 1, &carol, 0);
+    c.add_import(&alice, 2, &carol, 0);
+    c
+}
+
+#[test]
+fn test_forward_to_pending() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(ForwardTo::Carol, &log);
+    c.start();
+    let report = c.step().unwrap();
+    let (p_foo, p_bar) = (report.promises_created[0], report.promises_created[1]);
+    let report = c.step().unwrap();
+    let p_make = report.promises_created[0];
+
+    let s = c.dump_state();
+    assert_eq!(s.forwarded, vec![(p_foo, p_make)]);
+    // the spent resolver leaves Bob's clist
+    assert!(s.vat("bob").unwrap().resolvers.is_empty());
+    // bar() now goes to Carol, who decides its result, and Alice watches
+    // make() in place of foo()
+    let carol = VatName("carol".to_string());
+    assert_eq!(s.promise(p_bar).unwrap().decider, Some(carol.clone()));
+    let make = s.promise(p_make).unwrap();
+    assert_eq!(make.decider, Some(carol));
+    let subscribers: Vec<&str> = make.subscribers.iter().map(|v| v.0.as_str()).collect();
+    assert_eq!(subscribers, vec!["bob", "bootstrap"]);
+
+    c.run();
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "bob got foo on 0",
+            "carol got bar through make",
+            "bar fulfilled carol"
+        ]
+    );
+
+    // Alice hears about make() under her name for foo()
+    c.push("carol", 0, "resolve", b"");
+    c.run();
+    assert_eq!(log.borrow().last().unwrap(), "foo fulfilled made");
+    assert!(matches!(
+        c.promise_status(p_foo),
+        PromiseStatus::FulfilledToData(_)
+    ));
+}
+
+#[test]
+fn test_forward_to_fulfilled() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(ForwardTo::Export, &log);
+    c.start();
+    c.run();
+    // bar() is delivered straight to the export, and the notification
+    // names the promise Alice knows
+    let mut log = log.borrow().clone();
+    log.sort();
+    assert_eq!(
+        log,
+        vec![
+            "bar fulfilled bob",
+            "bob got bar on 1",
+            "bob got foo on 0",
+            "foo fulfilled to Import(VatImportID(0))",
+        ]
+    );
+}
+
+// Messages pipelined on the result of a message that follows the forward
+// follow it too (here baz finds bar already answered with data, and is
+// rejected), and Alice's name for foo's result still works, for sending
+// and for notifications, once it is an alias for her name for make's
+#[test]
+fn test_forward_to_known() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(ForwardTo::Argument, &log);
+    c.start();
+    let report = c.step().unwrap();
+    let p_foo = report.promises_created[1];
+    c.run();
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "bob got foo on 0",
+            "carol got bar through make",
+            "baz rejected cannot send to data",
+            "bar fulfilled carol",
+        ]
+    );
+
+    log.borrow_mut().clear();
+    c.push("bootstrap", 0, "again", b"");
+    c.run();
+    assert_eq!(
+        *log.borrow(),
+        vec!["carol got qux through make", "qux fulfilled carol"]
+    );
+
+    // and Alice hears about the resolution under both names
+    log.borrow_mut().clear();
+    c.push("carol", 0, "resolve", b"");
+    c.run();
+    assert_eq!(
+        *log.borrow(),
+        vec!["make fulfilled made", "foo fulfilled made"]
+    );
+    assert!(matches!(
+        c.promise_status(p_foo),
+        PromiseStatus::FulfilledToData(_)
+    ));
+}
+
+// Forwarding to a promise Alice already holds leaves her with two names for
+// one kernel promise, and Bob with neither a resolver nor a stale entry
+#[test]
+fn test_forward_aliases() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(ForwardTo::Argument, &log);
+    c.start();
+    let report = c.step().unwrap();
+    let (p_make, p_foo) = (report.promises_created[0], report.promises_created[1]);
+    // make() goes to Carol, then foo() to Bob, who forwards
+    c.step();
+    c.step();
+    assert_eq!(*log.borrow(), vec!["bob got foo on 0"]);
+
+    let s = c.dump_state();
+    assert_eq!(s.forwarded, vec![(p_foo, p_make)]);
+    let alice = &s.vat("bootstrap").unwrap().promises;
+    let names: Vec<VatPromiseID> = alice
+        .iter()
+        .filter(|(_, kp)| *kp == p_make)
+        .map(|(vp, _)| *vp)
+        .collect();
+    assert_eq!(names.len(), 2);
+    assert!(alice.iter().all(|(_, kp)| *kp != p_foo));
+    let bob = s.vat("bob").unwrap();
+    assert!(bob.resolvers.is_empty());
+    assert!(bob.promises.iter().all(|(_, kp)| *kp != p_foo));
+    assert!(s.promise(p_foo).is_none());
+    assert_eq!(c.check_invariants(), Ok(()));
+}
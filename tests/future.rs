@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use swingset::{
+    Config, Controller, Dispatch, HostCapData, InboundVatMessage, PromiseStatus, Setup,
+    Syscall, VatCapData, VatExportID, VatName, VatPromiseID, VatResolveTarget,
+    VatResolverID,
+};
+
+struct CounterDispatch {
+    syscall: Box<dyn Syscall>,
+    count: u32,
+    held: Option<VatResolverID>,
+}
+impl Dispatch for CounterDispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        println!("Counter.deliver {} .{}", target, message.name);
+        assert_eq!(target, VatExportID(0), "unexpected target");
+        let resolver = message.resolver.unwrap();
+        let data = |body: String| VatCapData {
+            body: body.into_bytes(),
+            slots: vec![],
+        };
+        if message.name == "incr" {
+            self.count += 1;
+            self.syscall
+                .fulfill_to_data(resolver, data(self.count.to_string()));
+        } else if message.name == "hold" {
+            self.held = Some(resolver);
+        } else if message.name == "release" {
+            let held = self.held.take().unwrap();
+            self.syscall
+                .fulfill_to_data(held, data("released".to_string()));
+            self.syscall.fulfill_to_data(resolver, data("".to_string()));
+        } else {
+            panic!("unknown message {}", message.name);
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build() -> Controller {
+    let mut cfg = Config::new();
+    let vn = VatName("counter".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> {
+        Box::new(CounterDispatch {
+            syscall,
+            count: 0,
+            held: None,
+        })
+    };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    Controller::new(cfg)
+}
+
+fn fulfilled(body: &[u8]) -> PromiseStatus {
+    PromiseStatus::FulfilledToData(HostCapData::new(body, vec![]))
+}
+
+#[test]
+fn test_await_promises() {
+    let mut c = build();
+    let counter = c.export_handle("counter", 0);
+    let empty = || HostCapData::new(b"", vec![]);
+    let p1 = c.queue_to_export(counter, "incr", empty());
+    let p2 = c.queue_to_export(counter, "incr", empty());
+    let f1 = c.promise_future(p1);
+    let f2 = c.promise_future(p2);
+    let both = async { (f1.await, f2.await) };
+    let (s1, s2) = c.run_until_complete(both).unwrap();
+    assert_eq!(s1, fulfilled(b"1"));
+    assert_eq!(s2, fulfilled(b"2"));
+}
+
+#[test]
+fn test_await_idle_kernel() {
+    let mut c = build();
+    let counter = c.export_handle("counter", 0);
+    let empty = || HostCapData::new(b"", vec![]);
+    let p1 = c.queue_to_export(counter, "hold", empty());
+    let f1 = c.promise_future(p1);
+    // nothing will ever resolve p1, so the kernel goes idle
+    assert_eq!(c.run_until_complete(f1), None);
+    assert_eq!(c.promise_status(p1), PromiseStatus::Unresolved);
+
+    c.queue_to_export(counter, "release", empty());
+    let f1 = c.promise_future(p1);
+    assert_eq!(c.run_until_complete(f1), Some(fulfilled(b"released")));
+}
+
+#[derive(Default)]
+struct CountingWaker(AtomicU32);
+impl CountingWaker {
+    fn count(&self) -> u32 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn poll_with<F: Future + Unpin>(
+    fut: &mut F,
+    counter: &Arc<CountingWaker>,
+) -> Poll<F::Output> {
+    let waker = Waker::from(counter.clone());
+    Pin::new(fut).poll(&mut Context::from_waker(&waker))
+}
+
+// repeated polls replace a future's waker rather than piling up more, and
+// a dropped future leaves nothing behind to be woken
+#[test]
+fn test_one_waker_per_future() {
+    let mut c = build();
+    let counter = c.export_handle("counter", 0);
+    let empty = || HostCapData::new(b"", vec![]);
+    let p1 = c.queue_to_export(counter, "hold", empty());
+    let mut kept = c.promise_future(p1);
+    let mut dropped = c.promise_future(p1);
+    let first = Arc::new(CountingWaker::default());
+    let second = Arc::new(CountingWaker::default());
+    let third = Arc::new(CountingWaker::default());
+    for _ in 0..3 {
+        assert!(poll_with(&mut kept, &first).is_pending());
+    }
+    assert!(poll_with(&mut kept, &second).is_pending());
+    assert!(poll_with(&mut dropped, &third).is_pending());
+    drop(dropped);
+
+    c.queue_to_export(counter, "release", empty());
+    c.run();
+    assert_eq!((first.count(), second.count(), third.count()), (0, 1, 0));
+    assert_eq!(
+        poll_with(&mut kept, &second),
+        Poll::Ready(fulfilled(b"released"))
+    );
+}
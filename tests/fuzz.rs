@@ -0,0 +1,375 @@
+//! Randomized syscall sequences, under both the FIFO and the chaos
+//! schedulers, checked against the kernel invariants and E-order. This is slow, so it only builds with the "fuzz" feature:
+//!
+//!     cargo test --features fuzz --test fuzz
+//!
+//! Set FUZZ_SEED to replay a single seed, or FUZZ_RUNS to change how many
+//! seeds are tried.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, Rng, Setup,
+    Syscall, VatArgSlot, VatCapData, VatExportID, VatImportID, VatName, VatPromiseID,
+    VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+/// the number of (exported) objects each vat offers
+const EXPORTS: u32 = 3;
+/// the initial imports of vat N's object 0 start here
+const FIRST_IMPORT: u32 = 1000;
+
+fn pick<T: Copy>(rng: &mut Rng, items: &[T]) -> Option<T> {
+    match items.len() {
+        0 => None,
+        n => Some(items[rng.below(n)]),
+    }
+}
+
+/// a message as it arrived: who sent it, through which of the sender's
+/// references, and where it fell in the sender's sequence of sends
+#[derive(Debug)]
+struct Arrival {
+    sender: usize,
+    reference: String,
+    seq: u64,
+}
+
+/// state shared by all the vats of one run
+struct World {
+    rng: Rng,
+    /// syscalls left to make: when this runs out, the vats go quiet
+    budget: u32,
+    arrivals: Vec<Arrival>,
+}
+
+struct FuzzVat {
+    index: usize,
+    syscall: Box<dyn Syscall>,
+    world: Rc<RefCell<World>>,
+    imports: Vec<VatImportID>,
+    promises: Vec<VatPromiseID>,
+    resolvers: Vec<VatResolverID>,
+    /// promises this vat allocated, with their resolvers
+    own: Vec<(VatPromiseID, VatResolverID)>,
+    /// Results of messages sent to imports during this crank. Resolvers are
+    /// only forwarded to these, which are decided by another vat and newer
+    /// than any promise whose resolver is held: that keeps forwarding chains
+    /// from ever looping back on themselves.
+    fresh: Vec<VatPromiseID>,
+    seq: u64,
+}
+
+impl FuzzVat {
+    fn learn(&mut self, slots: &[VatArgSlot]) {
+        for slot in slots {
+            match *slot {
+                VatArgSlot::Import(i) if !self.imports.contains(&i) => {
+                    self.imports.push(i)
+                }
+                VatArgSlot::Promise(p) if !self.promises.contains(&p) => {
+                    self.promises.push(p)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn receive(&mut self, message: InboundVatMessage) {
+        self.learn(&message.args.slots);
+        if let Some(r) = message.resolver {
+            self.resolvers.push(r);
+        }
+        let body = String::from_utf8(message.args.body).unwrap();
+        let fields: Vec<&str> = body.split(':').collect();
+        if let [sender, reference, seq] = fields[..] {
+            self.world.borrow_mut().arrivals.push(Arrival {
+                sender: sender.parse().unwrap(),
+                reference: reference.to_string(),
+                seq: seq.parse().unwrap(),
+            });
+        }
+        self.act();
+    }
+
+    fn random_slots(&mut self, rng: &mut Rng) -> Vec<VatArgSlot> {
+        let mut slots = vec![];
+        for _ in 0..rng.below(3) {
+            let slot = match rng.below(3) {
+                0 => VatArgSlot::Export(VatExportID(rng.below(EXPORTS as usize) as u32)),
+                1 => match pick(rng, &self.imports) {
+                    Some(i) => VatArgSlot::Import(i),
+                    None => continue,
+                },
+                _ => match pick(rng, &self.promises) {
+                    Some(p) => VatArgSlot::Promise(p),
+                    None => continue,
+                },
+            };
+            slots.push(slot);
+        }
+        slots
+    }
+
+    fn random_target(&mut self, rng: &mut Rng) -> Option<(VatSendTarget, String)> {
+        let n = self.imports.len() + self.promises.len();
+        if n == 0 {
+            return None;
+        }
+        let i = rng.below(n);
+        Some(if i < self.imports.len() {
+            let t = self.imports[i];
+            (VatSendTarget::Import(t), format!("i{}", t.0))
+        } else {
+            let t = self.promises[i - self.imports.len()];
+            (VatSendTarget::Promise(t), format!("p{}", t.0))
+        })
+    }
+
+    fn take_resolver(&mut self, rng: &mut Rng) -> Option<VatResolverID> {
+        match self.resolvers.len() {
+            0 => None,
+            n => Some(self.resolvers.swap_remove(rng.below(n))),
+        }
+    }
+
+    /// make a few random (but legal) syscalls
+    fn act(&mut self) {
+        self.fresh.clear();
+        let world = self.world.clone();
+        let mut world = world.borrow_mut();
+        let count = world.rng.below(5);
+        for _ in 0..count {
+            if world.budget == 0 {
+                return;
+            }
+            world.budget -= 1;
+            let rng = &mut world.rng;
+            match rng.below(12) {
+                0..=2 => {
+                    if let Some((target, reference)) = self.random_target(rng) {
+                        let body = format!("{}:{}:{}", self.index, reference, self.seq);
+                        self.seq += 1;
+                        let slots = self.random_slots(rng);
+                        let vmsg =
+                            OutboundVatMessage::new("poke", body.as_bytes(), slots);
+                        if rng.below(4) == 0 {
+                            self.syscall.send_only(target, vmsg);
+                        } else {
+                            let p = self.syscall.send(target, vmsg);
+                            self.promises.push(p);
+                            if let VatSendTarget::Import(_) = target {
+                                self.fresh.push(p);
+                            }
+                        }
+                    }
+                }
+                3 => {
+                    if let Some(p) = pick(rng, &self.promises) {
+                        self.syscall.subscribe(p);
+                    }
+                }
+                4 => {
+                    let (p, r) = self.syscall.allocate_promise_and_resolver();
+                    self.promises.push(p);
+                    self.resolvers.push(r);
+                    self.own.push((p, r));
+                }
+                5 => {
+                    if let Some(r) = self.take_resolver(rng) {
+                        let target = match pick(rng, &self.imports) {
+                            Some(i) if rng.below(2) == 0 => VatResolveTarget::Import(i),
+                            _ => VatResolveTarget::Export(VatExportID(
+                                rng.below(EXPORTS as usize) as u32,
+                            )),
+                        };
+                        self.syscall.fulfill_to_target(r, target);
+                    }
+                }
+                6 | 7 => {
+                    if let Some(r) = self.take_resolver(rng) {
+                        let data = VatCapData {
+                            body: b"data".to_vec(),
+                            slots: self.random_slots(rng),
+                        };
+                        if rng.below(2) == 0 {
+                            self.syscall.fulfill_to_data(r, data);
+                        } else {
+                            self.syscall.reject(r, data);
+                        }
+                    }
+                }
+                8 | 9 => {
+                    if let Some(p) = pick(rng, &self.fresh) {
+                        if let Some(r) = self.take_resolver(rng) {
+                            self.syscall.forward(r, p);
+                        }
+                    }
+                }
+                _ => {
+                    // forward one of our own promises, once it has a
+                    // subscriber and a message queued to it
+                    if let Some(p) = pick(rng, &self.fresh) {
+                        let resolvers = &self.resolvers;
+                        self.own.retain(|(_, r)| resolvers.contains(r));
+                        let (mine, r) = match pick(rng, &self.own) {
+                            Some(own) => own,
+                            None => {
+                                let (mine, r) =
+                                    self.syscall.allocate_promise_and_resolver();
+                                self.promises.push(mine);
+                                (mine, r)
+                            }
+                        };
+                        self.own.retain(|(_, other)| *other != r);
+                        self.resolvers.retain(|other| *other != r);
+                        self.syscall.subscribe(mine);
+                        let body = format!("{}:p{}:{}", self.index, mine.0, self.seq);
+                        self.seq += 1;
+                        let vmsg =
+                            OutboundVatMessage::new("poke", body.as_bytes(), vec![]);
+                        let target = VatSendTarget::Promise(mine);
+                        self.promises.push(self.syscall.send(target, vmsg));
+                        self.syscall.forward(r, p);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Dispatch for FuzzVat {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        self.receive(message);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, message: InboundVatMessage) {
+        self.receive(message);
+    }
+
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, target: VatResolveTarget) {
+        if let VatResolveTarget::Import(i) = target {
+            self.learn(&[VatArgSlot::Import(i)]);
+        }
+        self.act();
+    }
+
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, data: VatCapData) {
+        self.learn(&data.slots);
+        self.act();
+    }
+
+    fn notify_reject(&mut self, _id: VatPromiseID, data: VatCapData) {
+        self.learn(&data.slots);
+        self.act();
+    }
+}
+
+fn vat_name(index: usize) -> String {
+    match index {
+        0 => "bootstrap".to_string(),
+        n => format!("vat{}", n),
+    }
+}
+
+/// Messages sent through the same reference must arrive in the order they
+/// were sent, and no message may arrive twice.
+fn check_e_order(arrivals: &[Arrival]) {
+    let mut last: HashMap<(usize, &str), u64> = HashMap::new();
+    let mut seen: HashSet<(usize, u64)> = HashSet::new();
+    for a in arrivals {
+        assert!(
+            seen.insert((a.sender, a.seq)),
+            "message {} from vat {} arrived twice",
+            a.seq,
+            a.sender
+        );
+        if let Some(previous) = last.insert((a.sender, &a.reference), a.seq) {
+            assert!(
+                previous < a.seq,
+                "vat {} sent {} before {} through {}, but they arrived out of order",
+                a.sender,
+                a.seq,
+                previous,
+                a.reference
+            );
+        }
+    }
+}
+
+fn run(seed: u64) {
+    let mut rng = Rng::new(seed);
+    let vats = 2 + rng.below(4);
+    let budget = 50 + rng.below(150) as u32;
+    let world = Rc::new(RefCell::new(World {
+        rng,
+        budget,
+        arrivals: vec![],
+    }));
+
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    cfg.check_e_order(true);
+    // odd seeds shuffle the run queue as much as the kernel allows
+    if seed % 2 == 1 {
+        cfg.chaos_scheduler(seed);
+    }
+    for index in 0..vats {
+        let world = world.clone();
+        // everyone starts out knowing everyone else's object 0
+        let imports = (0..vats)
+            .filter(|other| *other != index)
+            .map(|other| VatImportID(FIRST_IMPORT + other as u32))
+            .collect();
+        let setup = move |syscall| -> Box<dyn Dispatch> {
+            Box::new(FuzzVat {
+                index,
+                syscall,
+                world,
+                imports,
+                promises: vec![],
+                resolvers: vec![],
+                own: vec![],
+                fresh: vec![],
+                seq: 0,
+            })
+        };
+        let sb: Box<Setup> = Box::new(setup);
+        cfg.add_vat(&VatName(vat_name(index)), sb);
+    }
+    let mut c = Controller::new(cfg);
+    for index in 0..vats {
+        for other in (0..vats).filter(|other| *other != index) {
+            let for_vat = VatName(vat_name(index));
+            let to_vat = VatName(vat_name(other));
+            c.add_import(&for_vat, FIRST_IMPORT + other as u32, &to_vat, 0);
+        }
+    }
+    c.start();
+    for index in 1..vats {
+        c.push(&vat_name(index), 0, "go", b"");
+    }
+    c.run();
+
+    assert_eq!(c.check_invariants(), Ok(()));
+    check_e_order(&world.borrow().arrivals);
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok().map(|v| v.parse().unwrap())
+}
+
+#[test]
+fn fuzz_syscalls() {
+    if let Some(seed) = env_u64("FUZZ_SEED") {
+        run(seed);
+        return;
+    }
+    let runs = env_u64("FUZZ_RUNS").unwrap_or(200);
+    for seed in 0..runs {
+        // the test harness shows this if the run panics
+        println!("seed {}", seed);
+        run(seed);
+    }
+}
@@ -0,0 +1,101 @@
+//! Transcripts of kernel runs, checked against tests/golden/. After a change
+//! which is meant to alter them, review the diff and rewrite them with:
+//!
+//!     SWINGSET_UPDATE_GOLDEN=1 cargo test --test golden
+
+use swingset::{
+    assert_golden, Config, Controller, EventLog, OutboundVatMessage, ScriptedVat,
+    Transcript, VatArgSlot, VatCapData, VatExportID, VatImportID, VatName,
+    VatResolveTarget, VatSendTarget,
+};
+
+fn controller(cfg: Config, imports: &[(&str, u32, &str)]) -> Controller {
+    let mut c = Controller::new(cfg);
+    for (for_vat, id, to_vat) in imports {
+        let for_vat = VatName(for_vat.to_string());
+        c.add_import(&for_vat, *id, &VatName(to_vat.to_string()), 0);
+    }
+    c
+}
+
+// bootstrap sends foo to vat2 and pipelines bar on its result, then vat2
+// resolves both
+#[test]
+fn test_pipeline() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let vmsg = OutboundVatMessage::new("foo", b"body", vec![]);
+            let p_foo = s.send(VatSendTarget::Import(VatImportID(1)), vmsg);
+            let vmsg = OutboundVatMessage::new("bar", b"", vec![]);
+            s.send(VatSendTarget::Promise(p_foo), vmsg);
+        })
+        .on_notify(|_, _| ())
+        .on_notify(|_, _| ())
+        .install(&mut cfg);
+    ScriptedVat::new("vat2", &log)
+        .on_method("foo", |s, d| s.keep_resolver("foo", d.resolver()))
+        .on_method("bar", |s, d| s.keep_resolver("bar", d.resolver()))
+        .on_method("resolve", |s, _| {
+            let r_foo = s.resolver("foo");
+            s.fulfill_to_target(r_foo, VatResolveTarget::Export(VatExportID(12)));
+            let r_bar = s.resolver("bar");
+            s.fulfill_to_data(r_bar, VatCapData::new(b"bar_data", vec![]));
+        })
+        .install(&mut cfg);
+
+    let mut c = controller(cfg, &[("bootstrap", 1, "vat2")]);
+    let mut t = Transcript::new();
+    c.start();
+    t.run(&mut c);
+    t.note("push vat2.resolve");
+    c.push("vat2", 1, "resolve", b"");
+    t.run(&mut c);
+    t.state(&c.dump_state());
+    t.assert_golden("tests/golden/pipeline.txt");
+}
+
+// bootstrap makes a promise, resolves it to data, and then sends it to vat2,
+// which subscribes to it
+#[test]
+fn test_subscribe_resolved() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let (p, r) = s.allocate_promise_and_resolver();
+            let slots = vec![
+                VatArgSlot::Export(VatExportID(10)),
+                VatArgSlot::Import(VatImportID(1)),
+            ];
+            s.fulfill_to_data(r, VatCapData::new(b"p2data", slots));
+            let vmsg = OutboundVatMessage::new("have", b"", vec![VatArgSlot::Promise(p)]);
+            s.send_only(VatSendTarget::Import(VatImportID(1)), vmsg);
+        })
+        .install(&mut cfg);
+    ScriptedVat::new("vat2", &log)
+        .on_method("have", |s, d| {
+            if let [VatArgSlot::Promise(p)] = d.message().unwrap().args.slots[..] {
+                s.subscribe(p);
+            }
+        })
+        .on_notify(|_, _| ())
+        .install(&mut cfg);
+
+    let mut c = controller(cfg, &[("bootstrap", 1, "vat2")]);
+    let mut t = Transcript::new();
+    c.start();
+    t.run(&mut c);
+    t.state(&c.dump_state());
+    t.assert_golden("tests/golden/subscribe_resolved.txt");
+}
+
+#[test]
+#[should_panic(expected = "does not match golden file")]
+fn test_mismatch() {
+    if std::env::var_os(swingset::UPDATE_GOLDEN).is_some() {
+        panic!("does not match golden file (skipped in update mode)");
+    }
+    assert_golden("tests/golden/pipeline.txt", "something else\n");
+}
@@ -0,0 +1,240 @@
+use swingset::{
+    unserialize, Cause, Config, Controller, Delivery, EventLog, HostCapData, Interface,
+    MethodGuard, OutboundVatMessage, PromiseStatus, ScriptedVat, Shape, Value,
+    VatArgSlot, VatCapData, VatImportID, VatName, VatPromiseID, VatSendTarget,
+};
+
+fn bank_interface() -> Interface {
+    Interface::new("Bank")
+        .method("deposit", MethodGuard::call(vec![Shape::BigInt]))
+        .method(
+            "describe",
+            MethodGuard::call(vec![Shape::String, Shape::optional(Shape::Bool)]),
+        )
+        .method("notify", MethodGuard::send_only(vec![Shape::Remotable]))
+}
+
+fn args(body: &str) -> VatCapData {
+    VatCapData::new(body.as_bytes(), vec![])
+}
+
+/// the data a NotifyReject delivery carries
+fn rejection(delivery: &Delivery) -> &VatCapData {
+    match delivery {
+        Delivery::NotifyReject { data, .. } => data,
+        other => panic!("unexpected {}", other),
+    }
+}
+
+/// the message of the TypeError a promise was rejected with
+fn refusal(data: &VatCapData) -> String {
+    match unserialize(data) {
+        Ok(Value::Error { name, message }) => {
+            assert_eq!(name, "TypeError");
+            message
+        }
+        other => panic!("expected an error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_check() {
+    let bank = bank_interface();
+    assert_eq!(bank.check("deposit", &args(r#"#["+5"]"#), true), Ok(()));
+    assert_eq!(bank.check("describe", &args(r#"#["x"]"#), true), Ok(()));
+    assert_eq!(
+        bank.check("describe", &args(r#"#["x",true]"#), false),
+        Ok(())
+    );
+    assert_eq!(
+        bank.check("describe", &args(r#"#["x",null]"#), true),
+        Ok(())
+    );
+    let purse = VatCapData::new(b"#[\"$0\"]", vec![VatArgSlot::Import(VatImportID(2))]);
+    assert_eq!(bank.check("notify", &purse, false), Ok(()));
+
+    let problem = |method: &str, data: &VatCapData, has_result: bool| {
+        bank.check(method, data, has_result).unwrap_err()
+    };
+    assert_eq!(
+        problem("steal", &args("#[]"), true),
+        "Bank has no method \"steal\""
+    );
+    assert_eq!(
+        problem("deposit", &args("#[5]"), true),
+        "Bank.deposit argument 0 must be a bigint, not Number(5.0)"
+    );
+    assert_eq!(
+        problem("deposit", &args("#[]"), true),
+        "Bank.deposit takes 1 arguments, not 0"
+    );
+    assert_eq!(
+        problem("describe", &args(r#"#["x",true,3]"#), true),
+        "Bank.describe takes 1 to 2 arguments, not 3"
+    );
+    assert_eq!(
+        problem("deposit", &args(r#"#{"amount":"+5"}"#), true),
+        "Bank.deposit arguments must be a list, not Record({\"amount\": BigInt(5)})"
+    );
+    assert!(problem("deposit", &args("5"), true)
+        .starts_with("Bank.deposit cannot unserialize"));
+    assert_eq!(
+        problem("notify", &purse, true),
+        "Bank.notify does not return a result"
+    );
+    let promise =
+        VatCapData::new(b"#[\"&0\"]", vec![VatArgSlot::Promise(VatPromiseID(2))]);
+    assert_eq!(
+        problem("notify", &promise, false),
+        "Bank.notify argument 0 must be a remotable, not Slot { slot: Promise(VatPromiseID(2)), iface: None }"
+    );
+}
+
+// the kernel refuses messages to a guarded export, so the vat only sees
+// the good ones
+#[test]
+fn test_guarded_export() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    ScriptedVat::new("bank", &log)
+        .on_method("deposit", |s, d| {
+            s.fulfill_to_data(d.resolver(), args("#\"+5\""))
+        })
+        .on_method("describe", |s, d| {
+            s.fulfill_to_data(d.resolver(), args("#\"x\""))
+        })
+        .install(&mut cfg);
+    let bank = VatName("bank".to_string());
+    cfg.guard_export(&bank, 0, bank_interface());
+    let mut c = Controller::new(cfg);
+    let target = c.export_handle("bank", 0);
+    let mut send = |method: &str, body: &str| {
+        c.queue_to_export(target, method, HostCapData::new(body.as_bytes(), vec![]))
+    };
+    let good = vec![send("deposit", r#"#["+5"]"#), send("describe", r#"#["x"]"#)];
+    let bad = vec![
+        send("deposit", "#[5]"),
+        send("withdraw", r#"#["+5"]"#),
+        send("notify", "#[]"),
+        send("describe", "not smallcaps"),
+    ];
+    c.run();
+    assert_eq!(
+        log.take(),
+        vec!["bank: deliver deposit", "bank: deliver describe"]
+    );
+    for p in good {
+        assert!(matches!(
+            c.promise_status(p),
+            PromiseStatus::FulfilledToData(_)
+        ));
+    }
+    let refusals: Vec<String> = bad
+        .into_iter()
+        .map(|p| match c.promise_status(p) {
+            PromiseStatus::Rejected(data) => {
+                refusal(&VatCapData::new(&data.body, vec![]))
+            }
+            other => panic!("expected a rejection, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        refusals,
+        vec![
+            "Bank.deposit argument 0 must be a bigint, not Number(5.0)",
+            "Bank has no method \"withdraw\"",
+            "Bank.notify does not return a result",
+            "Bank.describe cannot unserialize arguments: marshal: body is not smallcaps (no leading '#')",
+        ]
+    );
+}
+
+// bodies that are not valid smallcaps, however hostile, are refused with a
+// TypeError instead of taking the kernel down
+#[test]
+fn test_hostile_bodies() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    ScriptedVat::new("bank", &log).install(&mut cfg);
+    let bank = VatName("bank".to_string());
+    cfg.guard_export(&bank, 0, bank_interface());
+    let mut c = Controller::new(cfg);
+    let target = c.export_handle("bank", 0);
+    let deep = format!("#{}", "[".repeat(1_000_000));
+    let bodies = [r#"#["\ud800\u0041"]"#, r#"#["\u+041"]"#, deep.as_str()];
+    let sent: Vec<_> = bodies
+        .iter()
+        .map(|body| {
+            let args = HostCapData::new(body.as_bytes(), vec![]);
+            c.queue_to_export(target, "deposit", args)
+        })
+        .collect();
+    c.run();
+    assert!(log.take().is_empty());
+    for p in sent {
+        match c.promise_status(p) {
+            PromiseStatus::Rejected(data) => {
+                let problem = refusal(&VatCapData::new(&data.body, vec![]));
+                assert!(
+                    problem.starts_with(
+                        "Bank.deposit cannot unserialize arguments: marshal: "
+                    ),
+                    "{}",
+                    problem
+                );
+            }
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    }
+}
+
+// messages pipelined on a refused message's result are rejected too, and a
+// refused send_only is simply dropped
+#[test]
+fn test_refused_between_vats() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let bank = VatSendTarget::Import(VatImportID(1));
+            let bad = OutboundVatMessage::new("deposit", b"#[\"five\"]", vec![]);
+            let p1 = s.send(bank, bad);
+            let pipelined = OutboundVatMessage::new("deposit", b"#[\"+5\"]", vec![]);
+            let p2 = s.send(VatSendTarget::Promise(p1), pipelined);
+            s.subscribe(p2);
+            let notify = OutboundVatMessage::new("notify", b"#[\"purse\"]", vec![]);
+            s.send_only(bank, notify);
+        })
+        .on_notify(|s, d| s.log(&refusal(rejection(d))))
+        .on_notify(|s, d| s.log(&refusal(rejection(d))))
+        .install(&mut cfg);
+    ScriptedVat::new("bank", &log).install(&mut cfg);
+    cfg.guard_export(&VatName("bank".to_string()), 0, bank_interface());
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    c.add_import(&bootstrap, 1, &VatName("bank".to_string()), 0);
+    c.start();
+    c.step().unwrap();
+    // the refusal, not a syscall, queued both notifications
+    let refused = c.step().unwrap();
+    let cause = Cause::Refused {
+        message: refused.delivery.id,
+        crank: refused.crank,
+    };
+    let causes: Vec<Cause> = refused.queued.iter().map(|q| q.cause).collect();
+    assert_eq!(causes, vec![cause, cause]);
+    c.run();
+    assert_eq!(
+        log.take(),
+        vec![
+            "bootstrap: deliver bootstrap",
+            "bootstrap: notify_reject p0",
+            "bootstrap: Bank.deposit argument 0 must be a bigint, not String(\"five\")",
+            "bootstrap: notify_reject p1",
+            "bootstrap: Bank.deposit argument 0 must be a bigint, not String(\"five\")",
+        ]
+    );
+}
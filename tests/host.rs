@@ -0,0 +1,105 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, HostArgSlot, HostCapData, InboundVatMessage,
+    PromiseStatus, Setup, Syscall, VatArgSlot, VatCapData, VatExportID, VatName,
+    VatPromiseID, VatResolveTarget, VatResolverID,
+};
+
+struct BankDispatch {
+    syscall: Box<dyn Syscall>,
+    log: Rc<RefCell<Vec<u32>>>,
+}
+impl Dispatch for BankDispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        println!("Bank.deliver {} .{}", target, message.name);
+        assert_eq!(target, VatExportID(0), "unexpected target");
+        let resolver = message.resolver.unwrap();
+        if message.name == "deposit" {
+            assert_eq!(message.args.body, b"10");
+            assert_eq!(message.args.slots, vec![VatArgSlot::Export(VatExportID(4))]);
+            let data = VatCapData {
+                body: b"ok".to_vec(),
+                slots: vec![VatArgSlot::Export(VatExportID(4))],
+            };
+            self.syscall.fulfill_to_data(resolver, data);
+            self.log.borrow_mut().push(1);
+        } else if message.name == "get_purse" {
+            let purse = VatResolveTarget::Export(VatExportID(3));
+            self.syscall.fulfill_to_target(resolver, purse);
+            self.log.borrow_mut().push(2);
+        } else if message.name == "fail" {
+            match message.args.slots[0] {
+                VatArgSlot::Promise(_) => (),
+                _ => panic!("args.slots[0] was not a Promise"),
+            };
+            let data = VatCapData {
+                body: b"oops".to_vec(),
+                slots: vec![],
+            };
+            self.syscall.reject(resolver, data);
+            self.log.borrow_mut().push(3);
+        } else {
+            panic!("unknown message {}", message.name);
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+#[test]
+fn test_queue_to_export() {
+    let mut cfg = Config::new();
+    let log: Vec<u32> = vec![];
+    let r = Rc::new(RefCell::new(log));
+    let r2 = r.clone();
+    let vn = VatName("bank".to_string());
+    let setup =
+        |syscall| -> Box<dyn Dispatch> { Box::new(BankDispatch { syscall, log: r2 }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let mut c = Controller::new(cfg);
+
+    let bank = c.export_handle("bank", 0);
+    let purse = c.export_handle("bank", 3);
+    let note = c.export_handle("bank", 4);
+    let args = HostCapData::new(b"10", vec![HostArgSlot::Export(note)]);
+    let p1 = c.queue_to_export(bank, "deposit", args);
+    let p2 = c.queue_to_export(bank, "get_purse", HostCapData::new(b"", vec![]));
+    assert_eq!(c.promise_status(p1), PromiseStatus::Unresolved);
+    assert_eq!(c.promise_status(p2), PromiseStatus::Unresolved);
+
+    c.step();
+    assert_eq!(*r.borrow(), vec![1]);
+    let expected = HostCapData::new(b"ok", vec![HostArgSlot::Export(note)]);
+    assert_eq!(
+        c.promise_status(p1),
+        PromiseStatus::FulfilledToData(expected)
+    );
+    assert_eq!(c.promise_status(p2), PromiseStatus::Unresolved);
+
+    c.run();
+    assert_eq!(*r.borrow(), vec![1, 2]);
+    assert_eq!(
+        c.promise_status(p2),
+        PromiseStatus::FulfilledToTarget(purse)
+    );
+
+    let args = HostCapData::new(b"", vec![HostArgSlot::Promise(p1)]);
+    let p3 = c.queue_to_export(bank, "fail", args);
+    c.run();
+    assert_eq!(*r.borrow(), vec![1, 2, 3]);
+    let expected = HostCapData::new(b"oops", vec![]);
+    assert_eq!(c.promise_status(p3), PromiseStatus::Rejected(expected));
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, Setup, Syscall,
+    VatCapData, VatExportID, VatImportID, VatName, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
+};
+
+// The bootstrap (left) vat sends foo() to the right vat, and pipelines bar() on the
+// result. The right vat answers foo() by forwarding its result to a promise
+// of its own, which is either still pending or already fulfilled to data.
+
+struct LeftDispatch {
+    syscall: Box<dyn Syscall>,
+    log: Rc<RefCell<Vec<String>>>,
+    p_foo: Option<VatPromiseID>,
+    p_bar: Option<VatPromiseID>,
+}
+impl Dispatch for LeftDispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "bootstrap");
+        let t = VatSendTarget::Import(VatImportID(1));
+        let p_foo = self
+            .syscall
+            .send(t, OutboundVatMessage::new("foo", b"", vec![]));
+        let t = VatSendTarget::Promise(p_foo);
+        let p_bar = self
+            .syscall
+            .send(t, OutboundVatMessage::new("bar", b"", vec![]));
+        self.p_foo = Some(p_foo);
+        self.p_bar = Some(p_bar);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+
+    fn notify_fulfill_to_data(&mut self, id: VatPromiseID, data: VatCapData) {
+        let which = if Some(id) == self.p_foo { "foo" } else { "bar" };
+        let body = String::from_utf8(data.body).unwrap();
+        self.log
+            .borrow_mut()
+            .push(format!("{} fulfilled {}", which, body));
+    }
+
+    fn notify_reject(&mut self, id: VatPromiseID, data: VatCapData) {
+        let which = if Some(id) == self.p_foo { "foo" } else { "bar" };
+        let body = String::from_utf8(data.body).unwrap();
+        self.log
+            .borrow_mut()
+            .push(format!("{} rejected {}", which, body));
+    }
+}
+
+struct RightDispatch {
+    syscall: Box<dyn Syscall>,
+    log: Rc<RefCell<Vec<String>>>,
+    resolve_first: bool,
+    r_new: Option<VatResolverID>,
+}
+impl Dispatch for RightDispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        match message.name.as_str() {
+            "foo" => {
+                let (p, r) = self.syscall.allocate_promise_and_resolver();
+                if self.resolve_first {
+                    let data = VatCapData {
+                        body: b"early".to_vec(),
+                        slots: vec![],
+                    };
+                    self.syscall.fulfill_to_data(r, data);
+                }
+                self.syscall.forward(message.resolver.unwrap(), p);
+                self.r_new = Some(r);
+            }
+            "resolve" => {
+                let data = VatCapData {
+                    body: b"late".to_vec(),
+                    slots: vec![],
+                };
+                self.syscall.fulfill_to_data(self.r_new.unwrap(), data);
+            }
+            _ => panic!("unexpected message"),
+        }
+    }
+
+    fn deliver_promise(&mut self, target: VatResolverID, message: InboundVatMessage) {
+        // pipelined messages follow the forwarded promise
+        assert_eq!(Some(target), self.r_new);
+        self.log.borrow_mut().push(format!("got {}", message.name));
+        let data = VatCapData {
+            body: b"bar".to_vec(),
+            slots: vec![],
+        };
+        self.syscall
+            .fulfill_to_data(message.resolver.unwrap(), data);
+    }
+
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build(resolve_first: bool, log: &Rc<RefCell<Vec<String>>>) -> Controller {
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    let l = log.clone();
+    let setup = |syscall| -> Box<dyn Dispatch> {
+        Box::new(LeftDispatch {
+            syscall,
+            log: l,
+            p_foo: None,
+            p_bar: None,
+        })
+    };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&VatName("bootstrap".to_string()), sb);
+    let l = log.clone();
+    let setup = move |syscall| -> Box<dyn Dispatch> {
+        Box::new(RightDispatch {
+            syscall,
+            log: l,
+            resolve_first,
+            r_new: None,
+        })
+    };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&VatName("right".to_string()), sb);
+    let mut c = Controller::new(cfg);
+    let left = VatName("bootstrap".to_string());
+    let right = VatName("right".to_string());
+    c.add_import(&left, 1, &right, 0);
+    c
+}
+
+#[test]
+fn test_forward_to_pending() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(false, &log);
+    c.start();
+    c.run();
+    assert_eq!(c.check_invariants(), Ok(()));
+    assert_eq!(*log.borrow(), vec!["got bar", "bar fulfilled bar"]);
+
+    c.push("right", 1, "resolve", b"");
+    c.run();
+    assert_eq!(c.check_invariants(), Ok(()));
+    assert_eq!(
+        *log.borrow(),
+        vec!["got bar", "bar fulfilled bar", "foo fulfilled late"]
+    );
+}
+
+#[test]
+fn test_forward_to_resolved() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(true, &log);
+    c.start();
+    c.run();
+    assert_eq!(c.check_invariants(), Ok(()));
+    let mut log = log.borrow().clone();
+    // both notifications are queued by the same forward syscall
+    log.sort();
+    assert_eq!(
+        log,
+        vec!["bar rejected cannot send to data", "foo fulfilled early"]
+    );
+}
@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Args, Config, Controller, Dispatch, InboundVatMessage, LiveSlots, MockSyscall,
+    Presence, Ref, RemotableObject, Resolution, VatExportID, VatImportID, VatName,
+    VatResolverID,
+};
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+fn body(args: &Args) -> String {
+    String::from_utf8(args.body.clone()).unwrap()
+}
+
+// the "counter" vat: its root makes counters, which count, and tell
+// whoever is watching
+
+struct Maker;
+impl RemotableObject for Maker {
+    fn deliver(
+        &self,
+        vat: &mut LiveSlots,
+        method: &str,
+        _args: Args,
+        result: Option<VatResolverID>,
+    ) {
+        let result = result.unwrap();
+        match method {
+            "make" => {
+                let counter: Rc<dyn RemotableObject> = Rc::new(Counter {
+                    count: RefCell::new(0),
+                    watchers: RefCell::new(vec![]),
+                });
+                vat.resolve(result, Ref::Local(counter));
+            }
+            "make_nothing" => vat.resolve_to_data(result, Args::new(b"nothing", vec![])),
+            _ => panic!("Maker has no method {}", method),
+        }
+    }
+}
+
+struct Counter {
+    count: RefCell<u32>,
+    watchers: RefCell<Vec<Ref>>,
+}
+impl RemotableObject for Counter {
+    fn deliver(
+        &self,
+        vat: &mut LiveSlots,
+        method: &str,
+        args: Args,
+        result: Option<VatResolverID>,
+    ) {
+        match method {
+            "incr" => {
+                *self.count.borrow_mut() += 1;
+                let count = self.count.borrow().to_string();
+                for watcher in self.watchers.borrow().iter() {
+                    vat.send_only(watcher, "count", Args::new(count.as_bytes(), vec![]));
+                }
+                vat.resolve_to_data(result.unwrap(), Args::new(count.as_bytes(), vec![]));
+            }
+            "watch" => self.watchers.borrow_mut().push(args.refs[0].clone()),
+            _ => panic!("Counter has no method {}", method),
+        }
+    }
+}
+
+// the bootstrap vat: makes a counter, pipelines an increment on it, then
+// watches it and increments it again
+
+struct Watcher {
+    log: Log,
+}
+impl RemotableObject for Watcher {
+    fn deliver(
+        &self,
+        _vat: &mut LiveSlots,
+        method: &str,
+        args: Args,
+        _: Option<VatResolverID>,
+    ) {
+        self.log
+            .borrow_mut()
+            .push(format!("watcher {} {}", method, body(&args)));
+    }
+}
+
+struct Client {
+    log: Log,
+}
+impl RemotableObject for Client {
+    fn deliver(
+        &self,
+        vat: &mut LiveSlots,
+        method: &str,
+        _args: Args,
+        _: Option<VatResolverID>,
+    ) {
+        assert_eq!(method, "bootstrap");
+        let maker = Ref::Remote(Presence(VatImportID(1)));
+        let log = self.log.clone();
+        let record = move |what: &'static str| {
+            let log = log.clone();
+            move |_: &mut LiveSlots, resolution: Resolution| {
+                let entry = match resolution {
+                    Resolution::Fulfilled(r) => format!("{} fulfilled {:?}", what, r),
+                    Resolution::Data(args) => format!("{} -> {}", what, body(&args)),
+                    Resolution::Rejected(args) => {
+                        format!("{} rejected: {}", what, body(&args))
+                    }
+                };
+                log.borrow_mut().push(entry);
+            }
+        };
+
+        let p_counter = vat.send(&maker, "make", Args::default());
+        let p_first = vat.send(&Ref::Promise(p_counter), "incr", Args::default());
+        vat.when(p_first, record("first incr"));
+
+        let p_nothing = vat.send(&maker, "make_nothing", Args::default());
+        let p_broken = vat.send(&Ref::Promise(p_nothing), "incr", Args::default());
+        vat.when(p_broken, record("broken incr"));
+
+        let watcher: Rc<dyn RemotableObject> = Rc::new(Watcher {
+            log: self.log.clone(),
+        });
+        vat.when(p_counter, move |vat, resolution| {
+            let counter = match resolution {
+                Resolution::Fulfilled(counter) => counter,
+                other => panic!("counter was {:?}", other),
+            };
+            vat.send_only(&counter, "watch", Args::new(b"", vec![Ref::Local(watcher)]));
+            let p_second = vat.send(&counter, "incr", Args::default());
+            vat.when(p_second, record("second incr"));
+        });
+    }
+}
+
+#[test]
+fn test_liveslots() {
+    let log: Log = Rc::new(RefCell::new(vec![]));
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    let client_log = log.clone();
+    cfg.add_vat(
+        &VatName("bootstrap".to_string()),
+        LiveSlots::setup(move || Rc::new(Client { log: client_log })),
+    );
+    cfg.add_vat(
+        &VatName("counter".to_string()),
+        LiveSlots::setup(|| Rc::new(Maker)),
+    );
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    c.add_import(&bootstrap, 1, &VatName("counter".to_string()), 0);
+    c.start();
+    c.run();
+    let mut log = log.borrow().clone();
+    // the rejection races the counter; the rest is in order
+    let broken = log.iter().position(|e| e.starts_with("broken")).unwrap();
+    assert_eq!(
+        log.remove(broken),
+        "broken incr rejected: cannot send to data"
+    );
+    assert_eq!(
+        log,
+        vec!["first incr -> 1", "watcher count 2", "second incr -> 2",]
+    );
+}
+
+// answers every message at once, with its own name
+struct Echo;
+impl RemotableObject for Echo {
+    fn deliver(
+        &self,
+        vat: &mut LiveSlots,
+        method: &str,
+        _args: Args,
+        result: Option<VatResolverID>,
+    ) {
+        vat.resolve_to_data(result.unwrap(), Args::new(method.as_bytes(), vec![]));
+    }
+}
+
+#[test]
+#[should_panic(expected = "no unresolved promise for resolver")]
+fn test_deliver_to_resolved_promise() {
+    let mut vat = LiveSlots::new(Box::new(MockSyscall::new()), Rc::new(Echo));
+    let r = VatResolverID(3);
+    let call = || InboundVatMessage::call("echo", b"");
+    vat.deliver(VatExportID(0), call().with_resolver(r));
+    // the kernel sends nothing on to a promise once it is resolved
+    vat.deliver_promise(r, call());
+}
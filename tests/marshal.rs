@@ -0,0 +1,115 @@
+use swingset::{
+    serialize, unserialize, Value, VatArgSlot, VatCapData, VatExportID, VatImportID,
+    VatPromiseID,
+};
+
+fn body(data: &VatCapData) -> &str {
+    std::str::from_utf8(&data.body).unwrap()
+}
+
+/// check that `value` serializes to `expected` (and the slots), and back
+fn check(value: Value, expected: &str, slots: Vec<VatArgSlot>) {
+    let data = serialize(&value);
+    assert_eq!(body(&data), expected);
+    assert_eq!(data.slots, slots);
+    assert_eq!(unserialize(&data), Ok(value));
+}
+
+#[test]
+fn test_scalars() {
+    check(Value::Null, "#null", vec![]);
+    check(Value::Undefined, "#\"#undefined\"", vec![]);
+    check(true.into(), "#true", vec![]);
+    check(42.into(), "#42", vec![]);
+    check((-1.5).into(), "#-1.5", vec![]);
+    check(Value::Number(f64::INFINITY), "#\"#Infinity\"", vec![]);
+    check(Value::Number(f64::NEG_INFINITY), "#\"#-Infinity\"", vec![]);
+    check(Value::BigInt(7), "#\"+7\"", vec![]);
+    check(Value::BigInt(-7), "#\"-7\"", vec![]);
+    check("hello".into(), "#\"hello\"", vec![]);
+    // strings which look like something else are escaped with "!"
+    check("#undefined".into(), "#\"!#undefined\"", vec![]);
+    check("+7".into(), "#\"!+7\"", vec![]);
+    check("!".into(), "#\"!!\"", vec![]);
+    check("".into(), "#\"\"", vec![]);
+
+    let nan = unserialize(&serialize(&Value::Number(f64::NAN))).unwrap();
+    assert!(matches!(nan, Value::Number(n) if n.is_nan()));
+}
+
+#[test]
+fn test_structures() {
+    let value = Value::record(vec![
+        ("b", Value::from(vec![1, 2])),
+        ("a", "x".into()),
+        ("$weird", Value::Null),
+    ]);
+    check(value, r##"#{"!$weird":null,"a":"x","b":[1,2]}"##, vec![]);
+    check(
+        Value::error("TypeError", "oops"),
+        r##"#{"#error":"oops","name":"TypeError"}"##,
+        vec![],
+    );
+    check(
+        Value::Tagged("copySet".to_string(), Box::new(vec!["a", "b"].into())),
+        r##"#{"#tag":"copySet","payload":["a","b"]}"##,
+        vec![],
+    );
+    // a record with a "#tag" key is not a tagged value
+    check(
+        Value::record(vec![("#tag", Value::Null)]),
+        r##"#{"!#tag":null}"##,
+        vec![],
+    );
+}
+
+#[test]
+fn test_slots() {
+    let purse = VatArgSlot::Import(VatImportID(5));
+    let mine = VatArgSlot::Export(VatExportID(2));
+    let p = VatArgSlot::Promise(VatPromiseID(1));
+    let alleged = Value::Slot {
+        slot: purse,
+        iface: Some("Alleged: purse".to_string()),
+    };
+    let value = Value::from(vec![
+        alleged.clone(),
+        Value::slot(p),
+        Value::slot(mine),
+        Value::slot(purse),
+    ]);
+    let data = serialize(&value);
+    assert_eq!(body(&data), r#"#["$0.Alleged: purse","&1","$2","$0"]"#);
+    assert_eq!(data.slots, vec![purse, p, mine]);
+    let expected = Value::from(vec![
+        alleged,
+        Value::slot(p),
+        Value::slot(mine),
+        Value::slot(purse),
+    ]);
+    assert_eq!(unserialize(&data), Ok(expected));
+}
+
+#[test]
+fn test_errors() {
+    let bad = |body: &str, slots: Vec<VatArgSlot>| {
+        let result = unserialize(&VatCapData::new(body.as_bytes(), slots));
+        assert!(result.is_err(), "{} should not unserialize", body);
+    };
+    bad("null", vec![]);
+    bad("#[1,", vec![]);
+    bad(r#"#"$0""#, vec![]);
+    bad(r#"#"$+0""#, vec![VatArgSlot::Export(VatExportID(0))]);
+    bad(r#"#"&0""#, vec![VatArgSlot::Export(VatExportID(0))]);
+    bad(r#"#"$0""#, vec![VatArgSlot::Promise(VatPromiseID(0))]);
+    bad(r##"#"#what""##, vec![]);
+    bad(r#"#"+""#, vec![]);
+    bad(r#"#"++1""#, vec![]);
+    bad(r#"#"%sym""#, vec![]);
+    bad(r#"#"(reserved""#, vec![]);
+    bad(r##"#{"#tag":"x"}"##, vec![]);
+    // hostile JSON is an error too, not a panic
+    bad(r#"#"\ud800\u0041""#, vec![]);
+    bad(r#"#"\u+041""#, vec![]);
+    bad(&format!("#{}", "[".repeat(1_000_000)), vec![]);
+}
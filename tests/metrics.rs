@@ -0,0 +1,148 @@
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, Setup, Syscall,
+    VatArgSlot, VatCapData, VatExportID, VatImportID, VatName, VatPromiseID,
+    VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "bootstrap");
+        let t = VatSendTarget::Import(VatImportID(1));
+        let arg1 = VatArgSlot::Export(VatExportID(22));
+        let vmsg = OutboundVatMessage::new("foo", b"body", vec![arg1]);
+        self.syscall.send(t, vmsg);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, data: VatCapData) {
+        assert_eq!(data.body, b"result");
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+struct Vat2Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat2Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "foo");
+        let data = VatCapData {
+            body: b"result".to_vec(),
+            slots: vec![],
+        };
+        self.syscall
+            .fulfill_to_data(message.resolver.unwrap(), data);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build() -> Controller {
+    let mut cfg = Config::new();
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let vn2 = VatName("vat2".to_string());
+    let setup2 = |syscall| -> Box<dyn Dispatch> { Box::new(Vat2Dispatch { syscall }) };
+    let sb2: Box<Setup> = Box::new(setup2);
+    cfg.add_vat(&vn2, sb2);
+    let mut c = Controller::new(cfg);
+    c.add_import(&vn, 1, &vn2, 0);
+    c
+}
+
+fn vat(name: &str) -> VatName {
+    VatName(name.to_string())
+}
+
+#[test]
+fn test_metrics() {
+    let mut c = build();
+    let m = c.metrics();
+    assert_eq!(m.cranks, 0);
+    assert_eq!(m.run_queue_length, 0);
+    assert_eq!(m.crank_duration.count, 0);
+    assert_eq!(m.mean_crank_duration(), None);
+
+    c.start();
+    c.step();
+    let m = c.metrics();
+    assert_eq!(m.cranks, 1);
+    assert_eq!(m.run_queue_length, 1);
+    assert_eq!(m.unresolved_promises, 1);
+
+    c.run();
+    let m = c.metrics();
+    assert_eq!(m.cranks, 3);
+    assert_eq!(m.run_queue_length, 0);
+    assert_eq!(m.unresolved_promises, 0);
+    let deliveries: Vec<((VatName, &str), u64)> =
+        m.deliveries.clone().into_iter().collect();
+    assert_eq!(
+        deliveries,
+        vec![
+            ((vat("bootstrap"), "deliver"), 1),
+            ((vat("bootstrap"), "notify_fulfill_to_data"), 1),
+            ((vat("vat2"), "deliver"), 1),
+        ]
+    );
+    let syscalls: Vec<((VatName, &str), u64)> = m.syscalls.clone().into_iter().collect();
+    assert_eq!(
+        syscalls,
+        vec![
+            ((vat("bootstrap"), "send"), 1),
+            ((vat("vat2"), "fulfill_to_data"), 1),
+        ]
+    );
+    assert_eq!(m.clist_entries[&(vat("bootstrap"), "import")], 1);
+    assert_eq!(m.clist_entries[&(vat("bootstrap"), "promise")], 1);
+    assert_eq!(m.clist_entries[&(vat("vat2"), "import")], 1);
+    // the resolver is spent once vat2 fulfills the promise
+    assert_eq!(m.clist_entries[&(vat("vat2"), "resolver")], 0);
+    assert_eq!(m.crank_duration.count, 3);
+    assert!(m.mean_crank_duration().is_some());
+
+    let text = m.to_prometheus();
+    let lines: Vec<&str> = text.lines().collect();
+    for line in &[
+        "# TYPE swingset_cranks_total counter",
+        "swingset_cranks_total 3",
+        "swingset_deliveries_total{vat=\"bootstrap\",kind=\"deliver\"} 1",
+        "swingset_syscalls_total{vat=\"vat2\",kind=\"fulfill_to_data\"} 1",
+        "swingset_run_queue_length 0",
+        "swingset_unresolved_promises 0",
+        "swingset_clist_entries{vat=\"vat2\",table=\"resolver\"} 0",
+        "# TYPE swingset_crank_duration_seconds histogram",
+        "swingset_crank_duration_seconds_bucket{le=\"+Inf\"} 3",
+        "swingset_crank_duration_seconds_count 3",
+    ] {
+        assert!(lines.contains(line), "missing {}\n{}", line, text);
+    }
+
+    let path = std::env::temp_dir().join(format!("swingset-{}.prom", std::process::id()));
+    m.write_prometheus(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    std::fs::remove_file(&path).unwrap();
+}
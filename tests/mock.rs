@@ -0,0 +1,150 @@
+use swingset::{
+    Dispatch, InboundVatMessage, MockSyscall, OutboundVatMessage, RecordedSyscall,
+    Syscall, VatArgSlot, VatCapData, VatDeviceID, VatExportID, VatImportID, VatPromiseID,
+    VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+// A vat which answers ping() with "pong", relays relay(target) to target as
+// a ping() whose answer becomes its own, and answers read() by asking its
+// device.
+
+struct RelayDispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for RelayDispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        let resolver = message.resolver.unwrap();
+        match message.name.as_str() {
+            "ping" => {
+                let data = VatCapData::new(b"pong", vec![]);
+                self.syscall.fulfill_to_data(resolver, data);
+            }
+            "relay" => {
+                let target = match message.args.slots[0] {
+                    VatArgSlot::Import(id) => VatSendTarget::Import(id),
+                    VatArgSlot::Promise(id) => VatSendTarget::Promise(id),
+                    VatArgSlot::Export(_) => panic!("cannot relay to myself"),
+                };
+                let msg = OutboundVatMessage::new("ping", b"", vec![]);
+                let answer = self.syscall.send(target, msg);
+                self.syscall.forward(resolver, answer);
+            }
+            "read" => {
+                let msg = OutboundVatMessage::new("get", b"", vec![]);
+                let data = self.syscall.invoke(VatDeviceID(0), msg);
+                self.syscall.fulfill_to_data(resolver, data);
+            }
+            _ => self
+                .syscall
+                .reject(resolver, VatCapData::new(b"unknown method", vec![])),
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+}
+
+fn relay() -> (MockSyscall, RelayDispatch) {
+    let mock = MockSyscall::new();
+    let vat = RelayDispatch {
+        syscall: Box::new(mock.clone()),
+    };
+    (mock, vat)
+}
+
+#[test]
+fn test_ping() {
+    let (mock, mut vat) = relay();
+    let r = VatResolverID(7);
+    vat.deliver(
+        VatExportID(0),
+        InboundVatMessage::call("ping", b"").with_resolver(r),
+    );
+    mock.assert_calls(vec![RecordedSyscall::FulfillToData {
+        resolver: r,
+        data: VatCapData::new(b"pong", vec![]),
+    }]);
+
+    vat.deliver(
+        VatExportID(0),
+        InboundVatMessage::call("pong", b"").with_resolver(r),
+    );
+    mock.assert_calls(vec![RecordedSyscall::Reject {
+        resolver: r,
+        data: VatCapData::new(b"unknown method", vec![]),
+    }]);
+    mock.assert_no_calls();
+}
+
+#[test]
+fn test_relay() {
+    let (mock, mut vat) = relay();
+    mock.first_ids(10, 0);
+    let target = VatImportID(3);
+    let relay = InboundVatMessage::call("relay", b"")
+        .with_slots(vec![VatArgSlot::Import(target)])
+        .with_resolver(VatResolverID(1));
+    vat.deliver(VatExportID(0), relay);
+    mock.assert_calls(vec![
+        RecordedSyscall::Send {
+            target: VatSendTarget::Import(target),
+            method: "ping".to_string(),
+            args: VatCapData::new(b"", vec![]),
+            result: VatPromiseID(10),
+        },
+        RecordedSyscall::Forward {
+            resolver: VatResolverID(1),
+            target: VatPromiseID(10),
+        },
+    ]);
+}
+
+#[test]
+fn test_ids() {
+    let mut mock = MockSyscall::new();
+    let target = VatSendTarget::Import(VatImportID(1));
+    let msg = || OutboundVatMessage::new("foo", b"", vec![]);
+    assert_eq!(mock.send(target, msg()), VatPromiseID(0));
+    mock.send_only(target, msg());
+    assert_eq!(
+        mock.allocate_promise_and_resolver(),
+        (VatPromiseID(1), VatResolverID(0))
+    );
+    assert_eq!(mock.send(target, msg()), VatPromiseID(2));
+    // clones share the record
+    assert_eq!(mock.clone().take().len(), 4);
+    assert_eq!(mock.calls(), vec![]);
+}
+
+#[test]
+fn test_invoke() {
+    let (mock, mut vat) = relay();
+    mock.script_invoke(VatCapData::new(b"42", vec![]));
+    let read = InboundVatMessage::call("read", b"").with_resolver(VatResolverID(0));
+    vat.deliver(VatExportID(0), read);
+    mock.assert_calls(vec![
+        RecordedSyscall::Invoke {
+            target: VatDeviceID(0),
+            method: "get".to_string(),
+            args: VatCapData::new(b"", vec![]),
+            result: VatCapData::new(b"42", vec![]),
+        },
+        RecordedSyscall::FulfillToData {
+            resolver: VatResolverID(0),
+            data: VatCapData::new(b"42", vec![]),
+        },
+    ]);
+}
+
+#[test]
+#[should_panic(expected = "no result scripted")]
+fn test_unscripted_invoke() {
+    let (_mock, mut vat) = relay();
+    let read = InboundVatMessage::call("read", b"").with_resolver(VatResolverID(0));
+    vat.deliver(VatExportID(0), read);
+}
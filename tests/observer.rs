@@ -0,0 +1,173 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, KernelEvent, KernelObserver,
+    OutboundVatMessage, PromiseStatus, Setup, StderrLogger, Syscall, SyscallReport,
+    VatCapData, VatExportID, VatImportID, VatName, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "bootstrap");
+        let t = VatSendTarget::Import(VatImportID(1));
+        let vmsg = OutboundVatMessage::new("foo", b"body", vec![]);
+        self.syscall.send(t, vmsg);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+struct Vat2Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat2Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "foo");
+        let data = VatCapData {
+            body: b"result".to_vec(),
+            slots: vec![],
+        };
+        self.syscall
+            .fulfill_to_data(message.resolver.unwrap(), data);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+#[derive(Default)]
+struct EventLog(Vec<KernelEvent>);
+impl KernelObserver for EventLog {
+    fn observe(&mut self, event: &KernelEvent) {
+        self.0.push(event.clone());
+    }
+}
+
+fn tag(event: &KernelEvent) -> &'static str {
+    use KernelEvent::*;
+    match event {
+        VatCreated { .. } => "vat-created",
+        VatTerminated { .. } => "vat-terminated",
+        CrankStart { .. } => "crank-start",
+        Delivery { .. } => "delivery",
+        Syscall { .. } => "syscall",
+        Queued { .. } => "queued",
+        PromiseState { .. } => "promise-state",
+        PromiseForwarded { .. } => "promise-forwarded",
+        CrankFinish { .. } => "crank-finish",
+    }
+}
+
+#[test]
+fn test_observer() {
+    let log = Rc::new(RefCell::new(EventLog::default()));
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(log.clone()));
+    cfg.add_observer(Box::new(StderrLogger));
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let vn2 = VatName("vat2".to_string());
+    let setup2 = |syscall| -> Box<dyn Dispatch> { Box::new(Vat2Dispatch { syscall }) };
+    let sb2: Box<Setup> = Box::new(setup2);
+    cfg.add_vat(&vn2, sb2);
+    let mut c = Controller::new(cfg);
+    c.add_import(&vn, 1, &vn2, 0);
+    assert_eq!(log.borrow().0.len(), 2);
+
+    c.start();
+    c.run();
+    let tags: Vec<&str> = log.borrow().0.iter().map(tag).collect();
+    #[rustfmt::skip]
+    let expected = vec![
+        "vat-created", "vat-created", "queued",
+        // bootstrap sends foo, creating the result promise
+        "crank-start", "delivery", "promise-state", "syscall", "queued", "crank-finish",
+        // vat2 fulfills it
+        "crank-start", "delivery", "syscall", "promise-state", "queued", "crank-finish",
+        // bootstrap is notified
+        "crank-start", "delivery", "crank-finish",
+    ];
+    assert_eq!(tags, expected);
+
+    let events = log.borrow().0.clone();
+    match &events[6] {
+        KernelEvent::Syscall { vat, syscall } => {
+            assert_eq!(*vat, vn);
+            match syscall {
+                SyscallReport::Send { method, .. } => assert_eq!(method, "foo"),
+                _ => panic!("expected a send"),
+            }
+        }
+        _ => panic!("expected a syscall"),
+    }
+    match &events[12] {
+        KernelEvent::PromiseState { status, .. } => match status {
+            PromiseStatus::FulfilledToData(data) => assert_eq!(data.body, b"result"),
+            _ => panic!("expected fulfillment"),
+        },
+        _ => panic!("expected a promise-state"),
+    }
+
+    c.shutdown();
+    let tags: Vec<&str> = log.borrow().0.iter().map(tag).collect();
+    assert_eq!(tags[tags.len() - 2..], ["vat-terminated", "vat-terminated"]);
+}
+
+#[test]
+fn test_controller_observer() {
+    let mut cfg = Config::new();
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let vn2 = VatName("vat2".to_string());
+    let setup2 = |syscall| -> Box<dyn Dispatch> { Box::new(Vat2Dispatch { syscall }) };
+    let sb2: Box<Setup> = Box::new(setup2);
+    cfg.add_vat(&vn2, sb2);
+    let mut c = Controller::new(cfg);
+    c.add_import(&vn, 1, &vn2, 0);
+    c.start();
+    c.step();
+
+    // observers added later only see what happens afterwards
+    let log = Rc::new(RefCell::new(EventLog::default()));
+    c.add_observer(Box::new(log.clone()));
+    c.step();
+    let tags: Vec<&str> = log.borrow().0.iter().map(tag).collect();
+    assert_eq!(
+        tags,
+        vec![
+            "crank-start",
+            "delivery",
+            "syscall",
+            "promise-state",
+            "queued",
+            "crank-finish"
+        ]
+    );
+}
@@ -0,0 +1,169 @@
+//! Remote<I> and #[far_interface], which need the "macros" feature:
+//!
+//!     cargo test --features macros --test remote
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use swingset::{
+    far_interface, far_object, to_capdata, AsyncVat, Config, Controller, FarVat,
+    Fulfillment, MockSyscall, RecordedSyscall, Remote, Task, VatArgSlot, VatCapData,
+    VatHandle, VatImportID, VatName, VatPromiseID, VatSendTarget,
+};
+
+#[far_interface]
+trait Bank {
+    fn deposit(&self, amount: u64) -> u64;
+    fn withdraw(&self, amount: u64) -> Result<u64, String>;
+    fn balance(&self) -> u64;
+}
+
+#[far_interface]
+trait Registry {
+    fn register(&self, name: String, bank: Remote<dyn Bank>);
+    fn lookup(&self, name: String) -> Option<Remote<dyn Bank>>;
+}
+
+struct MyBank {
+    balance: RefCell<u64>,
+}
+
+#[far_object]
+impl Bank for MyBank {
+    fn deposit(&self, amount: u64) -> u64 {
+        *self.balance.borrow_mut() += amount;
+        *self.balance.borrow()
+    }
+
+    fn withdraw(&self, amount: u64) -> Result<u64, String> {
+        let mut balance = self.balance.borrow_mut();
+        if amount > *balance {
+            return Err(format!("cannot withdraw {} from {}", amount, balance));
+        }
+        *balance -= amount;
+        Ok(*balance)
+    }
+
+    fn balance(&self) -> u64 {
+        *self.balance.borrow()
+    }
+}
+
+#[derive(Default)]
+struct MyRegistry {
+    banks: RefCell<BTreeMap<String, Remote<dyn Bank>>>,
+}
+
+#[far_object]
+impl Registry for MyRegistry {
+    fn register(&self, name: String, bank: Remote<dyn Bank>) {
+        self.banks.borrow_mut().insert(name, bank);
+    }
+
+    fn lookup(&self, name: String) -> Option<Remote<dyn Bank>> {
+        self.banks.borrow().get(&name).copied()
+    }
+}
+
+#[test]
+fn test_stubs() {
+    let bank: Remote<dyn Bank> = Remote::new(VatSendTarget::Import(VatImportID(3)));
+    let call = bank.deposit(5);
+    assert_eq!(call.message().name, "deposit");
+    assert_eq!(call.message().args.body, b"#[\"+5\"]".to_vec());
+
+    let mut mock = MockSyscall::new();
+    mock.first_ids(7, 0);
+    let promised = call.send(&mut mock);
+    assert_eq!(promised.promise(), VatPromiseID(7));
+    match &mock.take()[..] {
+        [RecordedSyscall::Send { target, method, .. }] => {
+            assert_eq!(*target, VatSendTarget::Import(VatImportID(3)));
+            assert_eq!(method, "deposit");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    let fulfilled = |data: VatCapData| Fulfillment::Data(data);
+    assert_eq!(
+        promised.decode(fulfilled(to_capdata(&8u64).unwrap())),
+        Ok(8)
+    );
+    assert!(promised
+        .decode(fulfilled(to_capdata("eight").unwrap()))
+        .is_err());
+
+    // a Remote travels as a slot
+    let registry: Remote<dyn Registry> =
+        Remote::new(VatSendTarget::Import(VatImportID(4)));
+    let call = registry.register("main".to_string(), bank);
+    assert_eq!(call.message().args.body, b"#[\"main\",\"$0\"]".to_vec());
+    assert_eq!(
+        call.message().args.slots,
+        vec![VatArgSlot::Import(VatImportID(3))]
+    );
+}
+
+#[test]
+fn test_between_vats() {
+    let log: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(vec![]));
+    let mut cfg = Config::new();
+    cfg.check_invariants(true);
+    let bank = MyBank {
+        balance: RefCell::new(0),
+    };
+    cfg.add_vat(&VatName("bank".to_string()), FarVat::setup(bank));
+    cfg.add_vat(
+        &VatName("registry".to_string()),
+        FarVat::setup(MyRegistry::default()),
+    );
+    let client_log = log.clone();
+    let handler = move |vat: &VatHandle, _, _| {
+        let vat = vat.clone();
+        let log = client_log.clone();
+        let task: Task = Box::pin(async move {
+            let bank: Remote<dyn Bank> =
+                Remote::new(VatSendTarget::Import(VatImportID(1)));
+            let registry: Remote<dyn Registry> =
+                Remote::new(VatSendTarget::Import(VatImportID(2)));
+            let balance = vat.call(bank.deposit(10)).await?;
+            log.borrow_mut().push(format!("deposit -> {}", balance));
+            let e = vat.call(bank.withdraw(25)).await.unwrap_err();
+            log.borrow_mut().push(format!(
+                "withdraw -> {:?}",
+                String::from_utf8(e.body).unwrap()
+            ));
+
+            vat.call(registry.register("main".to_string(), bank))
+                .await?;
+            let missing = vat.call(registry.lookup("other".to_string())).await?;
+            log.borrow_mut().push(format!("other is {:?}", missing));
+            // the registry hands back the bank as its own import of it, and
+            // the kernel translates it back to ours
+            let found = vat.call(registry.lookup("main".to_string())).await?;
+            let found = found.unwrap();
+            log.borrow_mut()
+                .push(format!("same bank: {}", found == bank));
+            let balance = vat.call(found.balance()).await?;
+            log.borrow_mut().push(format!("balance -> {}", balance));
+            Ok(Fulfillment::Data(VatCapData::new(b"", vec![])))
+        });
+        task
+    };
+    cfg.add_vat(&VatName("bootstrap".to_string()), AsyncVat::setup(handler));
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    c.add_import(&bootstrap, 1, &VatName("bank".to_string()), 0);
+    c.add_import(&bootstrap, 2, &VatName("registry".to_string()), 0);
+    c.start();
+    c.run();
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "deposit -> 10",
+            r##"withdraw -> "#{\"#error\":\"cannot withdraw 25 from 10\",\"name\":\"Error\"}""##,
+            "other is None",
+            "same bank: true",
+            "balance -> 10",
+        ]
+    );
+}
@@ -0,0 +1,160 @@
+use swingset::{
+    Cause, Config, Controller, DeliveryKind, DeliveryReport, Dispatch, HostArgSlot,
+    HostCapData, InboundVatMessage, MessageID, OutboundVatMessage, Setup, Syscall,
+    SyscallReport, VatArgSlot, VatCapData, VatExportID, VatImportID, VatName,
+    VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "bootstrap");
+        let t = VatSendTarget::Import(VatImportID(1));
+        let arg1 = VatArgSlot::Export(VatExportID(22));
+        let vmsg = OutboundVatMessage::new("foo", b"body", vec![arg1]);
+        self.syscall.send(t, vmsg);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, data: VatCapData) {
+        assert_eq!(data.body, b"result");
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+struct Vat2Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat2Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        assert_eq!(message.name, "foo");
+        let data = VatCapData {
+            body: b"result".to_vec(),
+            slots: vec![],
+        };
+        self.syscall
+            .fulfill_to_data(message.resolver.unwrap(), data);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build() -> Controller {
+    let mut cfg = Config::new();
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let vn2 = VatName("vat2".to_string());
+    let setup2 = |syscall| -> Box<dyn Dispatch> { Box::new(Vat2Dispatch { syscall }) };
+    let sb2: Box<Setup> = Box::new(setup2);
+    cfg.add_vat(&vn2, sb2);
+    let mut c = Controller::new(cfg);
+    c.add_import(&vn, 1, &vn2, 0);
+    c
+}
+
+#[test]
+fn test_crank_reports() {
+    let mut c = build();
+    let boot = c.export_handle("bootstrap", 0);
+    let boot_22 = c.export_handle("bootstrap", 22);
+    let vat2 = c.export_handle("vat2", 0);
+    c.start();
+
+    let r0 = c.step().unwrap();
+    assert_eq!(r0.crank, 0);
+    let bootstrap = DeliveryReport {
+        id: MessageID(0),
+        cause: Cause::Host,
+        vat: VatName("bootstrap".to_string()),
+        kind: DeliveryKind::Deliver,
+        target: HostArgSlot::Export(boot),
+        method: Some("bootstrap".to_string()),
+    };
+    assert_eq!(r0.delivery, bootstrap);
+    assert_eq!(r0.promises_created.len(), 1);
+    let p = r0.promises_created[0];
+    let send = SyscallReport::Send {
+        target: HostArgSlot::Export(vat2),
+        method: "foo".to_string(),
+        args: HostCapData::new(b"body", vec![HostArgSlot::Export(boot_22)]),
+        result: Some(p),
+    };
+    assert_eq!(r0.syscalls, vec![send]);
+    let foo = DeliveryReport {
+        id: MessageID(1),
+        cause: Cause::Syscall {
+            message: MessageID(0),
+            crank: 0,
+            syscall: 0,
+        },
+        vat: VatName("vat2".to_string()),
+        kind: DeliveryKind::Deliver,
+        target: HostArgSlot::Export(vat2),
+        method: Some("foo".to_string()),
+    };
+    assert_eq!(r0.queued, vec![foo.clone()]);
+    assert_eq!(r0.promises_resolved, vec![]);
+
+    let r1 = c.step().unwrap();
+    assert_eq!(r1.crank, 1);
+    assert_eq!(r1.delivery, foo);
+    let fulfill = SyscallReport::FulfillToData {
+        promise: p,
+        data: HostCapData::new(b"result", vec![]),
+    };
+    assert_eq!(r1.syscalls, vec![fulfill]);
+    let notify = DeliveryReport {
+        id: MessageID(2),
+        cause: Cause::Syscall {
+            message: MessageID(1),
+            crank: 1,
+            syscall: 0,
+        },
+        vat: VatName("bootstrap".to_string()),
+        kind: DeliveryKind::NotifyFulfillToData,
+        target: HostArgSlot::Promise(p),
+        method: None,
+    };
+    assert_eq!(r1.queued, vec![notify]);
+    assert_eq!(r1.promises_created, vec![]);
+    assert_eq!(r1.promises_resolved, vec![p]);
+
+    let summary = c.run();
+    assert_eq!(summary.cranks, 1);
+    assert_eq!(summary.syscalls, 0);
+    assert!(c.step().is_none());
+}
+
+#[test]
+fn test_run_summary() {
+    let mut c = build();
+    c.start();
+    let summary = c.run();
+    assert_eq!(summary.cranks, 3);
+    assert_eq!(summary.syscalls, 2);
+    assert_eq!(summary.queued, 2);
+    assert_eq!(summary.promises_created, 1);
+    assert_eq!(summary.promises_resolved, 1);
+}
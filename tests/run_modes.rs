@@ -0,0 +1,109 @@
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, Setup, Syscall,
+    VatCapData, VatExportID, VatImportID, VatName, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
+};
+
+// each vat answers every message by sending the other name back to its
+// partner, forever
+struct PingPongDispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for PingPongDispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        let reply = match message.name.as_str() {
+            "bootstrap" | "pong" => "ping",
+            "ping" => "pong",
+            _ => panic!("unknown message {}", message.name),
+        };
+        let t = VatSendTarget::Import(VatImportID(1));
+        let vmsg = OutboundVatMessage::new(reply, b"", vec![]);
+        self.syscall.send_only(t, vmsg);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build() -> Controller {
+    let mut cfg = Config::new();
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(PingPongDispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let vn2 = VatName("vat2".to_string());
+    let setup2 =
+        |syscall| -> Box<dyn Dispatch> { Box::new(PingPongDispatch { syscall }) };
+    let sb2: Box<Setup> = Box::new(setup2);
+    cfg.add_vat(&vn2, sb2);
+    let mut c = Controller::new(cfg);
+    c.add_import(&vn, 1, &vn2, 0);
+    c.add_import(&vn2, 1, &vn, 0);
+    c.start();
+    c
+}
+
+#[test]
+fn test_run_with_limit() {
+    let mut c = build();
+    let summary = c.run_with_limit(10);
+    assert_eq!(summary.cranks, 10);
+    assert_eq!(summary.syscalls, 10);
+    let summary = c.run_with_limit(0);
+    assert_eq!(summary.cranks, 0);
+    // the ping-pong is still going
+    assert!(c.step().is_some());
+}
+
+#[test]
+fn test_run_until() {
+    let mut c = build();
+    let mut pongs = 0;
+    let summary = c.run_until(|report| {
+        if report.delivery.method == Some("pong".to_string()) {
+            pongs += 1;
+        }
+        pongs == 3
+    });
+    // bootstrap, ping, pong, ping, pong, ping, pong
+    assert_eq!(summary.cranks, 7);
+    let next = c.step().unwrap();
+    assert_eq!(next.delivery.method, Some("ping".to_string()));
+}
+
+#[test]
+fn test_run_block() {
+    let mut c = build();
+    // every crank makes one syscall, so costs two computrons
+    let (summary, remaining) = c.run_block(7);
+    assert_eq!(summary.cranks, 4);
+    assert_eq!(summary.computrons, 8);
+    assert_eq!(remaining, 0);
+    let (summary, remaining) = c.run_block(0);
+    assert_eq!(summary.cranks, 0);
+    assert_eq!(remaining, 0);
+}
+
+#[test]
+fn test_run_block_idle() {
+    let mut cfg = Config::new();
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(PingPongDispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    // never started, so there is nothing to spend the budget on
+    let mut c = Controller::new(cfg);
+    let (summary, remaining) = c.run_block(100);
+    assert_eq!(summary.cranks, 0);
+    assert_eq!(remaining, 100);
+}
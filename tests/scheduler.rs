@@ -0,0 +1,331 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, Setup, Syscall,
+    VatCapData, VatExportID, VatImportID, VatName, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
+};
+
+// Bootstrap sends numbered messages to alice and bob, interleaved, and
+// pipelines more messages on the result of its first message to alice. The
+// receivers log what they get.
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+struct Sender {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Sender {
+    fn deliver(&mut self, _target: VatExportID, _message: InboundVatMessage) {
+        let alice = VatSendTarget::Import(VatImportID(1));
+        let bob = VatSendTarget::Import(VatImportID(2));
+        let mut first = None;
+        for i in 0..5 {
+            let body = format!("{}", i);
+            let msg = OutboundVatMessage::new("count", body.as_bytes(), vec![]);
+            let p = self.syscall.send(alice, msg);
+            first = first.or(Some(p));
+            let msg = OutboundVatMessage::new("count", body.as_bytes(), vec![]);
+            self.syscall.send_only(bob, msg);
+        }
+        for i in 0..3 {
+            let body = format!("{}", i);
+            let msg = OutboundVatMessage::new("then", body.as_bytes(), vec![]);
+            self.syscall
+                .send_only(VatSendTarget::Promise(first.unwrap()), msg);
+        }
+    }
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+struct Receiver {
+    name: &'static str,
+    log: Log,
+}
+impl Receiver {
+    fn record(&mut self, message: InboundVatMessage) {
+        let body = String::from_utf8(message.args.body).unwrap();
+        let entry = format!("{} {} {}", self.name, message.name, body);
+        self.log.borrow_mut().push(entry);
+    }
+}
+impl Dispatch for Receiver {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        self.record(message);
+    }
+    fn deliver_promise(&mut self, _target: VatResolverID, message: InboundVatMessage) {
+        self.record(message);
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build(chaos_seed: Option<u64>, log: &Log) -> Controller {
+    let mut cfg = Config::new();
+    cfg.check_e_order(true);
+    if let Some(seed) = chaos_seed {
+        cfg.chaos_scheduler(seed);
+    }
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Sender { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&VatName("bootstrap".to_string()), sb);
+    for name in &["alice", "bob"] {
+        let log = log.clone();
+        let setup =
+            move |_syscall| -> Box<dyn Dispatch> { Box::new(Receiver { name, log }) };
+        let sb: Box<Setup> = Box::new(setup);
+        cfg.add_vat(&VatName(name.to_string()), sb);
+    }
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    c.add_import(&bootstrap, 1, &VatName("alice".to_string()), 0);
+    c.add_import(&bootstrap, 2, &VatName("bob".to_string()), 0);
+    c
+}
+
+fn run(chaos_seed: Option<u64>) -> Vec<String> {
+    let log: Log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(chaos_seed, &log);
+    c.start();
+    c.run();
+    let log = log.borrow().clone();
+    log
+}
+
+fn only(log: &[String], prefix: &str) -> Vec<String> {
+    log.iter()
+        .filter(|entry| entry.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn test_fifo() {
+    let log = run(None);
+    assert_eq!(
+        log[..4],
+        [
+            "alice count 0",
+            "bob count 0",
+            "alice count 1",
+            "bob count 1"
+        ]
+    );
+    assert_eq!(log.len(), 13);
+}
+
+#[test]
+fn test_chaos() {
+    let fifo = run(None);
+    let mut reordered = 0;
+    for seed in 0..20 {
+        let log = run(Some(seed));
+        // the same seed always makes the same choices
+        assert_eq!(log, run(Some(seed)));
+        // every message arrives, in some order...
+        let mut sorted = log.clone();
+        sorted.sort();
+        let mut expected = fifo.clone();
+        expected.sort();
+        assert_eq!(sorted, expected);
+        // ...but each reference keeps its order...
+        assert_eq!(only(&log, "alice count"), only(&fifo, "alice count"));
+        assert_eq!(only(&log, "bob count"), only(&fifo, "bob count"));
+        assert_eq!(only(&log, "alice then"), only(&fifo, "alice then"));
+        // ...and pipelined messages follow the message they were sent on
+        let first = log.iter().position(|e| e == "alice count 0").unwrap();
+        let then = log.iter().position(|e| e == "alice then 0").unwrap();
+        assert!(first < then);
+        if log != fifo {
+            reordered += 1;
+        }
+    }
+    assert!(reordered > 0);
+}
+
+// Delivering by hand is refused when the entry may not go next
+
+#[test]
+fn test_step_at_refuses_ineligible() {
+    let log: Log = Rc::new(RefCell::new(vec![]));
+    let mut c = build(None, &log);
+    c.start();
+    c.step();
+    // alice count 1, ahead of alice count 0
+    assert!(c.step_at(2).is_none());
+    // alice then 0, ahead of the alice count 0 it was pipelined on
+    assert!(c.step_at(10).is_none());
+    assert!(c.step_at(100).is_none());
+    assert!(log.borrow().is_empty());
+    // nothing was taken off the queue
+    c.run();
+    assert_eq!(*log.borrow(), run(None));
+}
+
+// Bootstrap asks alice to make something and pipelines messages on the
+// answer. Those are queued to alice, as the decider, until she resolves it:
+// then they must go wherever the answer leads instead.
+
+#[derive(Clone, Copy)]
+enum Answer {
+    /// fulfill to bob's object
+    Target,
+    /// fulfill to data
+    Data,
+    /// forward to the answer of a make() sent to bob
+    Forward,
+}
+
+struct Pipeliner {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Pipeliner {
+    fn deliver(&mut self, _target: VatExportID, _message: InboundVatMessage) {
+        let alice = VatSendTarget::Import(VatImportID(1));
+        let make = OutboundVatMessage::new("make", b"bootstrap", vec![]);
+        let p = self.syscall.send(alice, make);
+        for i in 0..3 {
+            let body = format!("{}", i);
+            let msg = OutboundVatMessage::new("then", body.as_bytes(), vec![]);
+            self.syscall.send_only(VatSendTarget::Promise(p), msg);
+        }
+    }
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+}
+
+struct Maker {
+    syscall: Box<dyn Syscall>,
+    answer: Answer,
+    receiver: Receiver,
+}
+impl Dispatch for Maker {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        let resolver = message.resolver;
+        self.receiver.record(message);
+        let r = match resolver {
+            Some(r) => r,
+            None => return,
+        };
+        let bob = VatImportID(1);
+        match self.answer {
+            Answer::Target => self
+                .syscall
+                .fulfill_to_target(r, VatResolveTarget::Import(bob)),
+            Answer::Data => self
+                .syscall
+                .fulfill_to_data(r, VatCapData::new(b"made", vec![])),
+            Answer::Forward => {
+                let make = OutboundVatMessage::new("make", b"alice", vec![]);
+                let p = self.syscall.send(VatSendTarget::Import(bob), make);
+                self.syscall.forward(r, p);
+            }
+        }
+    }
+    fn deliver_promise(&mut self, _target: VatResolverID, message: InboundVatMessage) {
+        self.receiver.record(message);
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+}
+
+fn run_answer(answer: Answer, chaos_seed: Option<u64>) -> Vec<String> {
+    let log: Log = Rc::new(RefCell::new(vec![]));
+    let mut cfg = Config::new();
+    cfg.check_e_order(true);
+    cfg.check_invariants(true);
+    if let Some(seed) = chaos_seed {
+        cfg.chaos_scheduler(seed);
+    }
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Pipeliner { syscall }) };
+    cfg.add_vat(&VatName("bootstrap".to_string()), Box::new(setup));
+    let l = log.clone();
+    let setup = move |syscall| -> Box<dyn Dispatch> {
+        Box::new(Maker {
+            syscall,
+            answer,
+            receiver: Receiver {
+                name: "alice",
+                log: l,
+            },
+        })
+    };
+    cfg.add_vat(&VatName("alice".to_string()), Box::new(setup));
+    let l = log.clone();
+    let setup = move |_syscall| -> Box<dyn Dispatch> {
+        Box::new(Receiver {
+            name: "bob",
+            log: l,
+        })
+    };
+    cfg.add_vat(&VatName("bob".to_string()), Box::new(setup));
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    let alice = VatName("alice".to_string());
+    c.add_import(&bootstrap, 1, &alice, 0);
+    c.add_import(&alice, 1, &VatName("bob".to_string()), 0);
+    c.start();
+    c.run();
+    let log = log.borrow().clone();
+    log
+}
+
+fn check_answer(answer: Answer, expected: &[&str]) {
+    for seed in std::iter::once(None).chain((0..10).map(Some)) {
+        assert_eq!(run_answer(answer, seed), expected);
+    }
+}
+
+#[test]
+fn test_resolution_redirects() {
+    check_answer(
+        Answer::Target,
+        &[
+            "alice make bootstrap",
+            "bob then 0",
+            "bob then 1",
+            "bob then 2",
+        ],
+    );
+    // nothing can be sent to data, so the messages are dropped
+    check_answer(Answer::Data, &["alice make bootstrap"]);
+}
+
+#[test]
+fn test_forward_redirects() {
+    check_answer(
+        Answer::Forward,
+        &[
+            "alice make bootstrap",
+            "bob make alice",
+            "bob then 0",
+            "bob then 1",
+            "bob then 2",
+        ],
+    );
+}
@@ -0,0 +1,48 @@
+use swingset::{Config, Controller, EventLog, ScriptedVat, VatExportID};
+
+fn controller(vat: ScriptedVat) -> Controller {
+    let mut cfg = Config::new();
+    vat.install(&mut cfg);
+    let mut c = Controller::new(cfg);
+    c.start();
+    c.run();
+    c
+}
+
+#[test]
+fn test_log() {
+    let log = EventLog::new();
+    let vat = ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| s.log("started"))
+        .on_target(VatExportID(3), |s, _| s.log("third"))
+        .on_method("ping", |s, _| s.log("first ping"))
+        .on_method("ping", |s, _| s.log("second ping"));
+    let mut c = controller(vat);
+    c.push("bootstrap", 0, "ping", b"");
+    c.push("bootstrap", 3, "ping", b"");
+    c.push("bootstrap", 0, "ping", b"");
+    c.run();
+    assert_eq!(
+        log.take(),
+        vec![
+            "bootstrap: deliver bootstrap",
+            "bootstrap: started",
+            "bootstrap: deliver ping",
+            "bootstrap: first ping",
+            "bootstrap: deliver ping",
+            "bootstrap: third",
+            "bootstrap: deliver ping",
+            "bootstrap: second ping",
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "bootstrap did not expect")]
+fn test_unexpected() {
+    let log = EventLog::new();
+    let vat = ScriptedVat::new("bootstrap", &log).on_method("bootstrap", |_, _| ());
+    let mut c = controller(vat);
+    c.push("bootstrap", 0, "bootstrap", b"");
+    c.run();
+}
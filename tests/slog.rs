@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, Dispatch, HostCapData, InboundVatMessage, OutboundVatMessage,
+    Setup, SlogWriter, Syscall, VatArgSlot, VatCapData, VatExportID, VatImportID,
+    VatName, VatPromiseID, VatResolveTarget, VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
+        if target == VatExportID(0) {
+            let t = VatSendTarget::Import(VatImportID(1));
+            let arg1 = VatArgSlot::Export(VatExportID(22));
+            let vmsg = OutboundVatMessage::new("foo", b"body", vec![arg1]);
+            self.syscall.send(t, vmsg);
+        } else {
+            let res = VatCapData {
+                body: b"result".to_vec(),
+                slots: vec![],
+            };
+            self.syscall.fulfill_to_data(message.resolver.unwrap(), res);
+        }
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build(cfg: &mut Config) {
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+}
+
+#[test]
+fn test_slog() {
+    let mut t = 0.0;
+    let clock = move || {
+        t += 1.0;
+        t
+    };
+    let slog = SlogWriter::with_clock(Vec::new(), Box::new(clock));
+    let slog = Rc::new(RefCell::new(slog));
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(slog.clone()));
+    build(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let vn = VatName("bootstrap".to_string());
+    c.add_import(&vn, 1, &vn, 2);
+    c.start();
+    c.run();
+    c.shutdown();
+
+    let out = String::from_utf8(slog.borrow().get_ref().clone()).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    let expected = vec![
+        r#"{"type":"create-vat","time":1,"vatName":"bootstrap"}"#,
+        r#"{"type":"queue","time":2,"msgID":"m0","cause":null,"vatName":"bootstrap","kind":"deliver","target":"ko0.0","method":"bootstrap"}"#,
+        r#"{"type":"crank-start","time":3,"crankNum":0}"#,
+        r#"{"type":"deliver","time":4,"crankNum":0,"msgID":"m0","cause":null,"vatName":"bootstrap","kind":"deliver","target":"ko0.0","method":"bootstrap","args":{"body":"","slots":[]},"result":null}"#,
+        r#"{"type":"promise","time":5,"crankNum":0,"kpid":"kp0","state":"unresolved"}"#,
+        r#"{"type":"syscall","time":6,"crankNum":0,"vatName":"bootstrap","syscall":"send","target":"ko0.2","method":"foo","args":{"body":"body","slots":["ko0.22"]},"result":"kp0"}"#,
+        r#"{"type":"queue","time":7,"crankNum":0,"msgID":"m1","cause":{"msgID":"m0","crankNum":0,"syscallNum":0},"vatName":"bootstrap","kind":"deliver","target":"ko0.2","method":"foo"}"#,
+        r#"{"type":"crank-finish","time":8,"crankNum":0,"syscalls":1,"queued":1}"#,
+        r#"{"type":"crank-start","time":9,"crankNum":1}"#,
+        r#"{"type":"deliver","time":10,"crankNum":1,"msgID":"m1","cause":{"msgID":"m0","crankNum":0,"syscallNum":0},"vatName":"bootstrap","kind":"deliver","target":"ko0.2","method":"foo","args":{"body":"body","slots":["ko0.22"]},"result":"kp0"}"#,
+        r#"{"type":"syscall","time":11,"crankNum":1,"vatName":"bootstrap","syscall":"fulfill_to_data","promise":"kp0","data":{"body":"result","slots":[]}}"#,
+        r#"{"type":"promise","time":12,"crankNum":1,"kpid":"kp0","state":"fulfilledToData","data":{"body":"result","slots":[]}}"#,
+        r#"{"type":"queue","time":13,"crankNum":1,"msgID":"m2","cause":{"msgID":"m1","crankNum":1,"syscallNum":0},"vatName":"bootstrap","kind":"notify_fulfill_to_data","target":"kp0","method":null}"#,
+        r#"{"type":"crank-finish","time":14,"crankNum":1,"syscalls":1,"queued":1}"#,
+        r#"{"type":"crank-start","time":15,"crankNum":2}"#,
+        r#"{"type":"deliver","time":16,"crankNum":2,"msgID":"m2","cause":{"msgID":"m1","crankNum":1,"syscallNum":0},"vatName":"bootstrap","kind":"notify_fulfill_to_data","target":"kp0","method":null,"args":{"body":"result","slots":[]},"result":null}"#,
+        r#"{"type":"crank-finish","time":17,"crankNum":2,"syscalls":0,"queued":0}"#,
+        r#"{"type":"terminate-vat","time":18,"vatName":"bootstrap"}"#,
+    ];
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn test_slog_file() {
+    let path = std::env::temp_dir().join(format!("swingset-{}.slog", std::process::id()));
+    let slog = SlogWriter::create(&path).unwrap();
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(slog));
+    build(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let vn = VatName("bootstrap".to_string());
+    c.add_import(&vn, 1, &vn, 2);
+    c.start();
+    c.run();
+    c.shutdown();
+
+    let out = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(out.lines().count(), 18);
+    for line in out.lines() {
+        assert!(line.starts_with(r#"{"type":""#));
+        assert!(line.ends_with('}'));
+    }
+}
+
+// bodies that are not UTF-8 are logged in hex, so no bytes are lost
+#[test]
+fn test_slog_binary_body() {
+    let slog = SlogWriter::with_clock(Vec::new(), Box::new(|| 0.0));
+    let slog = Rc::new(RefCell::new(slog));
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(slog.clone()));
+    build(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let target = c.export_handle("bootstrap", 2);
+    c.queue_to_export(target, "foo", HostCapData::new(b"\xffbin", vec![]));
+    c.run();
+    c.shutdown();
+
+    let out = String::from_utf8(slog.borrow().get_ref().clone()).unwrap();
+    let deliver = out
+        .lines()
+        .find(|l| l.contains(r#""type":"deliver""#))
+        .unwrap();
+    assert!(deliver.contains(r#""args":{"bodyHex":"ff62696e","slots":[]}"#));
+}
@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use swingset::{
+    Config, Controller, DeliveryKind, Dispatch, HostArgSlot, InboundVatMessage,
+    KernelEvent, KernelObserver, OutboundVatMessage, PromiseStatus, Setup, Syscall,
+    VatCapData, VatExportID, VatImportID, VatName, VatPromiseID, VatResolveTarget,
+    VatResolverID, VatSendTarget,
+};
+
+struct Vat1Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat1Dispatch {
+    fn deliver(&mut self, _target: VatExportID, _message: InboundVatMessage) {
+        let t = VatSendTarget::Import(VatImportID(1));
+        let vmsg = OutboundVatMessage::new("foo", b"body", vec![]);
+        let p = self.syscall.send(t, vmsg);
+        self.syscall.subscribe(p);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {}
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+struct Vat2Dispatch {
+    syscall: Box<dyn Syscall>,
+}
+impl Dispatch for Vat2Dispatch {
+    fn deliver(&mut self, _target: VatExportID, message: InboundVatMessage) {
+        let data = VatCapData {
+            body: b"result".to_vec(),
+            slots: vec![],
+        };
+        self.syscall
+            .fulfill_to_data(message.resolver.unwrap(), data);
+    }
+
+    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
+        panic!();
+    }
+    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
+        panic!();
+    }
+    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
+        panic!();
+    }
+}
+
+fn build() -> Controller {
+    let mut cfg = Config::new();
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    let sb: Box<Setup> = Box::new(setup);
+    cfg.add_vat(&vn, sb);
+    let vn2 = VatName("vat2".to_string());
+    let setup2 = |syscall| -> Box<dyn Dispatch> { Box::new(Vat2Dispatch { syscall }) };
+    let sb2: Box<Setup> = Box::new(setup2);
+    cfg.add_vat(&vn2, sb2);
+    let mut c = Controller::new(cfg);
+    c.add_import(&vn, 1, &vn2, 0);
+    c
+}
+
+#[test]
+fn test_dump_state() {
+    let vn = VatName("bootstrap".to_string());
+    let vn2 = VatName("vat2".to_string());
+    let mut c = build();
+    let foo = c.export_handle("vat2", 0);
+    c.start();
+
+    let s = c.dump_state();
+    assert_eq!(s.crank, 0);
+    let names: Vec<&str> = s.vats.iter().map(|v| v.name.0.as_str()).collect();
+    assert_eq!(names, vec!["bootstrap", "vat2"]);
+    assert_eq!(
+        s.vat("bootstrap").unwrap().imports,
+        vec![(VatImportID(1), foo)]
+    );
+    assert_eq!(s.vat("vat2").unwrap().exports, vec![(VatExportID(0), foo)]);
+    assert!(s.promises.is_empty());
+    assert_eq!(s.run_queue.len(), 1);
+    assert_eq!(
+        s.run_queue[0].delivery.method,
+        Some("bootstrap".to_string())
+    );
+
+    // bootstrap sends foo() and subscribes to the result
+    let report = c.step().unwrap();
+    let kp = report.promises_created[0];
+    let s = c.dump_state();
+    assert_eq!(s.crank, 1);
+    assert_eq!(
+        s.vat("bootstrap").unwrap().promises,
+        vec![(VatPromiseID(0), kp)]
+    );
+    let p = s.promise(kp).unwrap();
+    assert_eq!(p.status, PromiseStatus::Unresolved);
+    assert_eq!(p.decider, Some(vn2.clone()));
+    assert_eq!(p.subscribers, vec![vn.clone()]);
+    assert_eq!(s.run_queue.len(), 1);
+    let q = &s.run_queue[0];
+    assert_eq!(q.delivery.kind, DeliveryKind::Deliver);
+    assert_eq!(q.delivery.target, HostArgSlot::Export(foo));
+    assert_eq!(q.args.body, b"body");
+    assert_eq!(q.result, Some(kp));
+    let json = s.to_json();
+    let expected = format!(
+        r#"{{"kpid":"{}","state":"unresolved","decider":"vat2","subscribers":["bootstrap"]}}"#,
+        kp.kref()
+    );
+    assert!(json.contains(&expected), "{}", json);
+
+    // vat2 fulfills it, spending its resolver, and bootstrap is notified
+    c.step();
+    let s = c.dump_state();
+    assert!(s.vat("vat2").unwrap().resolvers.is_empty());
+    let p = s.promise(kp).unwrap();
+    assert!(matches!(p.status, PromiseStatus::FulfilledToData(_)));
+    assert_eq!(p.decider, None);
+    assert!(p.subscribers.is_empty());
+    assert_eq!(
+        s.run_queue[0].delivery.kind,
+        DeliveryKind::NotifyFulfillToData
+    );
+    assert_eq!(s.run_queue[0].delivery.vat, vn);
+    assert!(s.to_string().contains("run-queue:"));
+
+    c.run();
+    let s = c.dump_state();
+    assert!(s.run_queue.is_empty());
+    assert_eq!(s, c.dump_state());
+}
+
+struct Created(Rc<RefCell<Vec<String>>>);
+impl KernelObserver for Created {
+    fn observe(&mut self, event: &KernelEvent) {
+        if let KernelEvent::VatCreated { vat } = event {
+            self.0.borrow_mut().push(vat.0.clone());
+        }
+    }
+}
+
+/// a bootstrap vat which imports object 0 of each of eight other vats
+fn build_many(created: &Rc<RefCell<Vec<String>>>) -> Controller {
+    let mut cfg = Config::new();
+    cfg.add_observer(Box::new(Created(created.clone())));
+    let vn = VatName("bootstrap".to_string());
+    let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat1Dispatch { syscall }) };
+    cfg.add_vat(&vn, Box::new(setup));
+    for i in 2..10 {
+        let setup = |syscall| -> Box<dyn Dispatch> { Box::new(Vat2Dispatch { syscall }) };
+        cfg.add_vat(&VatName(format!("vat{}", i)), Box::new(setup));
+    }
+    let mut c = Controller::new(cfg);
+    for i in 2..10 {
+        c.add_import(&vn, i - 1, &VatName(format!("vat{}", i)), 0);
+    }
+    c
+}
+
+// vat IDs, and so the krefs in a dump, do not depend on hashing
+#[test]
+fn test_stable_dump() {
+    let created = Rc::new(RefCell::new(vec![]));
+    let mut dumps = vec![];
+    for _ in 0..2 {
+        created.borrow_mut().clear();
+        let mut c = build_many(&created);
+        c.start();
+        c.run();
+        dumps.push(c.dump_state().to_json());
+        let mut names = created.borrow().clone();
+        names.sort();
+        assert_eq!(*created.borrow(), names);
+    }
+    assert_eq!(dumps[0], dumps[1]);
+}
+
+#[test]
+fn test_export_dot() {
+    let mut c = build();
+    let foo = c.export_handle("vat2", 0).kref();
+    c.start();
+    let report = c.step().unwrap();
+    let kp = report.promises_created[0].kref();
+
+    let dot = c.export_dot();
+    assert!(dot.starts_with("digraph kernel {\n"));
+    assert!(dot.ends_with("}\n"));
+    let expected = vec![
+        "  subgraph \"cluster_bootstrap\" {".to_string(),
+        "    \"vat-bootstrap\" [shape=box];".to_string(),
+        format!("    \"{}\" [label=\"{}\\nexport 0\"];", foo, foo),
+        format!("  \"vat-bootstrap\" -> \"{}\" [label=\"import 1\"];", foo),
+        format!(
+            "  \"vat-bootstrap\" -> \"{}\" [label=\"promise 0\", style=dashed];",
+            kp
+        ),
+        format!("  \"{}\" [shape=diamond, label=\"{}\\nunresolved\"];", kp, kp),
+        format!("  \"{}\" -> \"vat-vat2\" [label=\"decider\", style=bold];", kp),
+        format!(
+            "  \"vat-bootstrap\" -> \"{}\" [label=\"subscribes\", style=dashed, color=gray];",
+            kp
+        ),
+        "  \"q0\" [shape=note, color=blue, label=\"q0: deliver foo\"];".to_string(),
+        format!("  \"q0\" -> \"{}\" [color=blue];", foo),
+        format!(
+            "  \"q0\" -> \"{}\" [label=\"result\", style=dashed, color=blue];",
+            kp
+        ),
+    ];
+    let lines: Vec<&str> = dot.lines().collect();
+    for line in expected {
+        assert!(lines.contains(&line.as_str()), "missing {}\n{}", line, dot);
+    }
+}
@@ -1,258 +1,141 @@
-use std::cell::RefCell;
-use std::rc::Rc;
 use swingset::{
-    Config, Controller, Dispatch, InboundVatMessage, OutboundVatMessage, Setup, Syscall,
-    VatArgSlot, VatCapData, VatExportID, VatImportID, VatName, VatPromiseID,
-    VatResolveTarget, VatResolverID, VatSendTarget,
+    Config, Controller, Delivery, EventLog, OutboundVatMessage, ScriptedVat, VatArgSlot,
+    VatCapData, VatExportID, VatImportID, VatName, VatPromiseID, VatResolveTarget,
+    VatSendTarget,
 };
 
-//#[derive(Debug)]
-struct Vat1Dispatch {
-    syscall: Box<dyn Syscall>,
-    log: Rc<RefCell<Vec<u32>>>,
-    p: Option<VatPromiseID>,
-    r: Option<VatResolverID>,
-}
-impl Dispatch for Vat1Dispatch {
-    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
-        println!("Vat1.deliver {} .{}", target, message.name);
-        assert_eq!(target, VatExportID(0), "unexpected target");
-
-        if message.name == "bootstrap" {
-            println!("in bootstrap");
-            let (p, r) = self.syscall.allocate_promise_and_resolver();
-            self.p = Some(p);
-            self.r = Some(r);
-            self.log.borrow_mut().push(0);
-        } else if message.name == "send_promise" {
-            let t = VatSendTarget::Import(VatImportID(1));
-            let arg1 = VatArgSlot::Promise(self.p.unwrap());
-            let vmsg = OutboundVatMessage::new("have_promise", b"body", vec![arg1]);
-            self.syscall.send_only(t, vmsg);
-            self.log.borrow_mut().push(1);
-        } else if message.name == "resolve_data" {
-            let slot0 = VatArgSlot::Export(VatExportID(10));
-            let slot1 = VatArgSlot::Import(VatImportID(1));
-            let data = VatCapData {
-                body: b"p2data".to_vec(),
-                slots: vec![slot0, slot1],
-            };
-            self.syscall.fulfill_to_data(self.r.unwrap(), data);
-            self.log.borrow_mut().push(3);
-        } else if message.name == "resolve_target" {
-            let slot0 = VatResolveTarget::Export(VatExportID(10));
-            //let slot1 = VatResolveTarget::Import(VatImportID(1));
-            self.syscall.fulfill_to_target(self.r.unwrap(), slot0);
-            self.log.borrow_mut().push(3);
-        } else if message.name == "reject" {
-            let slot0 = VatArgSlot::Export(VatExportID(10));
-            let slot1 = VatArgSlot::Import(VatImportID(1));
-            let data = VatCapData {
-                body: b"p2data".to_vec(),
-                slots: vec![slot0, slot1],
-            };
-            self.syscall.reject(self.r.unwrap(), data);
-            self.log.borrow_mut().push(3);
-        } else {
-            panic!("unknown message {}", message.name);
-        }
-    }
-
-    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
-        panic!();
-    }
-
-    fn notify_fulfill_to_target(&mut self, _id: VatPromiseID, _target: VatResolveTarget) {
-        panic!();
-    }
-    fn notify_fulfill_to_data(&mut self, _id: VatPromiseID, _data: VatCapData) {
-        panic!();
-    }
-    fn notify_reject(&mut self, _id: VatPromiseID, _data: VatCapData) {
-        panic!()
-    }
-}
-
-struct Vat2Dispatch {
-    syscall: Box<dyn Syscall>,
-    log: Rc<RefCell<Vec<u32>>>,
-    p: Option<VatPromiseID>,
-}
-impl Dispatch for Vat2Dispatch {
-    fn deliver(&mut self, target: VatExportID, message: InboundVatMessage) {
-        println!("Vat2.deliver {} .{}", target, message.name);
-        assert_eq!(target, VatExportID(0), "unexpected target");
-
-        if message.name == "have_promise" {
-            if let VatArgSlot::Promise(p) = message.args.slots[0] {
-                self.p = Some(p);
-            } else {
-                println!("args.slots[0] was not a Promise: {:?}", message.args.slots);
-                panic!("args.slots[0] was not a Promise");
-            }
-            self.syscall.subscribe(self.p.unwrap());
-            self.log.borrow_mut().push(2);
-        } else {
-            panic!("unknown message {}", message.name);
-        }
-    }
+// bootstrap makes a promise, sends it to vat2 (which subscribes to it), and
+// resolves it, either before or after sending it
 
-    fn deliver_promise(&mut self, _target: VatResolverID, _message: InboundVatMessage) {
-        panic!();
-    }
-
-    fn notify_fulfill_to_target(&mut self, id: VatPromiseID, target: VatResolveTarget) {
-        println!("Vat2.notify_fulfill_to_target {} {:?}", id, target);
-        assert_eq!(id, self.p.unwrap());
-        assert_eq!(target, VatResolveTarget::Import(VatImportID(0)));
-        self.log.borrow_mut().push(41);
-    }
-
-    fn notify_fulfill_to_data(&mut self, id: VatPromiseID, data: VatCapData) {
-        println!("Vat2.notify_fulfill_to_data {} {:?}", id, data);
-        assert_eq!(id, self.p.unwrap());
-        assert_eq!(data.body, b"p2data");
-        assert_eq!(
-            data.slots,
-            vec![
-                VatArgSlot::Import(VatImportID(0)),
-                VatArgSlot::Export(VatExportID(0)),
-            ]
-        );
-        self.log.borrow_mut().push(40);
-    }
-
-    fn notify_reject(&mut self, id: VatPromiseID, data: VatCapData) {
-        println!("Vat2.notify_reject {} {:?}", id, data);
-        assert_eq!(id, self.p.unwrap());
-        assert_eq!(data.body, b"p2data");
-        assert_eq!(
-            data.slots,
-            vec![
-                VatArgSlot::Import(VatImportID(0)),
-                VatArgSlot::Export(VatExportID(0)),
-            ]
-        );
-        self.log.borrow_mut().push(42);
-    }
+fn p2data(slots: Vec<VatArgSlot>) -> VatCapData {
+    VatCapData::new(b"p2data", slots)
 }
 
-fn do_test_subscribe_unresolved(mode: &str, expected_log: u32) {
+fn run(resolve_first: bool, mode: &'static str, expected: fn(VatPromiseID) -> Delivery) {
+    let log = EventLog::new();
     let mut cfg = Config::new();
-    let log: Vec<u32> = vec![];
-    let r = Rc::new(RefCell::new(log));
-    let r2 = r.clone();
-    let vn = VatName("bootstrap".to_string());
-    let setup = |syscall| -> Box<dyn Dispatch> {
-        Box::new(Vat1Dispatch {
-            syscall,
-            log: r2,
-            p: None,
-            r: None,
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let (p, r) = s.allocate_promise_and_resolver();
+            s.keep_promise("p", p);
+            s.keep_resolver("r", r);
         })
-    };
-    let sb: Box<Setup> = Box::new(setup);
-    cfg.add_vat(&vn, sb);
-
-    let r3 = r.clone();
-    let setup2 = |syscall| -> Box<dyn Dispatch> {
-        Box::new(Vat2Dispatch {
-            syscall,
-            log: r3,
-            p: None,
+        .on_method("send_promise", |s, _| {
+            let slots = vec![VatArgSlot::Promise(s.promise("p"))];
+            let vmsg = OutboundVatMessage::new("have_promise", b"body", slots);
+            s.send_only(VatSendTarget::Import(VatImportID(1)), vmsg);
+        })
+        .on_method(mode, move |s, _| {
+            let r = s.resolver("r");
+            let slots = vec![
+                VatArgSlot::Export(VatExportID(10)),
+                VatArgSlot::Import(VatImportID(1)),
+            ];
+            match mode {
+                "resolve_data" => s.fulfill_to_data(r, p2data(slots)),
+                "resolve_target" => {
+                    s.fulfill_to_target(r, VatResolveTarget::Export(VatExportID(10)))
+                }
+                _ => s.reject(r, p2data(slots)),
+            }
         })
-    };
-    let vn2 = VatName("vat2".to_string());
-    let sb2: Box<Setup> = Box::new(setup2);
-    cfg.add_vat(&vn2, sb2);
+        .install(&mut cfg);
+    ScriptedVat::new("vat2", &log)
+        .on_method("have_promise", |s, d| {
+            match d.message().unwrap().args.slots[..] {
+                [VatArgSlot::Promise(p)] => {
+                    s.keep_promise("p", p);
+                    s.subscribe(p);
+                }
+                ref slots => panic!("args.slots[0] was not a Promise: {:?}", slots),
+            }
+        })
+        .on_notify(move |s, d| assert_eq!(*d, expected(s.promise("p"))))
+        .install(&mut cfg);
 
     let mut c = Controller::new(cfg);
-    c.add_import(&vn, 1, &vn2, 0);
+    let vn = VatName("bootstrap".to_string());
+    c.add_import(&vn, 1, &VatName("vat2".to_string()), 0);
     c.start();
     c.run();
-    assert_eq!(*r.borrow(), vec![0]);
+    assert_eq!(log.take(), vec!["bootstrap: deliver bootstrap"]);
+
+    let deliver = |method| format!("bootstrap: deliver {}", method);
+    let have_promise = "vat2: deliver have_promise".to_string();
+    let notification = format!("vat2: {}", expected(VatPromiseID(0)));
+    if resolve_first {
+        c.push("bootstrap", 0, mode, b"body");
+        c.run();
+        assert_eq!(log.take(), vec![deliver(mode)]);
+        c.push("bootstrap", 0, "send_promise", b"body");
+        c.run();
+        assert_eq!(
+            log.take(),
+            vec![deliver("send_promise"), have_promise, notification]
+        );
+    } else {
+        c.push("bootstrap", 0, "send_promise", b"body");
+        c.run();
+        assert_eq!(log.take(), vec![deliver("send_promise"), have_promise]);
+        c.push("bootstrap", 0, mode, b"body");
+        c.run();
+        assert_eq!(log.take(), vec![deliver(mode), notification]);
+    }
+}
 
-    c.push("bootstrap", 0, "send_promise", b"body");
+fn data(promise: VatPromiseID) -> Delivery {
+    let slots = vec![
+        VatArgSlot::Import(VatImportID(0)),
+        VatArgSlot::Export(VatExportID(0)),
+    ];
+    Delivery::NotifyFulfillToData {
+        promise,
+        data: p2data(slots),
+    }
+}
 
-    c.run();
-    assert_eq!(*r.borrow(), vec![0, 1, 2]);
+fn target(promise: VatPromiseID) -> Delivery {
+    let target = VatResolveTarget::Import(VatImportID(0));
+    Delivery::NotifyFulfillToTarget { promise, target }
+}
 
-    c.push("bootstrap", 0, mode, b"body");
-    c.run();
-    assert_eq!(*r.borrow(), vec![0, 1, 2, 3, expected_log]);
+fn reject(promise: VatPromiseID) -> Delivery {
+    let slots = vec![
+        VatArgSlot::Import(VatImportID(0)),
+        VatArgSlot::Export(VatExportID(0)),
+    ];
+    Delivery::NotifyReject {
+        promise,
+        data: p2data(slots),
+    }
 }
 
 #[test]
 fn test_subscribe_unresolved_data() {
-    do_test_subscribe_unresolved("resolve_data", 40);
+    run(false, "resolve_data", data);
 }
 
 #[test]
 fn test_subscribe_unresolved_target() {
-    do_test_subscribe_unresolved("resolve_target", 41);
+    run(false, "resolve_target", target);
 }
 
 #[test]
 fn test_subscribe_unresolved_reject() {
-    do_test_subscribe_unresolved("reject", 42);
-}
-
-fn do_test_subscribe_resolved(mode: &str, expected_log: u32) {
-    let mut cfg = Config::new();
-    let log: Vec<u32> = vec![];
-    let r = Rc::new(RefCell::new(log));
-    let r2 = r.clone();
-    let vn = VatName("bootstrap".to_string());
-    let setup = |syscall| -> Box<dyn Dispatch> {
-        Box::new(Vat1Dispatch {
-            syscall,
-            log: r2,
-            p: None,
-            r: None,
-        })
-    };
-    let sb: Box<Setup> = Box::new(setup);
-    cfg.add_vat(&vn, sb);
-
-    let r3 = r.clone();
-    let setup2 = |syscall| -> Box<dyn Dispatch> {
-        Box::new(Vat2Dispatch {
-            syscall,
-            log: r3,
-            p: None,
-        })
-    };
-    let vn2 = VatName("vat2".to_string());
-    let sb2: Box<Setup> = Box::new(setup2);
-    cfg.add_vat(&vn2, sb2);
-
-    let mut c = Controller::new(cfg);
-    c.add_import(&vn, 1, &vn2, 0);
-    c.start();
-    c.run();
-    assert_eq!(*r.borrow(), vec![0]);
-
-    c.push("bootstrap", 0, mode, b"body");
-    c.run();
-    assert_eq!(*r.borrow(), vec![0, 3]);
-
-    c.push("bootstrap", 0, "send_promise", b"body");
-
-    c.run();
-    assert_eq!(*r.borrow(), vec![0, 3, 1, 2, expected_log]);
+    run(false, "reject", reject);
 }
 
 #[test]
 fn test_subscribe_fulfilled_to_target() {
-    do_test_subscribe_resolved("resolve_target", 41);
+    run(true, "resolve_target", target);
 }
 
 #[test]
 fn test_subscribe_fulfilled_to_data() {
-    do_test_subscribe_resolved("resolve_data", 40);
+    run(true, "resolve_data", data);
 }
 
 #[test]
 fn test_subscribe_rejected() {
-    do_test_subscribe_resolved("reject", 42);
+    run(true, "reject", reject);
 }
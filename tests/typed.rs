@@ -0,0 +1,124 @@
+//! Typed message arguments, which need the "serde" feature:
+//!
+//!     cargo test --features serde --test typed
+
+use serde::{Deserialize, Serialize};
+use swingset::{
+    from_capdata, to_capdata, Config, Controller, EventLog, InboundVatMessage,
+    OutboundVatMessage, ScriptedVat, Slot, VatArgSlot, VatCapData, VatExportID,
+    VatImportID, VatName, VatPromiseID, VatSendTarget,
+};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Kind {
+    Plain,
+    Counted(u32),
+    Named { name: String },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Offer {
+    purse: Slot,
+    amount: u64,
+    note: Option<String>,
+    kinds: Vec<Kind>,
+    result: Slot,
+}
+
+fn offer() -> Offer {
+    Offer {
+        purse: Slot(VatArgSlot::Import(VatImportID(4))),
+        amount: 10,
+        note: None,
+        kinds: vec![
+            Kind::Plain,
+            Kind::Counted(3),
+            Kind::Named {
+                name: "#1".to_string(),
+            },
+        ],
+        result: Slot(VatArgSlot::Promise(VatPromiseID(2))),
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let data = to_capdata(&offer()).unwrap();
+    assert_eq!(
+        String::from_utf8(data.body.clone()).unwrap(),
+        r##"#{"amount":"+10","kinds":["Plain",{"Counted":3},{"Named":{"name":"!#1"}}],"note":null,"purse":"$0","result":"&1"}"##
+    );
+    assert_eq!(
+        data.slots,
+        vec![
+            VatArgSlot::Import(VatImportID(4)),
+            VatArgSlot::Promise(VatPromiseID(2)),
+        ]
+    );
+    assert_eq!(from_capdata::<Offer>(&data), Ok(offer()));
+}
+
+#[test]
+fn test_mismatch() {
+    let data = to_capdata(&(1, "two")).unwrap();
+    assert!(from_capdata::<(String, u32)>(&data).is_err());
+    assert!(from_capdata::<Offer>(&data).is_err());
+    // a slot where something else was expected, and vice versa
+    let data = to_capdata(&Slot(VatArgSlot::Export(VatExportID(0)))).unwrap();
+    assert!(from_capdata::<u32>(&data).is_err());
+    let data = VatCapData::new(b"#\"$0\"", vec![]);
+    assert!(from_capdata::<Slot>(&data).is_err());
+    // map keys must be strings
+    let mut map = std::collections::BTreeMap::new();
+    map.insert(1, 2);
+    assert!(to_capdata(&map).is_err());
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Introduce {
+    name: String,
+    friend: Slot,
+}
+
+// the kernel translates slots between vats, underneath the typed arguments
+#[test]
+fn test_between_vats() {
+    let log = EventLog::new();
+    let mut cfg = Config::new();
+    ScriptedVat::new("bootstrap", &log)
+        .on_method("bootstrap", |s, _| {
+            let args = Introduce {
+                name: "carol".to_string(),
+                friend: Slot(VatArgSlot::Export(VatExportID(7))),
+            };
+            let vmsg = OutboundVatMessage::from_serde("introduce", &args).unwrap();
+            s.send_only(VatSendTarget::Import(VatImportID(1)), vmsg);
+        })
+        .install(&mut cfg);
+    ScriptedVat::new("bob", &log)
+        .on_method("introduce", |s, d| {
+            let message: &InboundVatMessage = d.message().unwrap();
+            let args: Introduce = message.decode().unwrap();
+            assert_eq!(args.name, "carol");
+            match args.friend {
+                Slot(VatArgSlot::Import(i)) => {
+                    s.log(&format!("friend is import {}", i.0))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        })
+        .install(&mut cfg);
+    let mut c = Controller::new(cfg);
+    let bootstrap = VatName("bootstrap".to_string());
+    c.add_import(&bootstrap, 1, &VatName("bob".to_string()), 0);
+    c.start();
+    c.run();
+    assert_eq!(
+        log.take(),
+        vec![
+            "bootstrap: deliver bootstrap",
+            "bob: deliver introduce",
+            "bob: friend is import 0",
+        ]
+    );
+}